@@ -0,0 +1,33 @@
+PROGRAM Arithmetic;
+VAR
+   i, total, scratch : INTEGER;
+   ratio, average    : REAL;
+
+PROCEDURE Unused(a : INTEGER; b : REAL);
+VAR
+   c : INTEGER;
+BEGIN
+   c := a * 2
+END;
+
+BEGIN {Arithmetic}
+   i := 1;
+   total := 0;
+   scratch := 7;
+   total := total + i * scratch;
+   i := i + 1;
+   total := total + i * scratch;
+   i := i + 1;
+   total := total + i * scratch;
+   i := i + 1;
+   total := total + i * scratch;
+   i := i + 1;
+   total := total + i * scratch;
+   scratch := (total div 3) mod 11 + -(scratch - total) * 2;
+   ratio := total / (i + 0.5);
+   average := (ratio + scratch) / 2 - 3.14 * (i - - 1);
+   BEGIN
+      total := total * 2 + scratch div 7;
+      scratch := scratch mod 5 + total div (i * i)
+   END
+END.  {Arithmetic}
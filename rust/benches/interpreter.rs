@@ -0,0 +1,86 @@
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+use spi::interpreting::interpreter::Interpreter;
+use spi::lexing::lexer::Lexer;
+use spi::lexing::token::Token;
+use spi::parsing::parser::Parser;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Counts heap allocations so benchmarks can report them next to their timings
+struct CountingAllocator;
+
+static ALLOCATIONS: AtomicUsize = AtomicUsize::new(0);
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        ALLOCATIONS.fetch_add(1, Ordering::Relaxed);
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+}
+
+#[global_allocator]
+static GLOBAL: CountingAllocator = CountingAllocator;
+
+fn allocations_during<T>(f: impl FnOnce() -> T) -> usize {
+    let before = ALLOCATIONS.load(Ordering::Relaxed);
+    black_box(f());
+    ALLOCATIONS.load(Ordering::Relaxed) - before
+}
+
+const PROGRAM: &str = include_str!("fixtures/arithmetic.pas");
+const NESTING_DEPTH: usize = 100;
+const REPL_LINE: &str = "7 + 3 * (10 div (12 DIV (3 + 1) - 1)) div (2 + 3) - 5 mod 3";
+
+fn end_to_end(c: &mut Criterion) {
+    c.bench_function("lex + parse + interpret", |b| {
+        b.iter(|| {
+            let ast = Parser::new(Lexer::new(black_box(PROGRAM))).parse().unwrap();
+            let mut interpreter = Interpreter::default();
+            interpreter.interpret(&ast).unwrap();
+            interpreter
+        })
+    });
+}
+
+fn nested_expression(c: &mut Criterion) {
+    let source = format!(
+        "{}1{}",
+        "(2 * ".repeat(NESTING_DEPTH),
+        " - 1)".repeat(NESTING_DEPTH)
+    );
+    let ast = Parser::new(Lexer::new(&source)).parse_expression().unwrap();
+    let interpreter = Interpreter::default();
+
+    c.bench_function("interpret_expression (nested)", |b| {
+        b.iter(|| interpreter.interpret_expression(black_box(&ast)).unwrap())
+    });
+}
+
+/// What a single line typed at the REPL costs to tokenize, and then to parse
+fn repl_line(c: &mut Criterion) {
+    let tokenize = || {
+        Lexer::new(black_box(REPL_LINE))
+            .take_while(|token| !matches!(token, Ok(Token::Eof)))
+            .count()
+    };
+    let parse = || {
+        Parser::new(Lexer::new(black_box(REPL_LINE)))
+            .parse_expression()
+            .unwrap()
+    };
+    println!(
+        "REPL line allocations: {} tokenizing, {} parsing",
+        allocations_during(tokenize),
+        allocations_during(parse)
+    );
+
+    c.bench_function("tokenize (REPL line)", |b| b.iter(tokenize));
+    c.bench_function("parse_expression (REPL line)", |b| b.iter(parse));
+}
+
+criterion_group!(benches, end_to_end, nested_expression, repl_line);
+criterion_main!(benches);
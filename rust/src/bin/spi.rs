@@ -1,16 +1,26 @@
-use anyhow::{Context, Ok, Result};
+use anyhow::{anyhow, bail, Context, Ok, Result};
 use clap::Parser as ClapParser;
 use cli_table::format::Justify;
 use cli_table::{print_stdout, Cell, Style, Table};
 use colored::*;
+use spi::interpreting::built_ins::Halt;
 use spi::interpreting::interpreter::Interpreter;
-use spi::interpreting::misc::{lisp_notation, rpn};
-use spi::interpreting::symbol_table::SymbolTable;
-use spi::interpreting::types::NumericType;
+use spi::interpreting::misc::{
+    first_difference, lisp_notation, node_counts, rpn, to_pascal_source, tree_string,
+};
+use spi::interpreting::output::LimitedOutput;
+use spi::interpreting::symbol_table::{Symbol, SymbolTable};
+use spi::interpreting::types::{NumericType, Value};
+use spi::lexing::highlight::highlight;
 use spi::lexing::lexer::Lexer;
+use spi::lexing::token::Token;
+use spi::parsing::ast::{normalize, Ast};
 use spi::parsing::parser::Parser;
+use std::fmt::{Display, Formatter};
 use std::io;
 use std::io::{BufRead, Write};
+use std::path::Path;
+use std::process::ExitCode;
 
 #[derive(ClapParser)]
 #[clap(author, version, about)]
@@ -30,38 +40,175 @@ struct CliArgs {
     /// Show everything
     #[clap(short('a'), long)]
     show_all: bool,
+
+    /// Show how many nodes of each kind the AST contains
+    #[clap(long)]
+    ast_stats: bool,
+
+    /// Count how many times each kind of node is evaluated
+    #[clap(long)]
+    profile: bool,
+
+    /// Enable debugging built-ins such as typeof
+    #[clap(long)]
+    debug: bool,
+
+    /// Reject implicit conversions between integers and reals
+    #[clap(long)]
+    strict: bool,
+
+    /// Write reals with this many decimals, in the program's output and the variables table
+    #[clap(long, value_name = "N")]
+    real_precision: Option<usize>,
+
+    /// Print the Pascal file with syntax highlighting instead of running it
+    #[clap(long, requires("path"))]
+    highlight: bool,
+
+    /// Rewrite the Pascal file in canonical layout instead of running it
+    #[clap(long, requires("path"))]
+    fmt: bool,
+
+    /// With --fmt, only check whether the file is already formatted, failing if not
+    #[clap(long, requires("fmt"))]
+    check: bool,
+
+    /// Never use colors, like setting NO_COLOR
+    #[clap(long)]
+    no_color: bool,
+
+    /// Encoding of the Pascal file
+    #[clap(long, arg_enum, default_value = "utf8")]
+    encoding: Encoding,
+
+    /// Pause before each statement of the program's main block, showing it and the variables
+    #[clap(long, requires("path"))]
+    step: bool,
+
+    /// After running the file, continue in the REPL with its variables
+    #[clap(short('i'), long, requires("path"))]
+    interactive: bool,
+
+    /// Write the program's output to this file instead of stdout
+    #[clap(long, parse(from_os_str))]
+    output: Option<std::path::PathBuf>,
+
+    /// Stop the program once it has written this many bytes of output
+    #[clap(long, value_name = "N")]
+    max_output_bytes: Option<usize>,
+
+    /// Instead of running anything, check whether two Pascal files parse to the same AST
+    #[clap(
+        long,
+        parse(from_os_str),
+        number_of_values = 2,
+        value_names = &["FILE1", "FILE2"],
+        conflicts_with = "path"
+    )]
+    ast_equal: Option<Vec<std::path::PathBuf>>,
+}
+
+#[derive(clap::ArgEnum, Clone, Copy)]
+enum Encoding {
+    Utf8,
+    Latin1,
 }
 
-fn main() -> Result<()> {
-    let args: CliArgs = CliArgs::parse();
+/// The step in which running a file failed, exiting with the step's number as exit code
+#[derive(Clone, Copy, Debug)]
+enum Phase {
+    Lexing = 2,
+    Parsing = 3,
+    Analysis = 4,
+    Running = 5,
+}
+
+impl Display for Phase {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Phase::Lexing => "Could not lex the program",
+            Phase::Parsing => "Could not parse the program",
+            Phase::Analysis => "The program is invalid",
+            Phase::Running => "The program failed",
+        })
+    }
+}
 
-    if args.path.is_some() {
-        let path = args.path.unwrap();
-        let content = std::fs::read_to_string(&path)
-            .with_context(|| format!("could not read file `{}`", &path.to_string_lossy()))?;
+/// Exits with 0 on success, the `Phase` that failed when running a file, the code given to
+/// `halt`, or 1 for anything else such as an unreadable file
+fn main() -> ExitCode {
+    match run(CliArgs::parse()) {
+        Result::Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("Error: {:?}", err);
+            ExitCode::from(err.downcast_ref::<Phase>().map_or(1, |phase| *phase as u8))
+        }
+    }
+}
 
-        let tokens = Lexer::new(&content);
-        let ast = Parser::new(tokens).parse()?;
-        let mut interpreter = Interpreter::new(args.show_symbols || args.show_all);
-        let output = interpreter.interpret(&ast);
+fn run(args: CliArgs) -> Result<()> {
+    if args.no_color {
+        colored::control::set_override(false);
+    }
+    if let Some(paths) = &args.ast_equal {
+        return compare_asts(&paths[0], &paths[1], args.encoding);
+    }
+    if let Some(path) = args.path {
+        let content = read_source(&path, args.encoding)?;
+        if args.highlight {
+            print!("{}", highlight(&content)?);
+            return Ok(());
+        }
+        if args.fmt {
+            return format_file(&path, &content, args.encoding, args.check);
+        }
+
+        // Lexing up front tells lexer errors apart from the parser's
+        for token in Lexer::new(&content) {
+            if token.context(Phase::Lexing)? == Token::Eof {
+                break;
+            }
+        }
+        let ast = normalize(
+            Parser::with_positions(Lexer::new(&content))
+                .parse()
+                .context(Phase::Parsing)?,
+        );
+        let symbol_table = SymbolTable::build_for(&ast, args.show_symbols || args.show_all)
+            .context(Phase::Analysis)?;
+        let mut interpreter = Interpreter::default();
+        interpreter.debug = args.debug;
+        interpreter.strict = args.strict;
+        interpreter.profile = args.profile;
+        interpreter.real_display_precision = args.real_precision;
+        interpreter.output = program_output(&args.output, args.max_output_bytes)?;
+        let output = if args.step {
+            step_through(&mut interpreter, &ast)
+        } else {
+            interpreter.interpret_with_table(&ast, &symbol_table)
+        };
+        io::stdout().flush()?;
 
         if args.show_tree || args.show_all {
-            println!("Tree:\n{:#?}", ast);
-            println!("\n");
+            println!("Tree:\n{}", tree_string(&ast, 0));
+        }
+        if args.ast_stats {
+            display_ast_stats(&ast)?;
+        }
+        if args.profile {
+            display_profile(&interpreter)?;
         }
         if args.show_symbols || args.show_all {
-            display_symbol_table(&interpreter.symbol_table.unwrap())?;
+            if let Some(symbol_table) = &interpreter.symbol_table {
+                display_symbol_table(symbol_table)?;
+            }
         }
         println!("\nVariables:");
         print_stdout(
-            interpreter
-                .global_scope
-                .iter()
-                .map(|(key, value)| {
-                    vec![
-                        key.to_string().cell().bold(true),
-                        value.to_string().cell().justify(Justify::Right),
-                    ]
+            variable_rows(&interpreter)
+                .into_iter()
+                .map(|(name, value)| {
+                    vec![name.cell().bold(true), value.cell().justify(Justify::Right)]
                 })
                 .table()
                 .title(vec![
@@ -69,41 +216,330 @@ fn main() -> Result<()> {
                     "Value".cell().bold(true),
                 ]),
         )?;
-        return output;
+        if args.interactive {
+            if let Err(err) = output {
+                eprintln!("{}: {:?}", "Error: ".red(), err);
+            }
+            return repl(interpreter);
+        }
+        return match output {
+            Err(err) => match err.downcast_ref::<Halt>() {
+                Some(halt) => {
+                    io::stdout().flush()?;
+                    std::process::exit(halt.code)
+                }
+                None => Err(err.context(Phase::Running)),
+            },
+            ok => ok,
+        };
     }
 
+    let mut interpreter = Interpreter::new(false);
+    interpreter.debug = args.debug;
+    interpreter.strict = args.strict;
+    interpreter.profile = args.profile;
+    interpreter.real_display_precision = args.real_precision;
+    interpreter.output = program_output(&args.output, args.max_output_bytes)?;
+    repl(interpreter)
+}
+
+/// Reads and evaluates lines until the end of the input. With `--profile`, every result is
+/// followed by the node counts of the session so far.
+fn repl(mut interpreter: Interpreter) -> Result<()> {
     loop {
         print!("calc > ");
         io::stdout().flush()?;
 
         let stdin = io::stdin();
-        let line = stdin.lock().lines().next().expect("could not read line")?;
+        let line = match stdin.lock().lines().next() {
+            Some(line) => line.context("could not read line")?,
+            None => return Ok(()),
+        };
 
-        match line_to_result(line) {
+        if let Some(command) = line.trim().strip_prefix(':') {
+            match run_repl_command(&mut interpreter, command) {
+                Result::Ok(message) => println!("{}\n", message),
+                Err(err) => eprintln!("{}: {:?}", "Error: ".red(), err),
+            }
+            continue;
+        }
+
+        match line_to_result(line, &interpreter) {
             Result::Ok((result, ast_debug, rpn_output, lisp_output)) => {
-                println!("{}: {}", "Result".green().bold(), result.to_string().bold());
+                println!(
+                    "{}: {}",
+                    "Result".green().bold(),
+                    interpreter.format_value(&result).bold()
+                );
                 println!("AST: {}", ast_debug);
                 println!("RPN: {}", rpn_output);
                 println!("Lisp: {}", lisp_output);
                 println!();
+                if interpreter.profile {
+                    display_profile(&interpreter)?;
+                }
             }
             Err(err) => eprintln!("{}: {:?}", "Error: ".red(), err),
         }
     }
 }
 
-fn line_to_result(line: String) -> Result<(NumericType, String, String, String)> {
+/// Runs the program one statement at a time: Enter runs the next statement, `c` runs the rest
+/// without pausing and `q` stops the program. Commands come from the interpreter's input, so they
+/// interleave with what the program reads through `readln`.
+fn step_through(interpreter: &mut Interpreter, ast: &Ast) -> Result<()> {
+    let mut pausing = true;
+    interpreter.interpret_stepping(ast, |interpreter, statement| {
+        if !pausing {
+            return Ok(true);
+        }
+        println!(
+            "{} {}",
+            "Next:".bold(),
+            to_pascal_source(statement).trim_end()
+        );
+        let mut variables: Vec<(String, String)> = interpreter
+            .global_scope
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        variables.sort();
+        for (name, value) in variables {
+            println!("  {} = {}", name, value);
+        }
+        print!("step [Enter], continue [c] or quit [q] > ");
+        io::stdout().flush()?;
+
+        let mut command = String::new();
+        let command = match interpreter.input.read_line(&mut command)? {
+            0 => None,
+            _ => Some(command),
+        };
+        match command.as_deref().map(str::trim) {
+            Some("q") => Ok(false),
+            Some("c") | None => {
+                pausing = false;
+                Ok(true)
+            }
+            Some(_) => Ok(true),
+        }
+    })
+}
+
+/// Where `write` and `writeln` go: the `--output` file if given, otherwise stdout, bounded by
+/// `--max-output-bytes`
+fn program_output(
+    path: &Option<std::path::PathBuf>,
+    max_bytes: Option<usize>,
+) -> Result<Box<dyn Write + Send>> {
+    let output: Box<dyn Write + Send> = match path {
+        Some(path) => Box::new(io::BufWriter::new(
+            std::fs::File::create(path).with_context(|| {
+                format!("could not create output file `{}`", path.to_string_lossy())
+            })?,
+        )),
+        None => Box::new(io::stdout()),
+    };
+    Ok(match max_bytes {
+        Some(limit) => Box::new(LimitedOutput::new(output, limit)),
+        None => output,
+    })
+}
+
+/// Reports whether two files parse to the same AST, exiting with 1 if they don't
+fn compare_asts(first: &Path, second: &Path, encoding: Encoding) -> Result<()> {
+    let parse = |path: &Path| -> Result<Ast> {
+        let content = read_source(path, encoding)?;
+        Ok(normalize(Parser::new(Lexer::new(&content)).parse()?))
+    };
+    match first_difference(&parse(first)?, &parse(second)?) {
+        None => {
+            println!("The ASTs are equal");
+            Ok(())
+        }
+        Some(difference) => {
+            println!("The ASTs differ at {}", difference);
+            io::stdout().flush()?;
+            std::process::exit(1)
+        }
+    }
+}
+
+/// Rewrites a Pascal file as `to_pascal_source` lays it out, or with `check` only tells whether
+/// it already is laid out so, exiting with 1 if not. Files with comments are left alone as the
+/// tree has no place for them.
+fn format_file(path: &Path, content: &str, encoding: Encoding, check: bool) -> Result<()> {
+    if has_comments(content)? {
+        bail!(
+            "cannot format `{}`, its comments would be lost",
+            path.to_string_lossy()
+        );
+    }
+    let formatted = to_pascal_source(&normalize(Parser::new(Lexer::new(content)).parse()?));
+    if formatted == content {
+        return Ok(());
+    }
+    if check {
+        println!("{} is not formatted", path.to_string_lossy());
+        io::stdout().flush()?;
+        std::process::exit(1)
+    }
+    let bytes = match encoding {
+        Encoding::Utf8 => formatted.into_bytes(),
+        // Every character came from a Latin-1 byte of the same value
+        Encoding::Latin1 => formatted.chars().map(|c| c as u8).collect(),
+    };
+    std::fs::write(path, bytes)
+        .with_context(|| format!("could not write file `{}`", path.to_string_lossy()))
+}
+
+/// Whether anything but whitespace lies between the tokens of `source`, i.e. comments
+fn has_comments(source: &str) -> Result<bool> {
+    let mut end = 0;
+    for spanned in Lexer::new(source).spanned() {
+        let spanned = spanned?;
+        if !source[end..spanned.offset].trim().is_empty() {
+            return Ok(true);
+        }
+        if spanned.token == Token::Eof {
+            break;
+        }
+        end = spanned.offset + spanned.lexeme.len();
+    }
+    Ok(false)
+}
+
+/// Reads a source file, decoding it as `encoding`
+fn read_source(path: &Path, encoding: Encoding) -> Result<String> {
+    let bytes = std::fs::read(path)
+        .with_context(|| format!("could not read file `{}`", path.to_string_lossy()))?;
+    match encoding {
+        Encoding::Utf8 => String::from_utf8(bytes).map_err(|err| {
+            anyhow!(
+                "source file `{}` is not valid UTF-8 (at byte {}); please re-encode it as UTF-8 \
+                 or pass --encoding latin1",
+                path.to_string_lossy(),
+                err.utf8_error().valid_up_to()
+            )
+        }),
+        // Every Latin-1 byte is the code point of the same value
+        Encoding::Latin1 => Ok(bytes.into_iter().map(char::from).collect()),
+    }
+}
+
+fn line_to_result(
+    line: String,
+    interpreter: &Interpreter,
+) -> Result<(Value, String, String, String)> {
     let tokens = Lexer::new(&line);
     let ast = Parser::new(tokens).parse_expression()?;
 
     Ok((
-        Interpreter::new(false).interpret_expression(&ast)?,
+        interpreter.evaluate(&ast)?,
         format!("{:?}", ast),
         rpn(&ast),
         lisp_notation(&ast),
     ))
 }
 
+/// Runs a REPL command given without its leading `:`, e.g. `load part10.pas`
+fn run_repl_command(interpreter: &mut Interpreter, command: &str) -> Result<String> {
+    let (name, argument) = command
+        .split_once(char::is_whitespace)
+        .map(|(name, argument)| (name, argument.trim()))
+        .unwrap_or((command, ""));
+    match name {
+        "load" if !argument.is_empty() => {
+            let content = read_source(Path::new(argument), Encoding::Utf8)?;
+            let ast = normalize(Parser::with_positions(Lexer::new(&content)).parse()?);
+            interpreter.interpret(&ast)?;
+            Ok(format!("Loaded {}", argument))
+        }
+        "load" => anyhow::bail!("Usage: :load <file>"),
+        "reset" => {
+            interpreter.reset();
+            Ok("Reset the interpreter".to_string())
+        }
+        _ => anyhow::bail!("Unknown command :{}", name),
+    }
+}
+
+/// How many decimals reals show in the variables table
+const REAL_DECIMALS: usize = 6;
+
+/// Shown in the variables table for a declared variable that was never assigned
+const UNINITIALIZED: &str = "<uninitialized>";
+
+/// The global variables sorted by name, with their values formatted for a right-justified
+/// column: reals get the interpreter's `real_display_precision` or else `REAL_DECIMALS` decimals
+/// and integers are padded to end where the reals' integer parts do, so the decimal points line up.
+/// Variables the symbol table knows of but that have no value yet show as `UNINITIALIZED`.
+fn variable_rows(interpreter: &Interpreter) -> Vec<(String, String)> {
+    let decimals = interpreter.real_display_precision.unwrap_or(REAL_DECIMALS);
+    let padding = if decimals == 0 { 0 } else { decimals + 1 };
+    let any_real = interpreter
+        .global_scope
+        .iter()
+        .any(|(_, value)| matches!(value, Value::Numeric(NumericType::Real(_))));
+    let mut rows: Vec<(String, String)> = interpreter
+        .global_scope
+        .iter()
+        .map(|(name, value)| {
+            let value = match value {
+                Value::Numeric(NumericType::Real(r)) => format!("{:.*}", decimals, r),
+                Value::Numeric(NumericType::Integer(i)) if any_real => {
+                    format!("{}{}", i, " ".repeat(padding))
+                }
+                value => interpreter.format_value(value),
+            };
+            (name.to_string(), value)
+        })
+        .collect();
+    if let Some(symbol_table) = &interpreter.symbol_table {
+        rows.extend(
+            symbol_table
+                .symbols
+                .iter()
+                .filter_map(|(_, symbol)| match symbol {
+                    Symbol::Variable { name, .. }
+                        if !interpreter.global_scope.contains_key(name.as_str()) =>
+                    {
+                        Some((name.clone(), UNINITIALIZED.to_string()))
+                    }
+                    _ => None,
+                }),
+        );
+    }
+    rows.sort();
+    rows
+}
+
+fn display_ast_stats(ast: &Ast) -> std::io::Result<()> {
+    println!("\nAST Stats:\n");
+    print_stdout(
+        node_counts(ast)
+            .into_iter()
+            .map(|(kind, count)| vec![kind.cell().bold(true), count.cell().justify(Justify::Right)])
+            .table()
+            .title(vec!["Node".cell().bold(true), "Count".cell().bold(true)]),
+    )
+}
+
+fn display_profile(interpreter: &Interpreter) -> std::io::Result<()> {
+    println!("\nProfile:\n");
+    print_stdout(
+        interpreter
+            .node_visits()
+            .into_iter()
+            .map(|(kind, count)| vec![kind.cell().bold(true), count.cell().justify(Justify::Right)])
+            .table()
+            .title(vec![
+                "Node".cell().bold(true),
+                "Evaluated".cell().bold(true),
+            ]),
+    )
+}
+
 fn display_symbol_table(symbol_table: &SymbolTable) -> std::io::Result<()> {
     println!("\nSymbol Table:\n");
     println!("Scope Name: {}", symbol_table.scope_name);
@@ -121,7 +557,12 @@ fn display_symbol_table(symbol_table: &SymbolTable) -> std::io::Result<()> {
             })
             .table()
             .title(vec!["Name".cell().bold(true), "Symbol".cell().bold(true)]),
-    )
+    )?;
+
+    symbol_table
+        .nested_scopes
+        .iter()
+        .try_for_each(display_symbol_table)
 }
 
 // based on https://stackoverflow.com/a/34666891
@@ -132,8 +573,8 @@ macro_rules! interpreter_tests {
         fn $name() -> Result<()>{
             let (input, expected) = $value;
 
-            let actual = line_to_result(input.to_owned())?.0;
-            assert_eq!(actual, expected);
+            let actual = line_to_result(input.to_owned(), &Interpreter::default())?.0;
+            assert_eq!(actual, Value::from(expected));
             Ok(())
         }
     )*
@@ -146,4 +587,100 @@ interpreter_tests! {
     test_parenthesis: ("(1 + 3) * 5", NumericType::Integer(20)),
     test_nested_parenthesis: ("7 + 3 * (10 div (12 Div (3 + 1) - 1)) dIV (2 + 3) - 5 - 3 + (8)", NumericType::Integer(10)),
     test_unary_operations: ("5 - - - + - (3 + 4) - +2", NumericType::Integer(10)),
+    test_modulo: ("17 mod 5 + 10 MOD 3", NumericType::Integer(3)),
+}
+
+#[test]
+fn test_load_command() -> Result<()> {
+    let mut interpreter = Interpreter::default();
+
+    let message = run_repl_command(&mut interpreter, "load tests/fixtures/load.pas")?;
+    assert_eq!(message, "Loaded tests/fixtures/load.pas");
+    let result = line_to_result("total * 2".to_string(), &interpreter)?.0;
+    assert_eq!(result, Value::from(NumericType::Integer(30)));
+
+    assert!(run_repl_command(&mut interpreter, "load tests/fixtures/missing.pas").is_err());
+    assert!(run_repl_command(&mut interpreter, "load").is_err());
+    assert!(run_repl_command(&mut interpreter, "unknown").is_err());
+    let result = line_to_result("total".to_string(), &interpreter)?.0;
+    assert_eq!(result, Value::from(NumericType::Integer(15)));
+
+    run_repl_command(&mut interpreter, "reset")?;
+    assert!(line_to_result("total".to_string(), &interpreter).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_load_command_with_arrays() -> Result<()> {
+    let mut interpreter = Interpreter::default();
+
+    run_repl_command(&mut interpreter, "load tests/fixtures/load_array.pas")?;
+    let (value, _, rpn, lisp) = line_to_result("squares[2] + 1".to_string(), &interpreter)?;
+    assert_eq!(value, Value::from(NumericType::Integer(5)));
+    assert_eq!(rpn, "squares 2 [] 1 +");
+    assert_eq!(lisp, "(+ (index squares 2) 1)");
+    Ok(())
+}
+
+#[test]
+fn test_variable_rows_align_numbers() -> Result<()> {
+    let mut interpreter = Interpreter::default();
+    interpreter.interpret(
+        &Parser::new(Lexer::new(
+            "PROGRAM Mixed; VAR count, total : INTEGER; ratio, tiny : REAL; name : STRING;
+         BEGIN count := 42; total := -7; ratio := 20 / 7; tiny := 0.5; name := 'mixed' END.",
+        ))
+        .parse()?,
+    )?;
+    assert_eq!(
+        variable_rows(&interpreter),
+        [
+            ("count", "42       "),
+            ("name", "mixed"),
+            ("ratio", "2.857143"),
+            ("tiny", "0.500000"),
+            ("total", "-7       "),
+        ]
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+    );
+
+    let mut interpreter = Interpreter::default();
+    interpreter.interpret(
+        &Parser::new(Lexer::new(
+            "PROGRAM Integers; VAR a : INTEGER; BEGIN a := 3 END.",
+        ))
+        .parse()?,
+    )?;
+    assert_eq!(
+        variable_rows(&interpreter),
+        [("a".to_string(), "3".to_string())]
+    );
+
+    let mut interpreter = Interpreter::default();
+    interpreter.interpret(
+        &Parser::new(Lexer::new(
+            "PROGRAM Empty; VAR x : INTEGER; r : REAL; BEGIN END.",
+        ))
+        .parse()?,
+    )?;
+    assert!(interpreter.global_scope.is_empty());
+    assert_eq!(
+        variable_rows(&interpreter),
+        [("r", UNINITIALIZED), ("x", UNINITIALIZED)]
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+    );
+
+    let mut interpreter = Interpreter::default();
+    interpreter.real_display_precision = Some(2);
+    interpreter.interpret(
+        &Parser::new(Lexer::new(
+            "PROGRAM Rounded; VAR a : INTEGER; r : REAL; BEGIN a := 3; r := 20 / 7 END.",
+        ))
+        .parse()?,
+    )?;
+    assert_eq!(
+        variable_rows(&interpreter),
+        [("a", "3   "), ("r", "2.86")].map(|(name, value)| (name.to_string(), value.to_string()))
+    );
+    Ok(())
 }
@@ -0,0 +1,379 @@
+use crate::interpreting::interpreter::Interpreter;
+use crate::interpreting::types::{Enumeration, NumericType, Value};
+use crate::parsing::ast::{Ast, TypeSpec};
+use crate::{IntegerMachineType, RealMachineType};
+use anyhow::{bail, Context, Result};
+use std::fmt::{Display, Formatter};
+use std::io::Write;
+use std::rc::Rc;
+
+/// Procedures provided by the interpreter rather than declared by the program
+pub const BUILT_IN_PROCEDURES: &[&str] = &["assert", "halt", "readln", "write", "writeln"];
+
+/// Functions provided by the interpreter. `typeof` is only callable in debug mode.
+pub const BUILT_IN_FUNCTIONS: &[&str] = &[
+    "abs", "copy", "cos", "exp", "high", "length", "ln", "low", "ord", "pos", "pred", "sin", "sqr",
+    "sqrt", "succ", "typeof",
+];
+
+/// Returned as the error when a program calls `halt`, stopping it with `code` as its exit code
+#[derive(Debug, PartialEq)]
+pub struct Halt {
+    pub code: i32,
+}
+
+impl Display for Halt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Program halted with exit code {}", self.code)
+    }
+}
+
+impl std::error::Error for Halt {}
+
+pub(crate) fn is_built_in_procedure(name: &str) -> bool {
+    BUILT_IN_PROCEDURES
+        .iter()
+        .any(|built_in| built_in.eq_ignore_ascii_case(name))
+}
+
+pub(crate) fn is_built_in_function(name: &str) -> bool {
+    BUILT_IN_FUNCTIONS
+        .iter()
+        .any(|built_in| built_in.eq_ignore_ascii_case(name))
+}
+
+fn expect_arguments(name: &str, arguments: &[Ast], count: usize) -> Result<()> {
+    if arguments.len() != count {
+        bail!(
+            "{} expects {} argument(s), found {}",
+            name,
+            count,
+            arguments.len()
+        );
+    }
+    Ok(())
+}
+
+impl Interpreter {
+    pub(super) fn call_built_in_procedure(&mut self, name: &str, arguments: &[Ast]) -> Result<()> {
+        match name.to_ascii_lowercase().as_str() {
+            "halt" => {
+                let code = match arguments {
+                    [] => 0,
+                    [code] => match self.interpret_expression(code)? {
+                        NumericType::Integer(code) => code,
+                        real => bail!("halt expects an integer exit code, found {}", real),
+                    },
+                    _ => expect_arguments(name, arguments, 1).map(|_| 0)?,
+                };
+                Err(Halt { code }.into())
+            }
+            "assert" => self.assert(name, arguments),
+            "readln" => self.readln(arguments),
+            "write" => self.write(arguments),
+            "writeln" => {
+                self.write(arguments)?;
+                writeln!(self.output)?;
+                Ok(())
+            }
+            _ => bail!("Unknown built-in procedure: {}", name),
+        }
+    }
+
+    /// assert(condition) or assert(condition, message) stops the program with an error unless
+    /// the condition holds, making test programs self-checking
+    fn assert(&mut self, name: &str, arguments: &[Ast]) -> Result<()> {
+        let (condition, message) = match arguments {
+            [condition] => (condition, None),
+            [condition, message] => (condition, Some(message)),
+            _ => bail!(
+                "{} expects 1 or 2 arguments, found {}",
+                name,
+                arguments.len()
+            ),
+        };
+        let holds = match self.evaluate(condition)? {
+            Value::Boolean(holds) => holds,
+            value => bail!(
+                "assert expects a boolean condition, found the {} {}",
+                value.type_name(),
+                value
+            ),
+        };
+        if !holds {
+            match message {
+                Some(message) => {
+                    bail!("Assertion failed: {}", self.string_argument(name, message)?)
+                }
+                None => bail!("Assertion failed"),
+            }
+        }
+        Ok(())
+    }
+
+    /// write(e1, e2, ...) prints each argument's value without separators, so a bare `write`
+    /// prints nothing and a bare `writeln` only ends the line
+    fn write(&mut self, arguments: &[Ast]) -> Result<()> {
+        for argument in arguments {
+            let value = self.evaluate(argument)?;
+            write!(self.output, "{}", self.format_value(&value))?;
+        }
+        Ok(())
+    }
+
+    pub(super) fn call_built_in_function(&self, name: &str, arguments: &[Ast]) -> Result<Value> {
+        match name.to_ascii_lowercase().as_str() {
+            "abs" => {
+                expect_arguments(name, arguments, 1)?;
+                Ok(Value::Numeric(
+                    match self.interpret_expression(&arguments[0])? {
+                        NumericType::Integer(i) => NumericType::Integer(
+                            i.checked_abs()
+                                .with_context(|| format!("abs({}) overflows an integer", i))?,
+                        ),
+                        NumericType::Real(r) => NumericType::Real(r.abs()),
+                    },
+                ))
+            }
+            "sqr" => {
+                expect_arguments(name, arguments, 1)?;
+                Ok(Value::Numeric(
+                    match self.interpret_expression(&arguments[0])? {
+                        NumericType::Integer(i) => NumericType::Integer(
+                            i.checked_mul(i)
+                                .with_context(|| format!("sqr({}) overflows an integer", i))?,
+                        ),
+                        NumericType::Real(r) => NumericType::Real(r * r),
+                    },
+                ))
+            }
+            "sqrt" => {
+                expect_arguments(name, arguments, 1)?;
+                let argument = self.interpret_expression(&arguments[0])?.as_real();
+                if argument < 0.0 {
+                    bail!("sqrt of the negative number {}", argument);
+                }
+                Ok(Value::Numeric(NumericType::Real(argument.sqrt())))
+            }
+            "ln" => {
+                expect_arguments(name, arguments, 1)?;
+                let argument = self.interpret_expression(&arguments[0])?.as_real();
+                if argument <= 0.0 {
+                    bail!("ln of the non-positive number {}", argument);
+                }
+                Ok(Value::Numeric(NumericType::Real(argument.ln())))
+            }
+            "sin" => self.real_function(name, arguments, RealMachineType::sin),
+            "cos" => self.real_function(name, arguments, RealMachineType::cos),
+            "exp" => self.real_function(name, arguments, RealMachineType::exp),
+            "low" | "high" => {
+                expect_arguments(name, arguments, 1)?;
+                let (lower, upper) = self.array_bounds(name, &arguments[0])?;
+                let bound = if name.eq_ignore_ascii_case("low") {
+                    lower
+                } else {
+                    upper
+                };
+                Ok(NumericType::Integer(bound).into())
+            }
+            "ord" => {
+                expect_arguments(name, arguments, 1)?;
+                Ok(NumericType::Integer(self.ordinal_argument(name, &arguments[0])?).into())
+            }
+            "succ" | "pred" => {
+                expect_arguments(name, arguments, 1)?;
+                let step = if name.eq_ignore_ascii_case("succ") {
+                    1
+                } else {
+                    -1
+                };
+                match self.evaluate(&arguments[0])? {
+                    Value::Enumerated {
+                        ordinal,
+                        enumeration,
+                    } => {
+                        let next = ordinal + step;
+                        if next < 0 || next as usize >= enumeration.variants.len() {
+                            bail!(
+                                "{}({}) is outside of the type {}",
+                                name,
+                                enumeration.variants[ordinal as usize],
+                                enumeration.name
+                            );
+                        }
+                        Ok(Value::Enumerated {
+                            ordinal: next,
+                            enumeration,
+                        })
+                    }
+                    Value::Numeric(NumericType::Integer(ordinal)) => Ok(NumericType::Integer(
+                        ordinal.checked_add(step).with_context(|| {
+                            format!("{}({}) overflows an integer", name, ordinal)
+                        })?,
+                    )
+                    .into()),
+                    other => bail!(
+                        "{} expects an ordinal value, found {}",
+                        name,
+                        other.type_name()
+                    ),
+                }
+            }
+            "length" => {
+                expect_arguments(name, arguments, 1)?;
+                let s = self.string_argument(name, &arguments[0])?;
+                Ok(NumericType::Integer(s.chars().count() as IntegerMachineType).into())
+            }
+            "pos" => {
+                expect_arguments(name, arguments, 2)?;
+                let substring = self.string_argument(name, &arguments[0])?;
+                let s = self.string_argument(name, &arguments[1])?;
+                let position = match s.find(&substring) {
+                    Some(byte) if !substring.is_empty() => s[..byte].chars().count() + 1,
+                    _ => 0,
+                };
+                Ok(NumericType::Integer(position as IntegerMachineType).into())
+            }
+            "copy" => {
+                expect_arguments(name, arguments, 3)?;
+                let s = self.string_argument(name, &arguments[0])?;
+                let start = self.integer_argument(name, &arguments[1])?;
+                let count = self.integer_argument(name, &arguments[2])?;
+                // Like Turbo Pascal, out of range positions are clamped rather than errors
+                let skipped = start.max(1) as usize - 1;
+                let taken = count.max(0) as usize;
+                Ok(Value::String(s.chars().skip(skipped).take(taken).collect()))
+            }
+            "typeof" if self.debug => {
+                expect_arguments(name, arguments, 1)?;
+                let value = self.evaluate(&arguments[0])?;
+                Ok(Value::String(value.type_name().to_string()))
+            }
+            _ => bail!("Unknown function: {}", name),
+        }
+    }
+
+    /// Applies `function` to a single numeric argument, always giving a real
+    fn real_function(
+        &self,
+        name: &str,
+        arguments: &[Ast],
+        function: fn(RealMachineType) -> RealMachineType,
+    ) -> Result<Value> {
+        expect_arguments(name, arguments, 1)?;
+        let argument = self.interpret_expression(&arguments[0])?.as_real();
+        Ok(Value::Numeric(NumericType::Real(function(argument))))
+    }
+
+    /// The declared index bounds of the array variable passed as `argument`
+    fn array_bounds(
+        &self,
+        name: &str,
+        argument: &Ast,
+    ) -> Result<(IntegerMachineType, IntegerMachineType)> {
+        let variable = match argument {
+            Ast::Variable(variable) => variable,
+            argument => bail!("{} expects an array variable, found {:?}", name, argument),
+        };
+        match self.value_of(&variable.name) {
+            Some(Value::Array { lower, elements }) => {
+                // Adding the length last keeps the bounds of an array ending at the largest integer
+                // in range
+                Ok((*lower, *lower + (elements.len() as IntegerMachineType - 1)))
+            }
+            Some(value) => bail!(
+                "{} expects an array, but {} is {}",
+                name,
+                variable.name,
+                value.type_name()
+            ),
+            None => bail!("{} not defined", variable.name),
+        }
+    }
+
+    fn string_argument(&self, name: &str, argument: &Ast) -> Result<String> {
+        match self.evaluate(argument)? {
+            Value::String(s) => Ok(s),
+            other => bail!("{} expects a string, found {}", name, other.type_name()),
+        }
+    }
+
+    /// The position of an integer, boolean or enumerated value among the values of its type
+    fn ordinal_argument(&self, name: &str, argument: &Ast) -> Result<IntegerMachineType> {
+        match self.evaluate(argument)? {
+            Value::Numeric(NumericType::Integer(i)) => Ok(i),
+            Value::Boolean(b) => Ok(IntegerMachineType::from(b)),
+            Value::Enumerated { ordinal, .. } => Ok(ordinal),
+            other => bail!(
+                "{} expects an ordinal value, found {}",
+                name,
+                other.type_name()
+            ),
+        }
+    }
+
+    fn integer_argument(&self, name: &str, argument: &Ast) -> Result<IntegerMachineType> {
+        match self.interpret_expression(argument)? {
+            NumericType::Integer(i) => Ok(i),
+            real => bail!("{} expects an integer, found the real {}", name, real),
+        }
+    }
+
+    /// readln(v1, v2, ...) reads one line, assigning its whitespace separated values in order.
+    /// Without arguments the line is just consumed.
+    fn readln(&mut self, arguments: &[Ast]) -> Result<()> {
+        let mut line = String::new();
+        self.input
+            .read_line(&mut line)
+            .context("readln could not read from input")?;
+
+        let mut values = line.split_whitespace();
+        for argument in arguments {
+            let variable = argument.variable()?;
+            let text = values
+                .next()
+                .with_context(|| format!("readln: no value left for {}", variable.name))?;
+            let value = self.parse_input(&variable.name, text)?;
+            self.assign(&variable.name, value)?;
+        }
+        Ok(())
+    }
+
+    /// Reads `text` as a value of the variable's declared type. Enumerated values are read by
+    /// the name of their variant, and variables of unknown type read integers.
+    fn parse_input(&self, variable_name: &str, text: &str) -> Result<Value> {
+        let not_a = |type_name: &str| {
+            format!(
+                "readln: {:?} is not {} for {}",
+                text, type_name, variable_name
+            )
+        };
+        Ok(match self.declared_type_spec(variable_name) {
+            Some(TypeSpec::Real) => Value::Numeric(NumericType::Real(
+                text.parse::<RealMachineType>()
+                    .with_context(|| not_a("a real"))?,
+            )),
+            Some(TypeSpec::String) => Value::String(text.to_string()),
+            Some(TypeSpec::Boolean) => match text.to_lowercase().as_str() {
+                "true" => Value::Boolean(true),
+                "false" => Value::Boolean(false),
+                _ => bail!(not_a("a boolean")),
+            },
+            Some(TypeSpec::Enumeration { name, variants }) => {
+                let ordinal = variants
+                    .iter()
+                    .position(|variant| variant.eq_ignore_ascii_case(text))
+                    .with_context(|| not_a(&format!("a {}", name)))?;
+                Value::Enumerated {
+                    ordinal: IntegerMachineType::try_from(ordinal)?,
+                    enumeration: Rc::new(Enumeration { name, variants }),
+                }
+            }
+            Some(TypeSpec::Array { .. }) => bail!("readln cannot read the array {}", variable_name),
+            Some(TypeSpec::Integer) | None => Value::Numeric(NumericType::Integer(
+                text.parse::<IntegerMachineType>()
+                    .with_context(|| not_a("an integer"))?,
+            )),
+        })
+    }
+}
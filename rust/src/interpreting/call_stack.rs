@@ -0,0 +1,263 @@
+use crate::interpreting::interpreter::Interpreter;
+use crate::interpreting::types::Value;
+use crate::parsing::ast::{Ast, TypeSpec};
+use anyhow::{anyhow, bail, Result};
+use case_insensitive_hashmap::CaseInsensitiveHashMap;
+use std::rc::Rc;
+
+/// How many procedures may be running at once, so runaway recursion fails with an error
+/// instead of overflowing the interpreter's own stack
+pub const MAX_CALL_DEPTH: usize = 200;
+
+/// Where a variable's value is stored
+#[derive(Clone, Debug, PartialEq)]
+pub(super) enum Slot {
+    Global(String),
+    /// A parameter or local variable of the procedure running in `call_stack[frame]`
+    Local {
+        frame: usize,
+        name: String,
+    },
+}
+
+/// The activation record of a running procedure
+pub(super) struct Frame {
+    /// The frame of the procedure this one is declared in, `None` when declared globally
+    parent: Option<usize>,
+    /// Declared types of the parameters and local variables
+    types: CaseInsensitiveHashMap<TypeSpec>,
+    /// Values of the value parameters and local variables, `None` until assigned
+    values: CaseInsensitiveHashMap<Option<Value>>,
+    /// Where the caller's variable passed to each VAR parameter lives
+    references: CaseInsensitiveHashMap<Slot>,
+    /// Procedures declared within this procedure
+    procedures: CaseInsensitiveHashMap<Rc<Ast>>,
+}
+
+impl Frame {
+    fn new(parent: Option<usize>) -> Frame {
+        Frame {
+            parent,
+            types: CaseInsensitiveHashMap::new(),
+            values: CaseInsensitiveHashMap::new(),
+            references: CaseInsensitiveHashMap::new(),
+            procedures: CaseInsensitiveHashMap::new(),
+        }
+    }
+}
+
+impl Interpreter {
+    /// Runs a procedure declared by the program. Value parameters get copies of their
+    /// arguments, while VAR parameters alias the caller's variables so writes propagate back.
+    pub(super) fn call_procedure(&mut self, name: &str, arguments: &[Ast]) -> Result<()> {
+        let (declaration, parent) = self
+            .lookup_procedure(name)
+            .ok_or_else(|| anyhow!("Unknown procedure: {}", name))?;
+        let (parameters, block) = match declaration.as_ref() {
+            Ast::ProcedureDeclaration {
+                parameters, block, ..
+            } => (parameters, block),
+            node => bail!("Expected a procedure declaration, was {:?}", node),
+        };
+        if arguments.len() != parameters.len() {
+            bail!(
+                "{} expects {} argument(s), found {}",
+                name,
+                parameters.len(),
+                arguments.len()
+            );
+        }
+
+        let mut frame = Frame::new(parent);
+        let mut value_parameters = vec![];
+        for (parameter, argument) in parameters.iter().zip(arguments) {
+            let (variable, type_spec, by_ref) = match parameter {
+                Ast::Parameter {
+                    variable,
+                    type_spec,
+                    by_ref,
+                } => (variable.variable()?, type_spec.type_spec()?, *by_ref),
+                node => bail!("Expected a parameter, was {:?}", node),
+            };
+            frame.types.insert(variable.name.clone(), type_spec.clone());
+            if by_ref {
+                let slot = match argument {
+                    Ast::Variable(argument) => {
+                        // An alias must hold the same type as the variable it stands for
+                        let parameter_type = type_spec.to_string();
+                        match self.declared_type(&argument.name) {
+                            Some(argument_type)
+                                if !argument_type.eq_ignore_ascii_case(&parameter_type) =>
+                            {
+                                bail!(
+                                    "VAR parameter {} of {} is {}, but {} is {}",
+                                    variable.name,
+                                    name,
+                                    parameter_type.to_lowercase(),
+                                    argument.name,
+                                    argument_type.to_lowercase()
+                                )
+                            }
+                            Some(_) => self.resolve(&argument.name),
+                            // Constants and enumeration variants have no storage to alias
+                            None => bail!(
+                                "VAR parameter {} of {} must be passed a variable, but {} is not one",
+                                variable.name,
+                                name,
+                                argument.name
+                            ),
+                        }
+                    }
+                    argument => bail!(
+                        "VAR parameter {} of {} must be passed a variable, found {:?}",
+                        variable.name,
+                        name,
+                        argument
+                    ),
+                };
+                frame.references.insert(variable.name.clone(), slot);
+            } else {
+                let value = self.evaluate(argument)?;
+                frame.values.insert(variable.name.clone(), None);
+                value_parameters.push((variable.name.clone(), value));
+            }
+        }
+
+        if self.call_stack.len() >= MAX_CALL_DEPTH {
+            bail!(
+                "Cannot call {}: call stack too deep, at most {} procedures may run at once",
+                name,
+                MAX_CALL_DEPTH
+            );
+        }
+        self.call_stack.push(frame);
+        let result = value_parameters
+            .into_iter()
+            .try_for_each(|(name, value)| self.assign(&name, value))
+            .and_then(|_| self.interpret_node(block));
+        self.call_stack.pop();
+        result
+    }
+
+    /// Finds a procedure from the innermost running procedure outwards, along with the frame
+    /// it is declared in
+    fn lookup_procedure(&self, name: &str) -> Option<(Rc<Ast>, Option<usize>)> {
+        let mut frame = self.call_stack.len().checked_sub(1);
+        while let Some(index) = frame {
+            if let Some(procedure) = self.call_stack[index].procedures.get(name) {
+                return Some((procedure.clone(), Some(index)));
+            }
+            frame = self.call_stack[index].parent;
+        }
+        self.procedures
+            .get(name)
+            .map(|procedure| (procedure.clone(), None))
+    }
+
+    /// Makes a procedure callable from the block it is declared in
+    pub(super) fn declare_procedure(&mut self, name: &str, declaration: Ast) {
+        let declaration = Rc::new(declaration);
+        match self.call_stack.last_mut() {
+            Some(frame) => frame.procedures.insert(name, declaration),
+            None => self.procedures.insert(name, declaration),
+        };
+    }
+
+    /// Declares a variable in the running procedure, or globally outside of any
+    pub(super) fn declare_variable(&mut self, name: &str, type_spec: &TypeSpec) -> Result<()> {
+        let initial_value = match type_spec {
+            TypeSpec::Array { .. } => Some(Value::default_for(type_spec)?),
+            _ => None,
+        };
+        match self.call_stack.last_mut() {
+            Some(frame) => {
+                frame.types.insert(name, type_spec.clone());
+                frame.values.insert(name, initial_value);
+            }
+            None => {
+                self.global_types.insert(name, type_spec.clone());
+                if let Some(value) = initial_value {
+                    self.global_scope.insert(name, value);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Declares a constant in the running procedure, or globally outside of any
+    pub(super) fn declare_constant(&mut self, name: &str, value: Value) {
+        match self.call_stack.last_mut() {
+            Some(frame) => {
+                frame.values.insert(name, Some(value));
+            }
+            None => {
+                self.global_scope.insert(name, value);
+            }
+        }
+    }
+
+    /// Where the variable `name` visible from the running procedure is stored, following
+    /// VAR parameters to the variable they alias
+    pub(super) fn resolve(&self, name: &str) -> Slot {
+        let mut frame = self.call_stack.len().checked_sub(1);
+        while let Some(index) = frame {
+            let current = &self.call_stack[index];
+            if let Some(slot) = current.references.get(name) {
+                return slot.clone();
+            }
+            if current.values.contains_key(name) {
+                return Slot::Local {
+                    frame: index,
+                    name: name.to_string(),
+                };
+            }
+            frame = current.parent;
+        }
+        Slot::Global(name.to_string())
+    }
+
+    /// The current value of a variable, if it has been assigned
+    pub(super) fn value_of(&self, name: &str) -> Option<&Value> {
+        match self.resolve(name) {
+            Slot::Global(name) => self.global_scope.get(name),
+            Slot::Local { frame, name } => self.call_stack[frame]
+                .values
+                .get(name)
+                .and_then(Option::as_ref),
+        }
+    }
+
+    pub(super) fn value_of_mut(&mut self, name: &str) -> Option<&mut Value> {
+        match self.resolve(name) {
+            Slot::Global(name) => self.global_scope.get_mut(name),
+            Slot::Local { frame, name } => self.call_stack[frame]
+                .values
+                .get_mut(name)
+                .and_then(Option::as_mut),
+        }
+    }
+
+    /// Stores `value` in a variable without checking its type
+    pub(super) fn store(&mut self, name: &str, value: Value) {
+        match self.resolve(name) {
+            Slot::Global(name) => {
+                self.global_scope.insert(name, value);
+            }
+            Slot::Local { frame, name } => {
+                self.call_stack[frame].values.insert(name, Some(value));
+            }
+        }
+    }
+
+    /// The declared type of a parameter or local variable visible from the running procedure
+    pub(super) fn local_type(&self, name: &str) -> Option<&TypeSpec> {
+        let mut frame = self.call_stack.len().checked_sub(1);
+        while let Some(index) = frame {
+            if let Some(var_type) = self.call_stack[index].types.get(name) {
+                return Some(var_type);
+            }
+            frame = self.call_stack[index].parent;
+        }
+        None
+    }
+}
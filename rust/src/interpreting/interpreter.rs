@@ -1,12 +1,61 @@
-use crate::interpreting::symbol_table::SymbolTable;
-use crate::interpreting::types::NumericType;
-use crate::parsing::ast::Ast;
+use crate::interpreting::built_ins::{is_built_in_procedure, Halt};
+use crate::interpreting::call_stack::Frame;
+use crate::interpreting::symbol_table::{Symbol, SymbolTable};
+use crate::interpreting::types::{Enumeration, NumericType, OverflowPolicy, Value};
+use crate::lexing::lexer::{Lexer, Position};
+use crate::parsing::ast::{Ast, CaseBranch, SetElement, TypeSpec, Variable};
+use crate::parsing::parser::Parser;
+use crate::parsing::visitor::AstVisitor;
+use crate::{IntegerMachineType, RealMachineType};
 use anyhow::{anyhow, bail, Error};
 use case_insensitive_hashmap::CaseInsensitiveHashMap;
+use std::cell::RefCell;
+use std::cmp::Ordering;
+use std::collections::BTreeMap;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::{BufRead, BufReader, Write};
+use std::rc::Rc;
+
+/// Receives the name and new value of an assigned variable, see `Interpreter::on_assign`
+pub type AssignHook = Box<dyn FnMut(&str, &NumericType)>;
 
 pub struct Interpreter {
-    pub global_scope: CaseInsensitiveHashMap<NumericType>,
+    pub global_scope: CaseInsensitiveHashMap<Value>,
     pub symbol_table: Option<SymbolTable>,
+    /// Where `readln` takes its lines from, stdin by default
+    pub input: Box<dyn BufRead>,
+    /// Where `write` and `writeln` print to, stdout by default. Any `Send` writer can be plugged
+    /// in, e.g. one teeing to the terminal and a log.
+    pub output: Box<dyn Write + Send>,
+    /// Where the verbose symbol table diagnostics go, stdout by default
+    pub symbol_table_log: Box<dyn Write>,
+    /// Enables non-standard debugging built-ins such as `typeof`
+    pub debug: bool,
+    /// Turns implicit conversions into errors: `div`/`mod` on reals and
+    /// assigning a real to an integer variable
+    pub strict: bool,
+    /// Counts how often each kind of node is evaluated, see `node_visits`
+    pub profile: bool,
+    /// What integer `+`, `-`, `*` and `**` do when they overflow, an error by default
+    pub overflow: OverflowPolicy,
+    /// How far apart a real `CASE` selector and label may be and still match, exact by default
+    pub real_eq_epsilon: RealMachineType,
+    /// Called with the variable's name and new value after every `:=` storing a number, e.g. to
+    /// trace or watch variables
+    pub on_assign: Option<AssignHook>,
+    /// The name declared by `PROGRAM` of the program interpreted last
+    pub program_name: Option<String>,
+    /// How many decimals reals are written with, see `format_value`. As many as needed to tell
+    /// the value apart by default.
+    pub real_display_precision: Option<usize>,
+    node_visits: RefCell<BTreeMap<&'static str, usize>>,
+    /// Procedures declared in the program's outermost block
+    pub(super) procedures: CaseInsensitiveHashMap<Rc<Ast>>,
+    /// The procedures currently running, innermost last
+    pub(super) call_stack: Vec<Frame>,
+    /// Declared types of the global variables, as they are declared
+    pub(super) global_types: CaseInsensitiveHashMap<TypeSpec>,
     verbose_symbol_table: bool,
 }
 
@@ -15,92 +64,765 @@ impl Interpreter {
         Interpreter {
             global_scope: CaseInsensitiveHashMap::new(),
             symbol_table: Option::None,
+            input: Box::new(BufReader::new(io::stdin())),
+            output: Box::new(io::stdout()),
+            symbol_table_log: Box::new(io::stdout()),
+            debug: false,
+            strict: false,
+            profile: false,
+            overflow: OverflowPolicy::default(),
+            real_eq_epsilon: 0.0,
+            on_assign: None,
+            program_name: None,
+            real_display_precision: None,
+            node_visits: RefCell::new(BTreeMap::new()),
+            procedures: CaseInsensitiveHashMap::new(),
+            call_stack: vec![],
+            global_types: CaseInsensitiveHashMap::new(),
             verbose_symbol_table,
         }
     }
 
+    /// Forgets everything learned from the programs run so far: variables, declared procedures,
+    /// the program name, the symbol table and the profile. Configuration is kept, i.e. the input
+    /// and output sinks, `debug`, `strict`, `profile`, `overflow`, `real_eq_epsilon`,
+    /// `on_assign`, `real_display_precision` and verbosity.
+    pub fn reset(&mut self) {
+        self.global_scope = CaseInsensitiveHashMap::new();
+        self.symbol_table = None;
+        self.program_name = None;
+        self.node_visits.borrow_mut().clear();
+        self.procedures = CaseInsensitiveHashMap::new();
+        self.call_stack.clear();
+        self.global_types = CaseInsensitiveHashMap::new();
+    }
+
+    /// The text of `value` in the program's output, with reals rounded to
+    /// `real_display_precision` decimals if set
+    pub fn format_value(&self, value: &Value) -> String {
+        match self.real_display_precision {
+            Some(precision) => format!("{:.*}", precision, value),
+            None => value.to_string(),
+        }
+    }
+
+    /// How many times each kind of node has been evaluated so far, when profiling
+    pub fn node_visits(&self) -> BTreeMap<&'static str, usize> {
+        self.node_visits.borrow().clone()
+    }
+
+    fn record_visit(&self, node: &Ast) {
+        if self.profile {
+            *self
+                .node_visits
+                .borrow_mut()
+                .entry(node.kind())
+                .or_insert(0) += 1;
+        }
+    }
+
+    /// Evaluates an expression of any type
+    pub fn evaluate(&self, node: &Ast) -> anyhow::Result<Value> {
+        if let Ast::Located { position, node } = node {
+            return self.evaluate(node).map_err(|err| locate(err, *position));
+        }
+        // Numeric nodes are counted by interpret_expression instead
+        if produces_any_value(node) {
+            self.record_visit(node);
+        }
+        match node {
+            Ast::FunctionCall { name, arguments } => self.call_built_in_function(name, arguments),
+            Ast::Equal(l, r) => self.comparison(l, r, Ordering::is_eq),
+            Ast::NotEqual(l, r) => self.comparison(l, r, Ordering::is_ne),
+            Ast::LessThan(l, r) => self.comparison(l, r, Ordering::is_lt),
+            Ast::LessOrEqual(l, r) => self.comparison(l, r, Ordering::is_le),
+            Ast::GreaterThan(l, r) => self.comparison(l, r, Ordering::is_gt),
+            Ast::GreaterOrEqual(l, r) => self.comparison(l, r, Ordering::is_ge),
+            Ast::In { value, set } => self.membership(value, set),
+            Ast::And(l, r) => self.logical_or_bitwise(l, r, "AND", false, |l, r| l & r),
+            Ast::Or(l, r) => self.logical_or_bitwise(l, r, "OR", true, |l, r| l | r),
+            Ast::Not(nested) => Ok(Value::Boolean(!self.boolean_operand(nested, "NOT")?)),
+            Ast::StringConstant(s) => Ok(Value::String(s.clone())),
+            Ast::BooleanConstant(b) => Ok(Value::Boolean(*b)),
+            Ast::Variable(var) if self.symbol_table.is_none() && self.global_scope.is_empty() => {
+                bail!(
+                    "Cannot use {}: variables aren't available in expression mode; define a program",
+                    var.name
+                )
+            }
+            Ast::Variable(var) => self
+                .value_of(&var.name)
+                .cloned()
+                .ok_or_else(|| anyhow!("{:} not defined", var.name)),
+            Ast::Index { array, index } => {
+                let index = self.interpret_expression(index)?.as_int();
+                match self.value_of(&array.name) {
+                    Some(Value::Array { lower, elements }) => {
+                        let position = element_position(&array.name, *lower, elements, index)?;
+                        Ok(elements[position].clone())
+                    }
+                    Some(value) => bail!("{} is {}, not an array", array.name, value.type_name()),
+                    None => bail!("{:} not defined", array.name),
+                }
+            }
+            _ => Ok(Value::Numeric(self.interpret_expression(node)?)),
+        }
+    }
+
     pub fn interpret_expression(&self, node: &Ast) -> anyhow::Result<NumericType> {
+        if let Ast::Located { position, node } = node {
+            return self
+                .interpret_expression(node)
+                .map_err(|err| locate(err, *position));
+        }
+        if !produces_any_value(node) {
+            self.record_visit(node);
+        }
         Ok(match node {
-            Ast::Add(l, r) => self.interpret_expression(l)? + self.interpret_expression(r)?,
-            Ast::Subtract(l, r) => self.interpret_expression(l)? - self.interpret_expression(r)?,
-            Ast::Multiply(l, r) => self.interpret_expression(l)? * self.interpret_expression(r)?,
-            Ast::IntegerDivide(l, r) => NumericType::Integer(
-                self.interpret_expression(l)?.as_int() / self.interpret_expression(r)?.as_int(),
-            ),
+            Ast::Add(l, r) => self.arithmetic('+', l, r)?,
+            Ast::Subtract(l, r) => self.arithmetic('-', l, r)?,
+            Ast::Multiply(l, r) => self.arithmetic('*', l, r)?,
+            Ast::IntegerDivide(l, r) => {
+                let (dividend, divisor) = self.integer_operands("div", l, r)?;
+                if NumericType::Integer(divisor).is_zero() {
+                    bail!("integer division (div) by zero");
+                }
+                NumericType::Integer(
+                    dividend.checked_div(divisor).ok_or_else(|| {
+                        anyhow!("integer overflow in {} div {}", dividend, divisor)
+                    })?,
+                )
+            }
+            Ast::Modulo(l, r) => {
+                let (dividend, divisor) = self.integer_operands("mod", l, r)?;
+                if NumericType::Integer(divisor).is_zero() {
+                    bail!("modulo (mod) by zero");
+                }
+                if divisor < 0 {
+                    bail!("modulo (mod) by the negative number {}", divisor);
+                }
+                // ISO Pascal's mod is never negative, unlike `%` which keeps the dividend's sign
+                NumericType::Integer(dividend.rem_euclid(divisor))
+            }
             Ast::IntegerConstant(i) => NumericType::Integer(*i),
             Ast::RealDivide(l, r) => NumericType::Real(
                 self.interpret_expression(l)?.as_real() / self.interpret_expression(r)?.as_real(),
             ),
             Ast::RealConstant(r) => NumericType::Real(*r),
-            Ast::PositiveUnary(nested) => self.interpret_expression(nested)?,
-            Ast::NegativeUnary(nested) => -self.interpret_expression(nested)?,
-            Ast::Variable(var) => {
-                *(self
-                    .global_scope
-                    .get(var.name.clone())
-                    .ok_or_else(|| anyhow!("{:} not defined", var.name))?)
+            Ast::Power(l, r) => {
+                match (self.interpret_expression(l)?, self.interpret_expression(r)?) {
+                    (NumericType::Integer(base), NumericType::Integer(exponent))
+                        if exponent >= 0 =>
+                    {
+                        NumericType::Integer(self.overflow.power(base, exponent as u32)?)
+                    }
+                    (base, exponent) => NumericType::Real(base.as_real().powf(exponent.as_real())),
+                }
             }
+            Ast::PositiveUnary(nested) => self.interpret_expression(nested)?,
+            Ast::NegativeUnary(nested) => match self.interpret_expression(nested)? {
+                NumericType::Integer(i) => NumericType::Integer(self.overflow.apply('-', 0, i)?),
+                real => -real,
+            },
+            Ast::Variable(_)
+            | Ast::Index { .. }
+            | Ast::FunctionCall { .. }
+            | Ast::StringConstant(_)
+            | Ast::BooleanConstant(_)
+            | Ast::Equal(_, _)
+            | Ast::NotEqual(_, _)
+            | Ast::LessThan(_, _)
+            | Ast::LessOrEqual(_, _)
+            | Ast::GreaterThan(_, _)
+            | Ast::GreaterOrEqual(_, _)
+            | Ast::In { .. }
+            | Ast::And(_, _)
+            | Ast::Or(_, _)
+            | Ast::Not(_) => self.evaluate(node)?.as_numeric()?,
             Ast::Compound { .. }
             | Ast::Assign(_, _)
+            | Ast::IndexedAssign { .. }
+            | Ast::ProcedureCall { .. }
+            | Ast::While { .. }
+            | Ast::With { .. }
+            | Ast::If { .. }
+            | Ast::Case { .. }
             | Ast::Program { .. }
             | Ast::Parameter { .. }
             | Ast::ProcedureDeclaration { .. }
             | Ast::Block { .. }
             | Ast::VariableDeclaration { .. }
+            | Ast::ConstantDeclaration { .. }
+            | Ast::TypeDeclaration { .. }
             | Ast::Type(_)
+            | Ast::Located { .. }
             | Ast::NoOp => {
                 bail!("Invalid node in expression: {:?}", node)
             }
         })
     }
 
-    pub fn interpret(&mut self, node: &Ast) -> anyhow::Result<()> {
-        self.symbol_table = Some(SymbolTable::build_for(node, self.verbose_symbol_table)?);
+    /// Evaluates `+`, `-` or `*`, handling integer overflow according to the `overflow` policy
+    fn arithmetic(&self, operator: char, l: &Ast, r: &Ast) -> anyhow::Result<NumericType> {
+        let (l, r) = (self.interpret_expression(l)?, self.interpret_expression(r)?);
+        Ok(match (l, r, operator) {
+            (NumericType::Integer(l), NumericType::Integer(r), _) => {
+                NumericType::Integer(self.overflow.apply(operator, l, r)?)
+            }
+            (_, _, '+') => l + r,
+            (_, _, '-') => l - r,
+            _ => l * r,
+        })
+    }
 
-        self.interpret_node(node)
+    fn comparison(&self, l: &Ast, r: &Ast, holds: fn(Ordering) -> bool) -> anyhow::Result<Value> {
+        let ordering = self.evaluate(l)?.compare(&self.evaluate(r)?)?;
+        Ok(Value::Boolean(holds(ordering)))
     }
 
-    fn interpret_node(&mut self, node: &Ast) -> Result<(), Error> {
-        match node {
-            Ast::Compound { statements } => {
-                for statement in statements {
-                    self.interpret_node(statement)?;
+    /// Whether the integer `value` is within any of the `set`'s elements
+    fn membership(&self, value: &Ast, set: &[SetElement]) -> anyhow::Result<Value> {
+        let value = self.set_integer(value)?;
+        for element in set {
+            let contains = match element {
+                SetElement::Single(single) => self.set_integer(single)? == value,
+                SetElement::Range(lower, upper) => {
+                    (self.set_integer(lower)?..=self.set_integer(upper)?).contains(&value)
                 }
+            };
+            if contains {
+                return Ok(Value::Boolean(true));
             }
-            Ast::Assign(var, expr) => {
-                self.global_scope
-                    .insert(var.name.clone(), self.interpret_expression(expr)?);
+        }
+        Ok(Value::Boolean(false))
+    }
+
+    /// Evaluates an operand of `in`, which only supports integers
+    fn set_integer(&self, node: &Ast) -> anyhow::Result<IntegerMachineType> {
+        match self.interpret_expression(node)? {
+            NumericType::Integer(i) => Ok(i),
+            real => bail!("in only supports integers, found the real {}", real),
+        }
+    }
+
+    /// Evaluates a `statement`'s condition, which has to be a boolean
+    fn condition(&self, node: &Ast, statement: &str) -> anyhow::Result<bool> {
+        match self.evaluate(node)? {
+            Value::Boolean(b) => Ok(b),
+            value => bail!(
+                "{} condition must be a boolean, found the {} {}",
+                statement,
+                value.type_name(),
+                value
+            ),
+        }
+    }
+
+    /// `AND` or `OR` by the type of `l`: on booleans `r` is skipped once `l` is `decided_by`, on
+    /// integers both are needed for the `bitwise` result
+    fn logical_or_bitwise(
+        &self,
+        l: &Ast,
+        r: &Ast,
+        operator: &str,
+        decided_by: bool,
+        bitwise: fn(IntegerMachineType, IntegerMachineType) -> IntegerMachineType,
+    ) -> anyhow::Result<Value> {
+        match self.evaluate(l)? {
+            Value::Boolean(b) if b == decided_by => Ok(Value::Boolean(b)),
+            Value::Boolean(_) => Ok(Value::Boolean(self.boolean_operand(r, operator)?)),
+            Value::Numeric(NumericType::Integer(left)) => match self.evaluate(r)? {
+                Value::Numeric(NumericType::Integer(right)) => {
+                    Ok(Value::Numeric(NumericType::Integer(bitwise(left, right))))
+                }
+                value => bail!(
+                    "{} needs two booleans or two integers, found the integer {} and the {} {}",
+                    operator,
+                    left,
+                    value.type_name(),
+                    value
+                ),
+            },
+            value => bail!(
+                "{} needs boolean or integer operands, found the {} {}",
+                operator,
+                value.type_name(),
+                value
+            ),
+        }
+    }
+
+    fn boolean_operand(&self, node: &Ast, operator: &str) -> anyhow::Result<bool> {
+        match self.evaluate(node)? {
+            Value::Boolean(b) => Ok(b),
+            value => bail!(
+                "{} needs boolean operands, found the {} {}",
+                operator,
+                value.type_name(),
+                value
+            ),
+        }
+    }
+
+    /// Evaluates both operands of an integer-only operator, left first, truncating reals unless
+    /// strict
+    fn integer_operands(
+        &self,
+        operator: &str,
+        l: &Ast,
+        r: &Ast,
+    ) -> anyhow::Result<(IntegerMachineType, IntegerMachineType)> {
+        let (l, r) = (self.interpret_expression(l)?, self.interpret_expression(r)?);
+        if self.strict && !(l.is_integer() && r.is_integer()) {
+            bail!(
+                "{} requires integer operands, found {} {} {}",
+                operator,
+                l,
+                operator,
+                r
+            );
+        }
+        let truncate = |operand: NumericType| match operand {
+            NumericType::Real(r)
+                if r.trunc() < IntegerMachineType::MIN as RealMachineType
+                    || r.trunc() > IntegerMachineType::MAX as RealMachineType
+                    || r.is_nan() =>
+            {
+                bail!("{} of the real {} is out of the integer range", operator, r)
             }
-            Ast::NoOp => {}
-            Ast::Program { block, .. } => self.interpret_node(block)?,
-            Ast::Parameter { .. } => {}            // TODO after part 14
-            Ast::ProcedureDeclaration { .. } => {} // TODO after part 12
-            Ast::Block {
-                declarations,
-                compound_statements,
-            } => {
-                for variable_declaration in declarations {
-                    self.interpret_node(variable_declaration)?;
+            operand => Ok(operand.as_int()),
+        };
+        Ok((truncate(l)?, truncate(r)?))
+    }
+
+    /// Rejects values that don't fit the variable's declared type. Reals only go into integer
+    /// variables outside of strict mode.
+    fn check_assignable(&self, variable_name: &str, value: &Value) -> anyhow::Result<()> {
+        let declared_type = match self.declared_type(variable_name) {
+            Some(declared_type) => declared_type,
+            None => return Ok(()),
+        };
+        let enumeration = self.declared_enumeration(variable_name);
+        if !self.fits(&declared_type, enumeration.as_ref(), value) {
+            bail!(
+                "Cannot assign the {} {} to the {} {}",
+                value.type_name(),
+                value,
+                declared_type.to_lowercase(),
+                variable_name
+            );
+        }
+        Ok(())
+    }
+
+    /// Whether `value` may be stored where a `declared_type` is expected, `enumeration` being that
+    /// type if it is enumerated
+    fn fits(&self, declared_type: &str, enumeration: Option<&Enumeration>, value: &Value) -> bool {
+        match value {
+            Value::Numeric(n) if self.strict && n.is_real() => {
+                declared_type == TypeSpec::Real.to_string()
+            }
+            Value::Numeric(_) => {
+                declared_type == TypeSpec::Integer.to_string()
+                    || declared_type == TypeSpec::Real.to_string()
+            }
+            Value::String(_) => declared_type == TypeSpec::String.to_string(),
+            Value::Boolean(_) => declared_type == TypeSpec::Boolean.to_string(),
+            Value::Array { .. } => false,
+            Value::Enumerated {
+                enumeration: actual,
+                ..
+            } => enumeration == Some(actual.as_ref()),
+        }
+    }
+
+    /// Type checks `value` against the variable's declared type before storing it
+    pub(super) fn assign(&mut self, variable_name: &str, value: Value) -> anyhow::Result<()> {
+        self.check_assignable(variable_name, &value)?;
+        self.store(variable_name, value);
+        Ok(())
+    }
+
+    /// The declared type of a variable, once the symbol table has been built
+    pub(super) fn declared_type(&self, variable_name: &str) -> Option<String> {
+        if let Some(local_type) = self.local_type(variable_name) {
+            return Some(local_type.to_string());
+        }
+        self.symbol_table
+            .as_ref()
+            .and_then(|symbol_table| symbol_table.symbols.get(variable_name))
+            .and_then(|symbol| match symbol {
+                Symbol::Variable { var_type, .. } => Some(var_type.clone()),
+                _ => None,
+            })
+    }
+
+    /// The full declared type of a variable, once its declaration has run
+    pub(super) fn declared_type_spec(&self, variable_name: &str) -> Option<TypeSpec> {
+        self.local_type(variable_name)
+            .or_else(|| self.global_types.get(variable_name))
+            .cloned()
+    }
+
+    /// The enumerated type a variable is declared with, if any. Global variables find it next to
+    /// them in the symbol table, as enumerations are told apart by their variants, not just names.
+    fn declared_enumeration(&self, variable_name: &str) -> Option<Enumeration> {
+        if let Some(local_type) = self.local_type(variable_name) {
+            return Enumeration::declared_by(local_type);
+        }
+        let symbols = &self.symbol_table.as_ref()?.symbols;
+        match symbols.get(variable_name)? {
+            Symbol::Variable { var_type, .. } => match symbols.get(var_type)? {
+                Symbol::Type { name, variants } => Some(Enumeration {
+                    name: name.clone(),
+                    variants: variants.clone(),
+                }),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// Evaluates a standalone expression against the current `global_scope`
+    pub fn eval_with(&self, expr_source: &str) -> anyhow::Result<NumericType> {
+        let ast = Parser::new(Lexer::new(expr_source)).parse_expression()?;
+        self.interpret_expression(&ast)
+    }
+
+    pub fn interpret(&mut self, node: &Ast) -> anyhow::Result<()> {
+        self.build_symbol_table(node)?;
+        self.run(node)
+    }
+
+    /// Like `interpret`, but calls `before_statement` before each statement of the program's
+    /// main `BEGIN ... END`, e.g. for stepping through it. Returning `false` stops the program.
+    /// The callback may read from the interpreter's `input`, which `readln` shares.
+    pub fn interpret_stepping(
+        &mut self,
+        node: &Ast,
+        mut before_statement: impl FnMut(&mut Interpreter, &Ast) -> anyhow::Result<bool>,
+    ) -> anyhow::Result<()> {
+        let (name, declarations, statements) = match node {
+            Ast::Program { name, block } => match block.as_ref() {
+                Ast::Block {
+                    declarations,
+                    compound_statements,
+                } => (name, declarations, compound_statements.statements()?),
+                _ => bail!("Expected the block of {}, found {:?}", name, block),
+            },
+            _ => bail!("Can only step through a program, not {}", node.kind()),
+        };
+        self.build_symbol_table(node)?;
+        self.program_name = Some(name.clone());
+        let result = declarations
+            .iter()
+            .try_for_each(|declaration| self.interpret_node(declaration))
+            .and_then(|_| {
+                for statement in statements {
+                    if !before_statement(self, statement)? {
+                        break;
+                    }
+                    self.interpret_node(statement)?;
                 }
-                self.interpret_node(compound_statements)?;
+                Ok(())
+            });
+        self.output.flush()?;
+        result
+    }
+
+    /// Runs each statement as soon as `statements` yields it, against the current
+    /// `global_scope`, e.g. those of `Parser::statements`. Nothing checks the names used ahead of
+    /// time since the statements aren't known up front. Stops at the first error.
+    pub fn interpret_statements(
+        &mut self,
+        mut statements: impl Iterator<Item = anyhow::Result<Ast>>,
+    ) -> anyhow::Result<()> {
+        let result = statements.try_for_each(|statement| self.interpret_node(&statement?));
+        self.output.flush()?;
+        result
+    }
+
+    fn build_symbol_table(&mut self, node: &Ast) -> anyhow::Result<()> {
+        self.symbol_table = Some(if self.verbose_symbol_table {
+            SymbolTable::build_logging_to(node, &mut self.symbol_table_log)?
+        } else {
+            SymbolTable::build_for(node, false)?
+        });
+        Ok(())
+    }
+
+    /// Like `interpret`, but reuses a symbol table already built for `node` rather than
+    /// building it again, e.g. when running one program against different seeded globals
+    pub fn interpret_with_table(
+        &mut self,
+        node: &Ast,
+        symbol_table: &SymbolTable,
+    ) -> anyhow::Result<()> {
+        self.symbol_table = Some(symbol_table.clone());
+        self.run(node)
+    }
+
+    fn run(&mut self, node: &Ast) -> anyhow::Result<()> {
+        let result = self.interpret_node(node);
+        self.output.flush()?;
+        result
+    }
+
+    pub(super) fn interpret_node(&mut self, node: &Ast) -> Result<(), Error> {
+        self.record_visit(node);
+        node.accept(self)
+    }
+}
+
+/// Runs statements and declarations, leaving expressions to `evaluate`
+impl AstVisitor for Interpreter {
+    fn visit_program(&mut self, name: &str, block: &Ast) -> Result<(), Error> {
+        self.program_name = Some(name.to_string());
+        self.interpret_node(block)
+    }
+
+    fn visit_block(
+        &mut self,
+        declarations: &[Ast],
+        compound_statements: &Ast,
+    ) -> Result<(), Error> {
+        for declaration in declarations {
+            self.interpret_node(declaration)?;
+        }
+        self.interpret_node(compound_statements)
+    }
+
+    fn visit_procedure_declaration(
+        &mut self,
+        name: &str,
+        parameters: &[Ast],
+        block: &Ast,
+    ) -> Result<(), Error> {
+        let declaration = Ast::ProcedureDeclaration {
+            name: name.to_string(),
+            parameters: parameters.to_vec(),
+            block: Box::from(block.clone()),
+        };
+        self.declare_procedure(name, declaration);
+        Ok(())
+    }
+
+    fn visit_parameter(&mut self, _: &Ast, _: &Ast, _: bool) -> Result<(), Error> {
+        Ok(())
+    }
+
+    fn visit_variable_declaration(&mut self, variable: &Ast, type_spec: &Ast) -> Result<(), Error> {
+        self.declare_variable(&variable.variable()?.name, type_spec.type_spec()?)
+    }
+
+    fn visit_constant_declaration(&mut self, name: &str, value: &Ast) -> Result<(), Error> {
+        let value = self.evaluate(value)?;
+        self.declare_constant(name, value);
+        Ok(())
+    }
+
+    /// The variants are constants numbered from 0 in the order they are declared
+    fn visit_type_declaration(&mut self, name: &str, variants: &[String]) -> Result<(), Error> {
+        let enumeration = Rc::new(Enumeration {
+            name: name.to_string(),
+            variants: variants.to_vec(),
+        });
+        for (ordinal, variant) in variants.iter().enumerate() {
+            let value = Value::Enumerated {
+                ordinal: IntegerMachineType::try_from(ordinal)?,
+                enumeration: Rc::clone(&enumeration),
+            };
+            self.declare_constant(variant, value);
+        }
+        Ok(())
+    }
+
+    fn visit_compound(&mut self, statements: &[Ast]) -> Result<(), Error> {
+        for statement in statements {
+            self.interpret_node(statement)?;
+        }
+        Ok(())
+    }
+
+    fn visit_assign(&mut self, variable: &Variable, value: &Ast) -> Result<(), Error> {
+        let value = self.evaluate(value)?;
+        let number = match value {
+            Value::Numeric(number) => Some(number),
+            _ => None,
+        };
+        self.assign(&variable.name, value)?;
+        if let (Some(on_assign), Some(number)) = (&mut self.on_assign, number) {
+            on_assign(&variable.name, &number);
+        }
+        Ok(())
+    }
+
+    fn visit_indexed_assign(
+        &mut self,
+        array: &Variable,
+        index: &Ast,
+        value: &Ast,
+    ) -> Result<(), Error> {
+        let index = self.interpret_expression(index)?.as_int();
+        let value = self.evaluate(value)?;
+        if let Some(TypeSpec::Array { element_type, .. }) = self.declared_type_spec(&array.name) {
+            let declared_type = element_type.to_string();
+            let enumeration = Enumeration::declared_by(&element_type);
+            if !self.fits(&declared_type, enumeration.as_ref(), &value) {
+                bail!(
+                    "Cannot assign the {} {} to the {} {}[{}]",
+                    value.type_name(),
+                    value,
+                    declared_type.to_lowercase(),
+                    array.name,
+                    index
+                );
+            }
+        }
+        match self.value_of_mut(&array.name) {
+            Some(Value::Array { lower, elements }) => {
+                let position = element_position(&array.name, *lower, elements, index)?;
+                elements[position] = value;
             }
-            // TODO for type safety
-            Ast::VariableDeclaration { .. } => {}
-            Ast::Type(_) => {}
-
-            Ast::Add(_, _)
-            | Ast::Subtract(_, _)
-            | Ast::Multiply(_, _)
-            | Ast::IntegerDivide(_, _)
-            | Ast::IntegerConstant(_)
-            | Ast::RealDivide(_, _)
-            | Ast::RealConstant(_)
-            | Ast::PositiveUnary(_)
-            | Ast::NegativeUnary(_)
-            | Ast::Variable(_) => bail!("Invalid node in program: {:?}", node),
+            Some(value) => bail!("{} is {}, not an array", array.name, value.type_name()),
+            None => bail!("{:} not defined", array.name),
         }
         Ok(())
     }
+
+    fn visit_procedure_call(&mut self, name: &str, arguments: &[Ast]) -> Result<(), Error> {
+        if is_built_in_procedure(name) {
+            self.call_built_in_procedure(name, arguments)
+        } else {
+            self.call_procedure(name, arguments)
+        }
+    }
+
+    fn visit_while(&mut self, condition: &Ast, body: &Ast) -> Result<(), Error> {
+        while self.condition(condition, "WHILE")? {
+            self.interpret_node(body)?;
+        }
+        Ok(())
+    }
+
+    /// Records aren't supported yet, so no variable has fields for the body to refer to
+    fn visit_with(&mut self, record: &Variable, _body: &Ast) -> Result<(), Error> {
+        match self.value_of(&record.name) {
+            Some(value) => bail!(
+                "WITH needs a record, but {} is {}",
+                record.name,
+                value.type_name()
+            ),
+            None => bail!("WITH needs a record, but {} is not defined", record.name),
+        }
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Ast,
+        then_branch: &Ast,
+        else_branch: Option<&Ast>,
+    ) -> Result<(), Error> {
+        if self.condition(condition, "IF")? {
+            self.interpret_node(then_branch)
+        } else if let Some(else_branch) = else_branch {
+            self.interpret_node(else_branch)
+        } else {
+            Ok(())
+        }
+    }
+
+    fn visit_case(
+        &mut self,
+        selector: &Ast,
+        branches: &[CaseBranch],
+        otherwise: Option<&Ast>,
+    ) -> Result<(), Error> {
+        let selector = self.interpret_expression(selector)?;
+        let branch = branches.iter().find(|branch| {
+            branch.labels.iter().any(|label| match selector {
+                NumericType::Integer(i) => label.matches(i),
+                NumericType::Real(r) => label.matches_real(r, self.real_eq_epsilon),
+            })
+        });
+        match (branch, otherwise) {
+            (Some(branch), _) => self.interpret_node(&branch.statement),
+            (None, Some(otherwise)) => self.interpret_node(otherwise),
+            (None, None) => Ok(()),
+        }
+    }
+
+    fn visit_expression(&mut self, expression: &Ast) -> Result<(), Error> {
+        bail!("Invalid node in program: {:?}", expression)
+    }
+}
+
+/// A runtime error raised while evaluating the expression at `position`
+#[derive(Debug)]
+pub struct LocatedError {
+    pub message: String,
+    pub position: Position,
+}
+
+impl Display for LocatedError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for LocatedError {}
+
+/// Attaches `position` to an error unless a nested expression already did, or it is a `Halt`
+fn locate(err: Error, position: Position) -> Error {
+    if err.is::<LocatedError>() || err.is::<Halt>() {
+        return err;
+    }
+    Error::new(LocatedError {
+        message: format!("{:#}", err),
+        position,
+    })
+}
+
+/// Whether `evaluate` rather than `interpret_expression` handles the node, as it may not be numeric
+fn produces_any_value(node: &Ast) -> bool {
+    matches!(
+        node,
+        Ast::Variable(_)
+            | Ast::Index { .. }
+            | Ast::FunctionCall { .. }
+            | Ast::StringConstant(_)
+            | Ast::BooleanConstant(_)
+            | Ast::Equal(_, _)
+            | Ast::NotEqual(_, _)
+            | Ast::LessThan(_, _)
+            | Ast::LessOrEqual(_, _)
+            | Ast::GreaterThan(_, _)
+            | Ast::GreaterOrEqual(_, _)
+            | Ast::In { .. }
+            | Ast::And(_, _)
+            | Ast::Or(_, _)
+            | Ast::Not(_)
+    )
+}
+
+/// Where `index` is stored within an array starting at `lower`
+fn element_position(
+    array_name: &str,
+    lower: IntegerMachineType,
+    elements: &[Value],
+    index: IntegerMachineType,
+) -> anyhow::Result<usize> {
+    let position = index as i64 - lower as i64;
+    if position < 0 || position >= elements.len() as i64 {
+        bail!(
+            "index {} out of bounds for array '{}' (range {}..{})",
+            index,
+            array_name,
+            lower,
+            lower as i64 + elements.len() as i64 - 1
+        );
+    }
+    Ok(position as usize)
 }
 
 impl Default for Interpreter {
@@ -108,3 +830,1570 @@ impl Default for Interpreter {
         Self::new(false)
     }
 }
+
+#[test]
+fn test_division_by_zero_names_operator() {
+    let interpreter = Interpreter::default();
+    let evaluate = |code: &str| {
+        let ast = Parser::new(Lexer::new(code)).parse_expression().unwrap();
+        interpreter.interpret_expression(&ast)
+    };
+
+    assert!(evaluate("5 div 0")
+        .expect_err("Expected div by zero to fail")
+        .to_string()
+        .contains("integer division (div) by zero"));
+    assert!(evaluate("5 mod 0")
+        .expect_err("Expected mod by zero to fail")
+        .to_string()
+        .contains("modulo (mod) by zero"));
+    assert_eq!(evaluate("5 / 0").unwrap(), NumericType::Real(f64::INFINITY));
+
+    // The dividend is evaluated before the divisor is checked
+    for code in ["missing div 0", "missing mod 0"] {
+        assert_eq!(
+            evaluate(code).unwrap_err().to_string(),
+            "Cannot use missing: variables aren't available in expression mode; define a program",
+            "{}",
+            code
+        );
+    }
+}
+
+#[test]
+fn test_eval_with_seeded_globals() -> anyhow::Result<()> {
+    let mut interpreter = Interpreter::default();
+    interpreter
+        .global_scope
+        .insert("x".to_string(), NumericType::Integer(10).into());
+
+    assert_eq!(
+        interpreter.eval_with("x * x + 1")?,
+        NumericType::Integer(101)
+    );
+    assert!(interpreter.eval_with("x := 2").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_readln_without_arguments_skips_a_line() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Pause;
+        VAR x : INTEGER;
+        BEGIN
+            readln;
+            readln(x)
+        END.
+    "#;
+
+    let mut interpreter = Interpreter {
+        input: Box::new("press enter to continue\n42\n".as_bytes()),
+        ..Interpreter::default()
+    };
+    interpreter.interpret(&Parser::new(Lexer::new(code)).parse()?)?;
+
+    assert_eq!(
+        interpreter.global_scope.get("x"),
+        Some(&Value::Numeric(NumericType::Integer(42)))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_readln_reads_the_declared_types() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Form;
+        TYPE Color = (Red, Green, Blue);
+        VAR
+            name : STRING;
+            ready : BOOLEAN;
+            shade : Color;
+            size : REAL;
+        BEGIN
+            readln(name, ready, shade, size);
+            writeln(name, ' ', ready, ' ', shade, ' ', size)
+        END.
+    "#;
+    let read = |line: &'static str| {
+        let interpreter = Interpreter {
+            input: Box::new(line.as_bytes()),
+            ..Interpreter::default()
+        };
+        interpreter.capture(code).map(|(output, _)| output)
+    };
+
+    assert_eq!(read("Ada TRUE green 1.5\n")?, "Ada TRUE Green 1.5\n");
+    assert_eq!(
+        read("Ada yes green 1.5\n").unwrap_err().to_string(),
+        "readln: \"yes\" is not a boolean for ready"
+    );
+    assert_eq!(
+        read("Ada true purple 1.5\n").unwrap_err().to_string(),
+        "readln: \"purple\" is not a Color for shade"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_typeof_requires_debug_mode() -> anyhow::Result<()> {
+    let ast = Parser::new(Lexer::new("typeof(3.14)")).parse_expression()?;
+
+    let debugging = Interpreter {
+        debug: true,
+        ..Interpreter::default()
+    };
+    assert_eq!(debugging.evaluate(&ast)?, Value::String("real".to_string()));
+
+    assert!(Interpreter::default()
+        .evaluate(&ast)
+        .expect_err("Expected typeof to be unknown outside of debug mode")
+        .to_string()
+        .contains("Unknown function"));
+    Ok(())
+}
+
+#[test]
+fn test_strict_mode_rejects_implicit_conversions() -> anyhow::Result<()> {
+    let lenient = Interpreter::default();
+    let strict = Interpreter {
+        strict: true,
+        ..Interpreter::default()
+    };
+
+    assert_eq!(lenient.eval_with("7.0 div 2")?, NumericType::Integer(3));
+    assert!(strict
+        .eval_with("7.0 div 2")
+        .expect_err("Expected div on a real to fail")
+        .to_string()
+        .contains("div requires integer operands"));
+    assert!(strict.eval_with("7.5 mod 2").is_err());
+    assert_eq!(strict.eval_with("7 div 2")?, NumericType::Integer(3));
+
+    let code = "PROGRAM Narrowing; VAR x : INTEGER; BEGIN x := 2.5 END.";
+    let ast = Parser::new(Lexer::new(code)).parse()?;
+    let mut lenient = Interpreter::default();
+    lenient.interpret(&ast)?;
+    assert_eq!(
+        lenient.global_scope.get("x"),
+        Some(&Value::Numeric(NumericType::Real(2.5)))
+    );
+
+    let mut strict = Interpreter {
+        strict: true,
+        ..Interpreter::default()
+    };
+    assert!(strict
+        .interpret(&ast)
+        .expect_err("Expected assigning a real to an integer to fail")
+        .to_string()
+        .contains("Cannot assign the real 2.5 to the integer x"));
+    Ok(())
+}
+
+#[test]
+fn test_indexed_assignment() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Arrays;
+        VAR
+            a : ARRAY[1..10] OF INTEGER;
+            x : INTEGER;
+        BEGIN
+            a[2] := 7;
+            a[1 + 2] := a[2] * 2;
+            x := a[3] + a[10]
+        END.
+    "#;
+
+    let mut interpreter = Interpreter::default();
+    interpreter.interpret(&Parser::new(Lexer::new(code)).parse()?)?;
+
+    assert_eq!(interpreter.eval_with("a[2]")?, NumericType::Integer(7));
+    assert_eq!(
+        interpreter.global_scope.get("x"),
+        Some(&Value::Numeric(NumericType::Integer(14)))
+    );
+    assert_eq!(
+        interpreter.eval_with("a[12]").unwrap_err().to_string(),
+        "index 12 out of bounds for array 'a' (range 1..10)"
+    );
+    let error = interpreter.eval_with("A[0]").unwrap_err().to_string();
+    assert!(error.contains("index 0"), "{}", error);
+    assert!(error.contains("'A'"), "{}", error);
+    assert!(error.contains("(range 1..10)"), "{}", error);
+    assert!(interpreter.eval_with("x[1]").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_numeric_built_ins_keep_their_argument_type() -> anyhow::Result<()> {
+    let interpreter = Interpreter::default();
+
+    assert_eq!(interpreter.eval_with("abs(-3)")?, NumericType::Integer(3));
+    assert_eq!(interpreter.eval_with("abs(-3.0)")?, NumericType::Real(3.0));
+    assert_eq!(interpreter.eval_with("sqr(4)")?, NumericType::Integer(16));
+    assert_eq!(interpreter.eval_with("sqr(2.5)")?, NumericType::Real(6.25));
+    assert_eq!(interpreter.eval_with("sqrt(16)")?, NumericType::Real(4.0));
+    assert_eq!(interpreter.eval_with("SQRT(2.25)")?, NumericType::Real(1.5));
+
+    assert!(interpreter.eval_with("sqrt(-1)").is_err());
+    assert!(interpreter.eval_with("sqr(50000)").is_err());
+    assert!(interpreter.eval_with("abs(1, 2)").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_transcendental_built_ins() -> anyhow::Result<()> {
+    let interpreter = Interpreter::default();
+
+    assert_eq!(interpreter.eval_with("exp(0)")?, NumericType::Real(1.0));
+    assert_eq!(interpreter.eval_with("sin(0)")?, NumericType::Real(0.0));
+    assert_eq!(interpreter.eval_with("cos(0.0)")?, NumericType::Real(1.0));
+    assert_eq!(interpreter.eval_with("ln(1)")?, NumericType::Real(0.0));
+    assert_eq!(interpreter.eval_with("Ln(Exp(2))")?, NumericType::Real(2.0));
+
+    assert_eq!(
+        interpreter.eval_with("ln(0)").unwrap_err().to_string(),
+        "ln of the non-positive number 0"
+    );
+    assert!(interpreter.eval_with("ln(-1.5)").is_err());
+    assert!(interpreter.eval_with("sin(1, 2)").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_string_built_ins() -> anyhow::Result<()> {
+    use crate::parsing::parser::parse_expr;
+
+    let interpreter = Interpreter::default();
+    let evaluate = |code: &str| interpreter.evaluate(&parse_expr(code)?);
+
+    assert_eq!(
+        interpreter.eval_with("length('abc')")?,
+        NumericType::Integer(3)
+    );
+    assert_eq!(
+        interpreter.eval_with("length('')")?,
+        NumericType::Integer(0)
+    );
+    assert_eq!(
+        interpreter.eval_with("pos('b', 'abc')")?,
+        NumericType::Integer(2)
+    );
+    assert_eq!(
+        interpreter.eval_with("pos('x', 'abc')")?,
+        NumericType::Integer(0)
+    );
+    assert_eq!(
+        interpreter.eval_with("pos('', 'abc')")?,
+        NumericType::Integer(0)
+    );
+    assert_eq!(
+        evaluate("copy('hello', 2, 3)")?,
+        Value::String("ell".to_string())
+    );
+    assert_eq!(
+        evaluate("copy('hello', 4, 10)")?,
+        Value::String("lo".to_string())
+    );
+    assert_eq!(
+        evaluate("copy('hello', 0, 2)")?,
+        Value::String("he".to_string())
+    );
+    assert_eq!(
+        evaluate("copy('hello', 9, 2)")?,
+        Value::String("".to_string())
+    );
+    assert_eq!(
+        evaluate("copy('hello', 2, -1)")?,
+        Value::String("".to_string())
+    );
+
+    assert_eq!(
+        evaluate("length(12)").unwrap_err().to_string(),
+        "length expects a string, found integer"
+    );
+    assert!(evaluate("copy('hello', 1.5, 2)").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_variables_in_expression_mode() {
+    let error = Interpreter::default().eval_with("x + 1").unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Cannot use x: variables aren't available in expression mode; define a program"
+    );
+
+    let mut seeded = Interpreter::default();
+    seeded
+        .global_scope
+        .insert("y".to_string(), NumericType::Integer(1).into());
+    assert_eq!(
+        seeded.eval_with("x + 1").unwrap_err().to_string(),
+        "x not defined"
+    );
+}
+
+#[test]
+fn test_case_statement() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Cases;
+        VAR
+            x, y, z : INTEGER;
+        BEGIN
+            x := -1;
+            CASE x OF
+                -2, 0: y := 10;
+                -1: y := 20;
+                1: y := 30
+            END;
+            CASE x + 2 OF
+                0: z := 1
+            ELSE
+                z := 2
+            END;
+            CASE 7 OF
+                1: z := 3;
+            END
+        END.
+    "#;
+
+    let mut interpreter = Interpreter::default();
+    interpreter.interpret(&Parser::new(Lexer::new(code)).parse()?)?;
+
+    assert_eq!(interpreter.eval_with("y")?, NumericType::Integer(20));
+    assert_eq!(interpreter.eval_with("z")?, NumericType::Integer(2));
+    Ok(())
+}
+
+#[test]
+fn test_mod_follows_pascal_signs() -> anyhow::Result<()> {
+    let interpreter = Interpreter::default();
+
+    assert_eq!(interpreter.eval_with("-7 mod 3")?, NumericType::Integer(2));
+    assert_eq!(interpreter.eval_with("7 mod 3")?, NumericType::Integer(1));
+    assert_eq!(interpreter.eval_with("-6 mod 3")?, NumericType::Integer(0));
+    assert_eq!(
+        interpreter.eval_with("7 mod -3").unwrap_err().to_string(),
+        "modulo (mod) by the negative number -3"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_case_label_ranges() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Ranges;
+        VAR
+            x, y, z : INTEGER;
+        BEGIN
+            x := 3;
+            CASE x OF
+                1..5: y := 1;
+                6, 8, 10: y := 2
+            END;
+            CASE x + 5 OF
+                1..5: z := 1;
+                6, 8, 10: z := 2
+            END
+        END.
+    "#;
+
+    let mut interpreter = Interpreter::default();
+    interpreter.interpret(&Parser::new(Lexer::new(code)).parse()?)?;
+
+    assert_eq!(interpreter.eval_with("y")?, NumericType::Integer(1));
+    assert_eq!(interpreter.eval_with("z")?, NumericType::Integer(2));
+    Ok(())
+}
+
+#[test]
+fn test_real_case_selector() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Reals;
+        VAR
+            x : INTEGER;
+        BEGIN
+            CASE 0.1 + 0.2 OF
+                0.3: x := 1;
+                -0.3, 2: x := 2
+            ELSE
+                x := 3
+            END
+        END.
+    "#;
+    let program = Parser::new(Lexer::new(code)).parse()?;
+
+    let mut interpreter = Interpreter::default();
+    interpreter.interpret(&program)?;
+    assert_eq!(interpreter.eval_with("x")?, NumericType::Integer(3));
+
+    let mut interpreter = Interpreter {
+        real_eq_epsilon: 1e-9,
+        ..Interpreter::default()
+    };
+    interpreter.interpret(&program)?;
+    assert_eq!(interpreter.eval_with("x")?, NumericType::Integer(1));
+    Ok(())
+}
+
+#[test]
+fn test_else_if_chain() -> anyhow::Result<()> {
+    let sign = |x: i32| -> anyhow::Result<String> {
+        let code = format!(
+            r#"
+            PROGRAM Sign;
+            VAR
+                x : INTEGER;
+            BEGIN
+                x := {};
+                IF x < 0 THEN
+                    write('negative')
+                ELSE IF x = 0 THEN
+                    write('zero')
+                ELSE
+                    write('positive')
+            END.
+            "#,
+            x
+        );
+        run_capturing_output(&code)
+    };
+
+    assert_eq!(sign(-5)?, "negative");
+    assert_eq!(sign(0)?, "zero");
+    assert_eq!(sign(7)?, "positive");
+    assert_eq!(
+        run_capturing_output("PROGRAM P; BEGIN IF 1 THEN write(1) END.")
+            .unwrap_err()
+            .to_string(),
+        "IF condition must be a boolean, found the integer 1"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_with_needs_a_record() -> anyhow::Result<()> {
+    let code = "PROGRAM P; VAR r : INTEGER; BEGIN r := 1; WITH r DO r := 2 END.";
+    assert_eq!(
+        run_capturing_output(code).unwrap_err().to_string(),
+        "WITH needs a record, but r is integer"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_array_bounds() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Bounds;
+        VAR
+            a : ARRAY[1..10] OF INTEGER;
+            b : ARRAY[-2..2] OF REAL;
+            lo, hi, width : INTEGER;
+        BEGIN
+            lo := low(a);
+            hi := High(a);
+            width := high(b) - low(b) + 1
+        END.
+    "#;
+    let (_, interpreter) = Interpreter::run_and_capture(code)?;
+    assert_eq!(interpreter.eval_with("lo")?, NumericType::Integer(1));
+    assert_eq!(interpreter.eval_with("hi")?, NumericType::Integer(10));
+    assert_eq!(interpreter.eval_with("width")?, NumericType::Integer(5));
+
+    assert_eq!(
+        run_capturing_output("PROGRAM P; VAR x : INTEGER; BEGIN x := 1; x := low(x) END.")
+            .unwrap_err()
+            .to_string(),
+        "low expects an array, but x is integer"
+    );
+    assert!(interpreter.eval_with("high(3)").is_err());
+
+    let declare = |bounds: &str| {
+        let code = format!(
+            "PROGRAM P; VAR a : ARRAY[{}] OF INTEGER; BEGIN END.",
+            bounds
+        );
+        run_capturing_output(&code).map(|_| ())
+    };
+    assert_eq!(
+        declare("-2147483647..2147483647").unwrap_err().to_string(),
+        "array -2147483647..2147483647 is too large, arrays hold at most \
+         16777216 elements"
+    );
+    assert_eq!(
+        declare("1..2000000000").unwrap_err().to_string(),
+        "array 1..2000000000 is too large, arrays hold at most 16777216 elements"
+    );
+
+    let code = r#"
+        PROGRAM Last;
+        VAR
+            a : ARRAY[2147483647..2147483647] OF INTEGER;
+            hi : INTEGER;
+        BEGIN
+            hi := high(a)
+        END.
+    "#;
+    let (_, interpreter) = Interpreter::run_and_capture(code)?;
+    assert_eq!(
+        interpreter.eval_with("hi")?,
+        NumericType::Integer(2147483647)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_halt() -> anyhow::Result<()> {
+    use crate::interpreting::built_ins::Halt;
+
+    let mut interpreter = Interpreter::default();
+    let error = interpreter
+        .interpret(&Parser::new(Lexer::new("PROGRAM p; BEGIN halt END.")).parse()?)
+        .unwrap_err();
+    assert_eq!(error.downcast_ref::<Halt>(), Some(&Halt { code: 0 }));
+
+    let error = interpreter
+        .interpret(&Parser::new(Lexer::new("PROGRAM p; BEGIN halt(1.5) END.")).parse()?)
+        .unwrap_err();
+    assert!(error.downcast_ref::<Halt>().is_none());
+    Ok(())
+}
+
+#[test]
+fn test_assert() -> anyhow::Result<()> {
+    let run = |body: &str| {
+        let code = format!(
+            "PROGRAM Checks; VAR x : INTEGER; BEGIN x := 2; {} END.",
+            body
+        );
+        Interpreter::run_and_capture(&code).map(|(output, _)| output)
+    };
+
+    assert_eq!(run("assert(x = 2); write('done')")?, "done");
+    assert_eq!(run("assert(x > 1, 'x is too small')")?, "");
+    assert_eq!(
+        run("assert(x = 3)").unwrap_err().to_string(),
+        "Assertion failed"
+    );
+    assert_eq!(
+        run("assert(x < 0, 'x must be negative')")
+            .unwrap_err()
+            .to_string(),
+        "Assertion failed: x must be negative"
+    );
+    assert_eq!(
+        run("assert(x)").unwrap_err().to_string(),
+        "assert expects a boolean condition, found the integer 2"
+    );
+    assert_eq!(
+        run("assert").unwrap_err().to_string(),
+        "assert expects 1 or 2 arguments, found 0"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_program_name_after_interpreting() -> anyhow::Result<()> {
+    let mut interpreter = Interpreter::default();
+    assert_eq!(interpreter.program_name, None);
+    interpreter.interpret(&Parser::new(Lexer::new("PROGRAM Part10AST; BEGIN END.")).parse()?)?;
+    assert_eq!(interpreter.program_name.as_deref(), Some("Part10AST"));
+    Ok(())
+}
+
+#[test]
+fn test_reset() -> anyhow::Result<()> {
+    let buffer = SharedBuffer::default();
+    let mut interpreter = Interpreter {
+        output: Box::new(buffer.clone()),
+        strict: true,
+        profile: true,
+        ..Interpreter::default()
+    };
+    let program = |code: &str| Parser::new(Lexer::new(code)).parse();
+
+    interpreter.interpret(&program(
+        "PROGRAM A; VAR x : INTEGER; PROCEDURE P; BEGIN END; BEGIN x := 1; write(x) END.",
+    )?)?;
+    interpreter.reset();
+    assert!(interpreter.global_scope.is_empty());
+    assert!(interpreter.symbol_table.is_none());
+    assert!(interpreter.program_name.is_none());
+    assert!(interpreter.node_visits().is_empty());
+    assert!(interpreter.strict && interpreter.profile);
+    assert!(interpreter.eval_with("x").is_err());
+
+    // P was forgotten along with x, so it can't be called without being declared again
+    assert!(interpreter
+        .interpret(&program("PROGRAM B; BEGIN P END.")?)
+        .is_err());
+    interpreter.reset();
+    interpreter.interpret(&program("PROGRAM C; BEGIN write(2) END.")?)?;
+    assert_eq!(buffer.contents(), "12");
+    Ok(())
+}
+
+/// An output sink whose contents stay readable after it is handed to an interpreter
+#[cfg(test)]
+#[derive(Clone, Default)]
+struct SharedBuffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>);
+
+#[cfg(test)]
+impl SharedBuffer {
+    fn contents(&self) -> String {
+        String::from_utf8_lossy(&self.0.lock().unwrap()).into_owned()
+    }
+}
+
+#[cfg(test)]
+impl Write for SharedBuffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+impl Interpreter {
+    /// Interprets the program in `source` with its output going to memory, returning everything
+    /// it wrote along with the finished interpreter for inspecting its variables
+    pub fn run_and_capture(source: &str) -> anyhow::Result<(String, Interpreter)> {
+        Interpreter::default().capture(source)
+    }
+
+    /// Like `run_and_capture`, keeping the other settings of this interpreter
+    fn capture(mut self, source: &str) -> anyhow::Result<(String, Interpreter)> {
+        let buffer = SharedBuffer::default();
+        self.output = Box::new(buffer.clone());
+        self.interpret(&Parser::new(Lexer::new(source)).parse()?)?;
+        Ok((buffer.contents(), self))
+    }
+}
+
+/// Interprets `code`, returning everything it wrote
+#[cfg(test)]
+fn run_capturing_output(code: &str) -> anyhow::Result<String> {
+    Interpreter::run_and_capture(code).map(|(output, _)| output)
+}
+
+#[test]
+fn test_tee_output() -> anyhow::Result<()> {
+    /// Duplicates everything written into two sinks
+    struct Tee<A, B>(A, B);
+
+    impl<A: Write, B: Write> Write for Tee<A, B> {
+        fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+            self.0.write_all(buf)?;
+            self.1.write_all(buf)?;
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> io::Result<()> {
+            self.0.flush()?;
+            self.1.flush()
+        }
+    }
+
+    let (terminal, log) = (SharedBuffer::default(), SharedBuffer::default());
+    let mut interpreter = Interpreter {
+        output: Box::new(Tee(terminal.clone(), log.clone())),
+        ..Interpreter::default()
+    };
+    let code = "PROGRAM Tee; BEGIN writeln('first'); write(1, 2); writeln END.";
+    interpreter.interpret(&Parser::new(Lexer::new(code)).parse()?)?;
+
+    assert_eq!(terminal.contents(), "first\n12\n");
+    assert_eq!(log.contents(), terminal.contents());
+    Ok(())
+}
+
+#[test]
+fn test_writeln_booleans_and_strings() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Output;
+        VAR
+            flag : BOOLEAN;
+            s : STRING;
+            x : INTEGER;
+        BEGIN
+            flag := true;
+            s := 'it''s';
+            x := 42;
+            writeln(flag);
+            writeln(FALSE);
+            writeln(s);
+            writeln('x is ', x, ' and done');
+            write(s, flag)
+        END.
+    "#;
+
+    assert_eq!(
+        run_capturing_output(code)?,
+        "TRUE\nFALSE\nit's\nx is 42 and done\nit'sTRUE"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_assignments_match_declared_types() {
+    let assign = |declaration: &str, value: &str| {
+        run_capturing_output(&format!(
+            "PROGRAM p; VAR v : {}; BEGIN v := {} END.",
+            declaration, value
+        ))
+    };
+
+    assert!(assign("STRING", "'text'").is_ok());
+    assert!(assign("BOOLEAN", "false").is_ok());
+    assert!(assign("REAL", "1").is_ok());
+    assert_eq!(
+        assign("INTEGER", "'text'").unwrap_err().to_string(),
+        "Cannot assign the string text to the integer v"
+    );
+    assert!(assign("STRING", "1").is_err());
+    assert!(assign("BOOLEAN", "'true'").is_err());
+}
+
+#[test]
+fn test_array_elements_match_the_element_type() {
+    let assign = |declaration: &str, value: &str| {
+        run_capturing_output(&format!(
+            "PROGRAM p; VAR a : ARRAY [1..3] OF {}; BEGIN a[1] := {} END.",
+            declaration, value
+        ))
+    };
+
+    assert!(assign("INTEGER", "7").is_ok());
+    assert!(assign("REAL", "7").is_ok());
+    assert_eq!(
+        assign("INTEGER", "'text'").unwrap_err().to_string(),
+        "Cannot assign the string text to the integer a[1]"
+    );
+    assert!(assign("INTEGER", "TRUE").is_err());
+}
+
+#[test]
+fn test_comparisons() -> anyhow::Result<()> {
+    use crate::parsing::parser::parse_expr;
+
+    let interpreter = Interpreter::default();
+    let evaluate = |code: &str| interpreter.evaluate(&parse_expr(code)?);
+
+    assert_eq!(evaluate("1 < 2")?, Value::Boolean(true));
+    assert_eq!(evaluate("2 + 3 = 5")?, Value::Boolean(true));
+    assert_eq!(evaluate("1 <> 1.0")?, Value::Boolean(false));
+    assert_eq!(evaluate("2.5 >= 3")?, Value::Boolean(false));
+    assert_eq!(evaluate("'abc' <= 'abd'")?, Value::Boolean(true));
+    assert_eq!(evaluate("false < true")?, Value::Boolean(true));
+    assert_eq!(
+        evaluate("1 = 'one'").unwrap_err().to_string(),
+        "Cannot compare the integer 1 with the string one"
+    );
+    assert!(interpreter.eval_with("1 < 2").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_while_loop() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Loop;
+        VAR
+            i, total : INTEGER;
+        BEGIN
+            i := 0;
+            total := 0;
+            WHILE i < 5 DO
+            BEGIN
+                i := i + 1;
+                total := total + i
+            END;
+            WHILE false DO total := 0
+        END.
+    "#;
+
+    let mut interpreter = Interpreter::default();
+    interpreter.interpret(&Parser::new(Lexer::new(code)).parse()?)?;
+    assert_eq!(interpreter.eval_with("total")?, NumericType::Integer(15));
+
+    assert_eq!(
+        run_capturing_output("PROGRAM p; BEGIN WHILE 1 DO END.")
+            .unwrap_err()
+            .to_string(),
+        "WHILE condition must be a boolean, found the integer 1"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_profile_counts_loop_bodies() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Profiled;
+        VAR
+            i : INTEGER;
+        BEGIN
+            i := 0;
+            WHILE i < 10 DO
+                i := i + 1
+        END.
+    "#;
+    let ast = Parser::new(Lexer::new(code)).parse()?;
+
+    let mut interpreter = Interpreter {
+        profile: true,
+        ..Interpreter::default()
+    };
+    interpreter.interpret(&ast)?;
+    let visits = interpreter.node_visits();
+
+    assert_eq!(visits["While"], 1);
+    assert_eq!(visits["LessThan"], 11);
+    assert_eq!(visits["Assign"], 11);
+    assert_eq!(visits["Add"], 10);
+    assert_eq!(visits["Variable"], 21);
+    assert_eq!(visits["IntegerConstant"], 22);
+
+    let mut unprofiled = Interpreter::default();
+    unprofiled.interpret(&ast)?;
+    assert!(unprofiled.node_visits().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_interpret_with_prebuilt_table() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Doubling;
+        VAR
+            x, doubled : INTEGER;
+        BEGIN
+            doubled := x * 2
+        END.
+    "#;
+    let ast = Parser::new(Lexer::new(code)).parse()?;
+    let symbol_table = SymbolTable::build_for(&ast, false)?;
+
+    let log = SharedBuffer::default();
+    let mut interpreter = Interpreter::new(true);
+    interpreter.symbol_table_log = Box::new(log.clone());
+    for x in [3, 10] {
+        interpreter
+            .global_scope
+            .insert("x".to_string(), NumericType::Integer(x).into());
+        interpreter.interpret_with_table(&ast, &symbol_table)?;
+        assert_eq!(
+            interpreter.eval_with("doubled")?,
+            NumericType::Integer(x * 2)
+        );
+    }
+    // Building a table in verbose mode would have logged its definitions
+    assert!(log.contents().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_writeln_without_arguments() -> anyhow::Result<()> {
+    assert_eq!(
+        run_capturing_output("PROGRAM P; BEGIN writeln; END.")?,
+        "\n"
+    );
+    assert_eq!(
+        run_capturing_output("PROGRAM P; BEGIN WriteLn() END.")?,
+        "\n"
+    );
+    assert_eq!(
+        run_capturing_output("PROGRAM P; BEGIN write; write() END.")?,
+        ""
+    );
+    assert_eq!(
+        run_capturing_output("PROGRAM P; BEGIN write('a'); writeln; writeln; write('b') END.")?,
+        "a\n\nb"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_var_parameter_swap() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Swapping;
+        VAR
+            x, y : INTEGER;
+
+        PROCEDURE Swap(VAR a, b : INTEGER);
+        VAR
+            t : INTEGER;
+        BEGIN
+            t := a;
+            a := b;
+            b := t
+        END;
+
+        BEGIN
+            x := 1;
+            y := 2;
+            Swap(x, y);
+            writeln(x, ' ', y)
+        END.
+    "#;
+    let (output, interpreter) = Interpreter::run_and_capture(code)?;
+    assert_eq!(output, "2 1\n");
+    assert_eq!(
+        interpreter.global_scope.get("x"),
+        Some(&Value::Numeric(NumericType::Integer(2)))
+    );
+    // The procedure's local is gone with its frame
+    assert_eq!(interpreter.global_scope.get("t"), None);
+    Ok(())
+}
+
+#[test]
+fn test_procedure_parameters_and_scopes() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Scopes;
+        VAR
+            total, n : INTEGER;
+
+        PROCEDURE AddTo(VAR sum : INTEGER; n : INTEGER);
+        VAR
+            step : INTEGER;
+
+            PROCEDURE Bump(VAR by : INTEGER);
+            BEGIN
+                sum := sum + by;
+                by := by + 1
+            END;
+
+        BEGIN
+            step := n;
+            Bump(step);
+            Bump(step);
+            n := 0
+        END;
+
+        PROCEDURE Countdown(n : INTEGER);
+        BEGIN
+            write(n);
+            CASE n OF
+                0: writeln
+            ELSE
+                Countdown(n - 1)
+            END
+        END;
+
+        BEGIN
+            total := 100;
+            n := 5;
+            AddTo(total, n);
+            writeln(total, ' ', n);
+            Countdown(3)
+        END.
+    "#;
+    assert_eq!(run_capturing_output(code)?, "111 5\n3210\n");
+
+    let by_value =
+        "PROGRAM P; PROCEDURE Inc(VAR i : INTEGER); BEGIN i := i + 1 END; BEGIN Inc(1) END.";
+    assert!(run_capturing_output(by_value)
+        .unwrap_err()
+        .to_string()
+        .contains("VAR parameter i of Inc must be passed a variable"));
+
+    let wrong_count =
+        "PROGRAM P; PROCEDURE Inc(VAR i : INTEGER); BEGIN i := i + 1 END; BEGIN Inc END.";
+    assert_eq!(
+        run_capturing_output(wrong_count).unwrap_err().to_string(),
+        "Inc expects 1 argument(s), found 0"
+    );
+
+    let wrong_type = |declaration: &str| {
+        let code = format!(
+            "PROGRAM P; VAR r : {}; PROCEDURE Inc(VAR i : INTEGER); BEGIN i := i + 1 END; \
+             BEGIN Inc(r) END.",
+            declaration
+        );
+        run_capturing_output(&code).unwrap_err().to_string()
+    };
+    assert_eq!(
+        wrong_type("REAL"),
+        "VAR parameter i of Inc is integer, but r is real"
+    );
+    assert_eq!(
+        wrong_type("STRING"),
+        "VAR parameter i of Inc is integer, but r is string"
+    );
+
+    let constant = r#"
+        PROGRAM P;
+        CONST Limit = 10;
+        PROCEDURE Inc(VAR i : INTEGER); BEGIN i := i + 1 END;
+        BEGIN Inc(Limit) END.
+    "#;
+    assert_eq!(
+        run_capturing_output(constant).unwrap_err().to_string(),
+        "VAR parameter i of Inc must be passed a variable, but Limit is not one"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_runaway_recursion() {
+    let code = "PROGRAM P; PROCEDURE Forever; BEGIN Forever END; BEGIN Forever END.";
+    assert_eq!(
+        run_capturing_output(code).unwrap_err().to_string(),
+        "Cannot call Forever: call stack too deep, at most 200 procedures may run at once"
+    );
+
+    let deep = r#"
+        PROGRAM P;
+        VAR n : INTEGER;
+        PROCEDURE Down(depth : INTEGER);
+        BEGIN
+            n := depth;
+            IF depth > 1 THEN Down(depth - 1)
+        END;
+        BEGIN Down(150); writeln(n) END.
+    "#;
+    assert_eq!(run_capturing_output(deep).unwrap(), "1\n");
+}
+
+#[test]
+fn test_in_operator() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Membership;
+        VAR
+            x : INTEGER;
+        BEGIN
+            x := 0;
+            WHILE x <= 12 DO
+            BEGIN
+                CASE x OF
+                    0: ;
+                ELSE
+                    write(x in [1, 3, 5], ' ', x in [2..4, 10..11], ' ', x in [])
+                END;
+                writeln;
+                x := x + 3
+            END
+        END.
+    "#;
+    assert_eq!(
+        run_capturing_output(code)?,
+        "\nTRUE TRUE FALSE\nFALSE FALSE FALSE\nFALSE FALSE FALSE\nFALSE FALSE FALSE\n"
+    );
+
+    let interpreter = Interpreter::default();
+    let evaluate = |code: &str| -> anyhow::Result<Value> {
+        interpreter.evaluate(&Parser::new(Lexer::new(code)).parse_expression()?)
+    };
+    assert_eq!(evaluate("4 in [1..3, 2 + 2]")?, Value::Boolean(true));
+    assert_eq!(evaluate("-1 in [0..3]")?, Value::Boolean(false));
+    assert_eq!(
+        evaluate("1.5 in [1..2]").unwrap_err().to_string(),
+        "in only supports integers, found the real 1.5"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_interpret_standalone_statement() -> anyhow::Result<()> {
+    let mut interpreter = Interpreter::default();
+    let statement = Parser::new(Lexer::new("BEGIN x := 1; WHILE x < 100 DO x := x * 3 END"))
+        .parse_statement()?;
+    interpreter.interpret_node(&statement)?;
+    assert_eq!(
+        interpreter.global_scope.get("x"),
+        Some(&Value::Numeric(NumericType::Integer(243)))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_overflow_policy() -> anyhow::Result<()> {
+    let evaluate = |overflow: OverflowPolicy, code: &str| {
+        Interpreter {
+            overflow,
+            ..Interpreter::default()
+        }
+        .eval_with(code)
+    };
+
+    assert_eq!(
+        evaluate(OverflowPolicy::Wrap, "65536 * 65536")?,
+        NumericType::Integer(0)
+    );
+    assert_eq!(
+        evaluate(OverflowPolicy::Wrap, "2147483647 + 1")?,
+        NumericType::Integer(IntegerMachineType::MIN)
+    );
+    assert_eq!(
+        evaluate(OverflowPolicy::Saturate, "65536 * 65536")?,
+        NumericType::Integer(IntegerMachineType::MAX)
+    );
+    assert_eq!(
+        evaluate(OverflowPolicy::Saturate, "-65536 * 65536")?,
+        NumericType::Integer(IntegerMachineType::MIN)
+    );
+    assert_eq!(
+        evaluate(OverflowPolicy::Error, "65536 * 65536")
+            .unwrap_err()
+            .to_string(),
+        "integer overflow in 65536 * 65536"
+    );
+    assert_eq!(
+        evaluate(OverflowPolicy::Wrap, "2 ** 32")?,
+        NumericType::Integer(0)
+    );
+    assert_eq!(
+        evaluate(OverflowPolicy::Saturate, "-2 ** 31")?,
+        NumericType::Integer(-IntegerMachineType::MAX)
+    );
+    assert_eq!(OverflowPolicy::default(), OverflowPolicy::Error);
+    assert!(evaluate(OverflowPolicy::default(), "-2147483647 - 2").is_err());
+
+    // Reals never overflow into an error
+    assert_eq!(
+        evaluate(OverflowPolicy::Error, "65536.0 * 65536")?,
+        NumericType::Real(4294967296.0)
+    );
+    Ok(())
+}
+
+#[test]
+fn test_constants() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Constants;
+        CONST
+            Greeting = 'Hello, ' + 'World';
+            Limit = 3;
+        VAR
+            i : INTEGER;
+
+        PROCEDURE Greet;
+        CONST
+            Suffix = '!';
+        BEGIN
+            writeln(Greeting, Suffix)
+        END;
+
+        BEGIN
+            i := 0;
+            WHILE i < Limit DO
+                i := i + 1;
+            Greet;
+            writeln(i)
+        END.
+    "#;
+    let (output, interpreter) = Interpreter::run_and_capture(code)?;
+    assert_eq!(output, "Hello, World!\n3\n");
+    assert_eq!(
+        interpreter.global_scope.get("greeting"),
+        Some(&Value::String("Hello, World".to_string()))
+    );
+    assert_eq!(interpreter.global_scope.get("Suffix"), None);
+
+    let assigned = "PROGRAM P; CONST Limit = 3; BEGIN Limit := 4 END.";
+    assert_eq!(
+        run_capturing_output(assigned).unwrap_err().to_string(),
+        "Cannot assign to the constant Limit"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_runtime_errors_point_at_the_source() -> anyhow::Result<()> {
+    let run = |source: &str| -> anyhow::Result<()> {
+        let ast = Parser::with_positions(Lexer::new(source)).parse()?;
+        Interpreter::default().interpret(&ast)
+    };
+    let error = |source: &str| run(source).unwrap_err().to_string();
+
+    let source = "PROGRAM P;\nVAR x : INTEGER;\nBEGIN\n   x := 0;\n   x := 10 div x\nEND.";
+    assert_eq!(
+        error(source),
+        "integer division (div) by zero at line 5, column 12"
+    );
+    // The innermost failing expression is reported
+    assert_eq!(
+        error("PROGRAM P;\nVAR x : INTEGER;\nBEGIN\n  x := 1 + (2147483647 + 1) * 2\nEND."),
+        "integer overflow in 2147483647 + 1 at line 4, column 24"
+    );
+    assert_eq!(
+        error("PROGRAM P;\nBEGIN\n  writeln(sqrt(-1))\nEND."),
+        format!(
+            "{} at line 3, column 11",
+            Interpreter::default()
+                .evaluate(&crate::parsing::parser::parse_expr("sqrt(-1)")?)
+                .unwrap_err()
+        )
+    );
+    // Positions are only known when the parser tracks them
+    let ast = crate::parsing::parser::parse_program(source)?;
+    assert_eq!(
+        Interpreter::default()
+            .interpret(&ast)
+            .unwrap_err()
+            .to_string(),
+        "integer division (div) by zero"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_on_assign() -> anyhow::Result<()> {
+    let assignments = Rc::new(RefCell::new(vec![]));
+    let mut interpreter = Interpreter::default();
+    let recorded = assignments.clone();
+    interpreter.on_assign = Some(Box::new(move |name, value| {
+        recorded.borrow_mut().push((name.to_string(), *value))
+    }));
+
+    let program = "PROGRAM Trace;
+    VAR i, total : INTEGER; average : REAL; name : STRING;
+    BEGIN
+        total := 0;
+        i := 1;
+        name := 'trace';
+        WHILE i <= 2 DO
+        BEGIN
+            total := total + i;
+            i := i + 1
+        END;
+        average := total / 2
+    END.";
+    interpreter.interpret(&crate::parsing::parser::parse_program(program)?)?;
+
+    let expected = [
+        ("total", NumericType::Integer(0)),
+        ("i", NumericType::Integer(1)),
+        ("total", NumericType::Integer(1)),
+        ("i", NumericType::Integer(2)),
+        ("total", NumericType::Integer(3)),
+        ("i", NumericType::Integer(3)),
+        ("average", NumericType::Real(1.5)),
+    ];
+    assert_eq!(
+        *assignments.borrow(),
+        expected.map(|(name, value)| (name.to_string(), value))
+    );
+    Ok(())
+}
+
+#[test]
+fn test_output_limit() -> anyhow::Result<()> {
+    let buffer = SharedBuffer::default();
+    let mut interpreter = Interpreter {
+        output: Box::new(crate::interpreting::output::LimitedOutput::new(
+            buffer.clone(),
+            10,
+        )),
+        ..Interpreter::default()
+    };
+    let program = "PROGRAM Runaway; VAR i : INTEGER; BEGIN i := 0; WHILE 1 = 1 DO BEGIN writeln(i); i := i + 1 END END.";
+    let error = interpreter
+        .interpret(&Parser::new(Lexer::new(program)).parse()?)
+        .unwrap_err();
+
+    assert_eq!(error.to_string(), "output limit exceeded");
+    assert_eq!(buffer.contents(), "0\n1\n2\n3\n4\n");
+    assert_eq!(interpreter.eval_with("i")?, NumericType::Integer(5));
+    Ok(())
+}
+
+#[test]
+fn test_short_circuit_loop_guard() -> anyhow::Result<()> {
+    // Without short-circuiting the last check of the condition would read a[6]
+    let (_, interpreter) = Interpreter::run_and_capture(
+        "PROGRAM Guard;
+        VAR a : ARRAY[1..5] OF INTEGER; i, n, total : INTEGER; found : BOOLEAN;
+        BEGIN
+            a[1] := 3; a[2] := 1; a[3] := 4; a[4] := 1; a[5] := 5;
+            n := 5; i := 1; total := 0;
+            WHILE (i <= n) AND (a[i] > 0) DO
+            BEGIN
+                total := total + a[i];
+                i := i + 1
+            END;
+            found := (i > n) OR (a[i] = 0)
+        END.",
+    )?;
+    assert_eq!(interpreter.eval_with("total")?, NumericType::Integer(14));
+    assert_eq!(interpreter.eval_with("i")?, NumericType::Integer(6));
+    assert_eq!(
+        interpreter.evaluate(&crate::parsing::parser::parse_expr("found")?)?,
+        Value::Boolean(true)
+    );
+    assert!(interpreter
+        .evaluate(&crate::parsing::parser::parse_expr(
+            "(i <= n) OR (a[i] > 0)"
+        )?)
+        .is_err());
+
+    let evaluate =
+        |code: &str| Interpreter::default().evaluate(&crate::parsing::parser::parse_expr(code)?);
+    assert_eq!(evaluate("NOT (1 > 2) AND TRUE")?, Value::Boolean(true));
+    assert_eq!(evaluate("FALSE OR NOT TRUE")?, Value::Boolean(false));
+    assert_eq!(
+        evaluate("TRUE AND 1").unwrap_err().to_string(),
+        "AND needs boolean operands, found the integer 1"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_calling_a_procedure_declared_later() -> anyhow::Result<()> {
+    let (output, interpreter) = Interpreter::run_and_capture(
+        "PROGRAM Forward;
+        VAR x : INTEGER;
+        PROCEDURE First;
+        BEGIN
+            Second(2);
+            writeln('first')
+        END;
+        PROCEDURE Second(value : INTEGER);
+        BEGIN
+            x := value;
+            writeln('second')
+        END;
+        BEGIN
+            First
+        END.",
+    )?;
+    assert_eq!(output, "second\nfirst\n");
+    assert_eq!(interpreter.eval_with("x")?, NumericType::Integer(2));
+    Ok(())
+}
+
+#[test]
+fn test_real_display_precision() -> anyhow::Result<()> {
+    let program = "PROGRAM Precision;
+        VAR a : ARRAY[1..2] OF REAL;
+        BEGIN
+            a[1] := 0.5; a[2] := 1 / 3;
+            writeln(20 / 7, ' ', 3, ' ', TRUE, ' ', 'text');
+            writeln(a)
+        END.";
+    let interpreter = Interpreter {
+        real_display_precision: Some(2),
+        ..Default::default()
+    };
+    let (output, _) = interpreter.capture(program)?;
+    assert_eq!(output, "2.86 3 TRUE text\n[0.50, 0.33]\n");
+
+    let (output, _) = Interpreter::run_and_capture(program)?;
+    assert_eq!(
+        output,
+        "2.857142857142857 3 TRUE text\n[0.5, 0.3333333333333333]\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_interpret_statements() -> anyhow::Result<()> {
+    use crate::parsing::ast::Variable;
+
+    let mut interpreter = Interpreter::default();
+    let generated = (1..=1000).map(|i| {
+        Ok(Ast::Assign(
+            Variable::from("total"),
+            Box::from(Ast::Add(
+                Box::from(Ast::var("total")),
+                Box::from(Ast::int(i)),
+            )),
+        ))
+    });
+    interpreter.interpret_statements(
+        std::iter::once(Ok(Ast::Assign(
+            Variable::from("total"),
+            Box::from(Ast::int(0)),
+        )))
+        .chain(generated),
+    )?;
+    assert_eq!(
+        interpreter.eval_with("total")?,
+        NumericType::Integer(500500)
+    );
+
+    interpreter.interpret_statements(
+        Parser::new(Lexer::new("a := 2; b := a * 3; BEGIN c := a + b END;")).statements(),
+    )?;
+    assert_eq!(interpreter.eval_with("c")?, NumericType::Integer(8));
+
+    // The statements before a bad one still run
+    let error = interpreter
+        .interpret_statements(Parser::new(Lexer::new("d := 1; e := ; f := 2")).statements())
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Expected integer, parenthesis, or variable instead of ;"
+    );
+    assert_eq!(interpreter.eval_with("d")?, NumericType::Integer(1));
+    assert!(interpreter.eval_with("f").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_and_or_on_integers_are_bitwise() -> anyhow::Result<()> {
+    let evaluate =
+        |code: &str| Interpreter::default().evaluate(&crate::parsing::parser::parse_expr(code)?);
+    assert_eq!(evaluate("TRUE AND FALSE")?, Value::Boolean(false));
+    assert_eq!(evaluate("FALSE OR TRUE")?, Value::Boolean(true));
+    assert_eq!(evaluate("12 AND 10")?, Value::from(NumericType::Integer(8)));
+    assert_eq!(evaluate("12 OR 10")?, Value::from(NumericType::Integer(14)));
+    assert_eq!(
+        evaluate("-1 AND 255")?,
+        Value::from(NumericType::Integer(255))
+    );
+    assert_eq!(
+        Interpreter::default().eval_with("(12 AND 10) + 1")?,
+        NumericType::Integer(9)
+    );
+
+    assert_eq!(
+        evaluate("12 AND TRUE").unwrap_err().to_string(),
+        "AND needs two booleans or two integers, found the integer 12 and the boolean TRUE"
+    );
+    assert_eq!(
+        evaluate("FALSE OR 1").unwrap_err().to_string(),
+        "OR needs boolean operands, found the integer 1"
+    );
+    assert_eq!(
+        evaluate("1.5 OR 1").unwrap_err().to_string(),
+        "OR needs boolean or integer operands, found the real 1.5"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_enumerated_types() -> anyhow::Result<()> {
+    let (output, interpreter) = Interpreter::run_and_capture(
+        "PROGRAM Colors;
+        TYPE
+            Color = (Red, Green, Blue);
+        VAR
+            c, d : Color;
+            before : BOOLEAN;
+        BEGIN
+            c := Green;
+            d := succ(c);
+            before := c < d;
+            writeln(ord(Red), ord(c), ord(d), ' ', c = Green, ' ', pred(c) = Red)
+        END.",
+    )?;
+    assert_eq!(output, "012 TRUE TRUE\n");
+    assert_eq!(
+        interpreter.eval_with("ord(Green)")?,
+        NumericType::Integer(1)
+    );
+    assert_eq!(interpreter.eval_with("ord(d)")?, NumericType::Integer(2));
+    assert_eq!(
+        interpreter.evaluate(&crate::parsing::parser::parse_expr("before")?)?,
+        Value::Boolean(true)
+    );
+    assert_eq!(interpreter.eval_with("ord(TRUE)")?, NumericType::Integer(1));
+    assert_eq!(
+        interpreter.eval_with("ord(1.5)").unwrap_err().to_string(),
+        "ord expects an ordinal value, found real"
+    );
+
+    let error = |code: &str| match Interpreter::run_and_capture(code) {
+        Ok(_) => panic!("Expected {} to fail", code),
+        Err(err) => err.to_string(),
+    };
+    assert_eq!(
+        error("PROGRAM P; TYPE Color = (Red, Green); Light = (Green); BEGIN END."),
+        "Duplicate Identifier: \"Green\""
+    );
+    assert_eq!(
+        error("PROGRAM P; TYPE Color = (Red); BEGIN Red := 1 END."),
+        "Cannot assign to the constant Red"
+    );
+    assert_eq!(
+        error("PROGRAM P; VAR c : Colour; BEGIN END."),
+        "Unknown type: Colour"
+    );
+    assert_eq!(
+        error("PROGRAM P; TYPE Color = (Red); VAR c : Color; BEGIN c := 0 END."),
+        "Cannot assign the integer 0 to the color c"
+    );
+    assert_eq!(
+        error("PROGRAM P; TYPE Color = (Red, Blue); VAR c : Color; BEGIN c := succ(Blue) END."),
+        "succ(Blue) is outside of the type Color"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_writing_enumerated_values() -> anyhow::Result<()> {
+    let (output, interpreter) = Interpreter::run_and_capture(
+        "PROGRAM Colors;
+        TYPE
+            Color = (Red, Green, Blue);
+        VAR
+            c : Color;
+        BEGIN
+            c := Red;
+            WHILE c < Blue DO
+            BEGIN
+                write(c, ' ');
+                c := succ(c)
+            END;
+            writeln(c, ' ', pred(Green))
+        END.",
+    )?;
+    assert_eq!(output, "Red Green Blue Red\n");
+    assert_eq!(interpreter.value_of("c").unwrap().to_string(), "Blue");
+    Ok(())
+}
+
+#[test]
+fn test_enumerated_types_are_told_apart_by_their_variants() -> anyhow::Result<()> {
+    let shadowed = |statement: &str| {
+        let code = format!(
+            "PROGRAM P;
+            TYPE Color = (Red, Green, Blue);
+            VAR c : Color; same : BOOLEAN;
+            PROCEDURE Q;
+            TYPE Color = (X, Y);
+            BEGIN {} END;
+            BEGIN c := Red; Q END.",
+            statement
+        );
+        match Interpreter::run_and_capture(&code) {
+            Ok(_) => panic!("Expected {} to fail", statement),
+            Err(err) => err.to_string(),
+        }
+    };
+    assert_eq!(
+        shadowed("same := c = X"),
+        "Cannot compare the enumerated Red with the enumerated X"
+    );
+    assert_eq!(
+        shadowed("c := Y"),
+        "Cannot assign the enumerated Y to the color c"
+    );
+
+    let (_, interpreter) = Interpreter::run_and_capture(
+        "PROGRAM P;
+        TYPE Color = (Red, Green, Blue);
+        VAR a : ARRAY[1..2] OF Color;
+        BEGIN a[2] := Blue END.",
+    )?;
+    assert_eq!(
+        interpreter.value_of("a").unwrap().to_string(),
+        "[Red, Blue]"
+    );
+    match Interpreter::run_and_capture(
+        "PROGRAM P;
+        TYPE Color = (Red, Green, Blue);
+        VAR a : ARRAY[1..2] OF Color;
+        BEGIN a[1] := 1 END.",
+    ) {
+        Ok(_) => panic!("Expected assigning an integer to a Color element to fail"),
+        Err(err) => assert_eq!(
+            err.to_string(),
+            "Cannot assign the integer 1 to the color a[1]"
+        ),
+    }
+    Ok(())
+}
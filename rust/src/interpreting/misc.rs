@@ -1,4 +1,5 @@
-use crate::parsing::ast::Ast;
+use crate::parsing::ast::{Ast, SetElement};
+use std::collections::BTreeMap;
 
 pub fn rpn(node: &Ast) -> String {
     match node {
@@ -6,18 +7,52 @@ pub fn rpn(node: &Ast) -> String {
         Ast::Subtract(l, r) => format!("{} {} -", rpn(l), rpn(r)),
         Ast::Multiply(l, r) => format!("{} {} *", rpn(l), rpn(r)),
         Ast::IntegerDivide(l, r) => format!("{} {} /", rpn(l), rpn(r)),
+        Ast::RealDivide(l, r) => format!("{} {} /", rpn(l), rpn(r)),
+        Ast::Modulo(l, r) => format!("{} {} mod", rpn(l), rpn(r)),
+        Ast::Power(l, r) => format!("{} {} **", rpn(l), rpn(r)),
+        Ast::And(l, r) => format!("{} {} and", rpn(l), rpn(r)),
+        Ast::Or(l, r) => format!("{} {} or", rpn(l), rpn(r)),
+        Ast::Not(nested) => format!("{} not", rpn(nested)),
+        Ast::Equal(l, r)
+        | Ast::NotEqual(l, r)
+        | Ast::LessThan(l, r)
+        | Ast::LessOrEqual(l, r)
+        | Ast::GreaterThan(l, r)
+        | Ast::GreaterOrEqual(l, r) => {
+            format!("{} {} {}", rpn(l), rpn(r), relational_operator(node))
+        }
+        Ast::In { value, set } => format!("{} {} in", rpn(value), set_literal(set)),
         Ast::IntegerConstant(i) => i.to_string(),
+        Ast::RealConstant(r) => r.to_string(),
+        Ast::StringConstant(s) => string_literal(s),
+        Ast::BooleanConstant(b) => boolean_literal(*b),
         Ast::PositiveUnary(nested) => rpn(nested),
-        Ast::NegativeUnary(nested) => format!("0 {} -", rpn(nested)),
+        Ast::NegativeUnary(nested) => {
+            negative_literal(nested).unwrap_or_else(|| format!("0 {} -", rpn(nested)))
+        }
+        Ast::Located { node, .. } => rpn(node),
+        Ast::FunctionCall { name, arguments } => arguments
+            .iter()
+            .map(rpn)
+            .chain(std::iter::once(name.clone()))
+            .collect::<Vec<String>>()
+            .join(" "),
         Ast::Compound { .. } => todo!(""),
-        Ast::Variable(_) => todo!(""),
+        Ast::Variable(variable) => variable.name.clone(),
         Ast::Assign(_, _) => todo!(""),
+        Ast::Index { array, index } => format!("{} {} []", array.name, rpn(index)),
+        Ast::IndexedAssign { .. } => todo!(""),
+        Ast::ProcedureCall { .. } => todo!(""),
+        Ast::Case { .. } => todo!(""),
+        Ast::While { .. } => todo!(""),
+        Ast::If { .. } => todo!(""),
+        Ast::With { .. } => todo!(""),
         Ast::NoOp => todo!(""),
-        Ast::RealDivide(_, _) => todo!(""),
-        Ast::RealConstant(_) => todo!(""),
         Ast::Program { .. } => todo!(""),
         Ast::Block { .. } => todo!(""),
         Ast::VariableDeclaration { .. } => todo!(""),
+        Ast::ConstantDeclaration { .. } => todo!(""),
+        Ast::TypeDeclaration { .. } => todo!(""),
         Ast::Type(_) => todo!(""),
         Ast::ProcedureDeclaration { .. } => todo!(""),
         Ast::Parameter { .. } => todo!(""),
@@ -30,20 +65,521 @@ pub fn lisp_notation(node: &Ast) -> String {
         Ast::Subtract(l, r) => format!("(- {} {})", lisp_notation(l), lisp_notation(r)),
         Ast::Multiply(l, r) => format!("(* {} {})", lisp_notation(l), lisp_notation(r)),
         Ast::IntegerDivide(l, r) => format!("(/ {} {})", lisp_notation(l), lisp_notation(r)),
+        Ast::RealDivide(l, r) => format!("(/ {} {})", lisp_notation(l), lisp_notation(r)),
+        Ast::Modulo(l, r) => format!("(mod {} {})", lisp_notation(l), lisp_notation(r)),
+        Ast::Power(l, r) => format!("(** {} {})", lisp_notation(l), lisp_notation(r)),
+        Ast::And(l, r) => format!("(and {} {})", lisp_notation(l), lisp_notation(r)),
+        Ast::Or(l, r) => format!("(or {} {})", lisp_notation(l), lisp_notation(r)),
+        Ast::Not(nested) => format!("(not {})", lisp_notation(nested)),
+        Ast::Equal(l, r)
+        | Ast::NotEqual(l, r)
+        | Ast::LessThan(l, r)
+        | Ast::LessOrEqual(l, r)
+        | Ast::GreaterThan(l, r)
+        | Ast::GreaterOrEqual(l, r) => format!(
+            "({} {} {})",
+            relational_operator(node),
+            lisp_notation(l),
+            lisp_notation(r)
+        ),
+        Ast::In { value, set } => format!("(in {} {})", lisp_notation(value), set_literal(set)),
         Ast::IntegerConstant(i) => i.to_string(),
+        Ast::RealConstant(r) => r.to_string(),
+        Ast::StringConstant(s) => string_literal(s),
+        Ast::BooleanConstant(b) => boolean_literal(*b),
         Ast::PositiveUnary(nested) => lisp_notation(nested),
-        Ast::NegativeUnary(nested) => format!("(- {})", lisp_notation(nested)),
+        Ast::NegativeUnary(nested) => {
+            negative_literal(nested).unwrap_or_else(|| format!("(- {})", lisp_notation(nested)))
+        }
+        Ast::Located { node, .. } => lisp_notation(node),
+        Ast::FunctionCall { name, arguments } => format!(
+            "({})",
+            std::iter::once(name.clone())
+                .chain(arguments.iter().map(lisp_notation))
+                .collect::<Vec<String>>()
+                .join(" ")
+        ),
         Ast::Compound { .. } => todo!(""),
-        Ast::Variable(_) => todo!(""),
+        Ast::Variable(variable) => variable.name.clone(),
         Ast::Assign(_, _) => todo!(""),
+        Ast::Index { array, index } => format!("(index {} {})", array.name, lisp_notation(index)),
+        Ast::IndexedAssign { .. } => todo!(""),
+        Ast::ProcedureCall { .. } => todo!(""),
+        Ast::Case { .. } => todo!(""),
+        Ast::While { .. } => todo!(""),
+        Ast::If { .. } => todo!(""),
+        Ast::With { .. } => todo!(""),
         Ast::NoOp => todo!(""),
-        Ast::RealDivide(_, _) => todo!(""),
-        Ast::RealConstant(_) => todo!(""),
         Ast::Program { .. } => todo!(""),
         Ast::Block { .. } => todo!(""),
         Ast::VariableDeclaration { .. } => todo!(""),
+        Ast::ConstantDeclaration { .. } => todo!(""),
+        Ast::TypeDeclaration { .. } => todo!(""),
         Ast::Type(_) => todo!(""),
         Ast::ProcedureDeclaration { .. } => todo!(""),
         Ast::Parameter { .. } => todo!(""),
     }
 }
+
+/// Negated numeric literals like `-3.14` print as a single number. Reals keep every digit needed
+/// to read them back exactly.
+fn negative_literal(nested: &Ast) -> Option<String> {
+    match nested {
+        Ast::IntegerConstant(i) => Some(format!("-{}", i)),
+        Ast::RealConstant(r) => Some(format!("-{}", r)),
+        _ => None,
+    }
+}
+
+/// How many nodes of each kind appear in the tree, keyed by variant name
+pub fn node_counts(node: &Ast) -> BTreeMap<&'static str, usize> {
+    let mut counts = BTreeMap::new();
+    let mut pending = vec![node];
+    while let Some(node) = pending.pop() {
+        // Positions are bookkeeping rather than part of the program
+        if !matches!(node, Ast::Located { .. }) {
+            *counts.entry(node.kind()).or_insert(0) += 1;
+        }
+        pending.extend(node.children());
+    }
+    counts
+}
+
+/// Renders the tree as an outline, one node per line indented by its depth (starting at `indent`)
+pub fn tree_string(node: &Ast, indent: usize) -> String {
+    let mut output = String::new();
+    write_tree(&mut output, node, indent);
+    output
+}
+
+fn write_tree(output: &mut String, node: &Ast, depth: usize) {
+    if let Ast::Located { node, .. } = node {
+        return write_tree(output, node, depth);
+    }
+    output.push_str(&format!("{}{}\n", "  ".repeat(depth), tree_label(node)));
+    if let Ast::Case {
+        selector,
+        branches,
+        otherwise,
+    } = node
+    {
+        write_tree(output, selector, depth + 1);
+        for branch in branches {
+            let labels = branch
+                .labels
+                .iter()
+                .map(|label| label.to_string())
+                .collect::<Vec<String>>()
+                .join(", ");
+            output.push_str(&format!("{}Branch {}\n", "  ".repeat(depth + 1), labels));
+            write_tree(output, &branch.statement, depth + 2);
+        }
+        if let Some(otherwise) = otherwise {
+            output.push_str(&format!("{}Else\n", "  ".repeat(depth + 1)));
+            write_tree(output, otherwise, depth + 2);
+        }
+        return;
+    }
+    for child in node.children() {
+        write_tree(output, child, depth + 1);
+    }
+}
+
+/// The node's kind followed by whatever it holds besides its children
+fn tree_label(node: &Ast) -> String {
+    let detail = match node {
+        Ast::Program { name, .. }
+        | Ast::ProcedureDeclaration { name, .. }
+        | Ast::ConstantDeclaration { name, .. }
+        | Ast::ProcedureCall { name, .. }
+        | Ast::FunctionCall { name, .. } => name.clone(),
+        Ast::Variable(variable) | Ast::Assign(variable, _) => variable.name.clone(),
+        Ast::Index { array, .. } | Ast::IndexedAssign { array, .. } => array.name.clone(),
+        Ast::With { record, .. } => record.name.clone(),
+        Ast::Parameter { by_ref: true, .. } => "VAR".to_string(),
+        Ast::TypeDeclaration { name, variants } => format!("{} = ({})", name, variants.join(", ")),
+        Ast::Type(type_spec) => type_spec.to_string(),
+        Ast::IntegerConstant(i) => i.to_string(),
+        Ast::RealConstant(r) => format!("{:?}", r),
+        Ast::StringConstant(s) => string_literal(s),
+        Ast::BooleanConstant(b) => boolean_literal(*b),
+        _ => return node.kind().to_string(),
+    };
+    format!("{} {}", node.kind(), detail)
+}
+
+/// Where two trees first differ, as the path of node labels leading to the differing nodes,
+/// e.g. `Program P > Block > Compound > Assign x > IntegerConstant 1 vs IntegerConstant 2`
+pub fn first_difference(l: &Ast, r: &Ast) -> Option<String> {
+    if l == r {
+        return None;
+    }
+    let (l_children, r_children) = (l.children(), r.children());
+    if tree_label(l) == tree_label(r) && l_children.len() == r_children.len() {
+        let nested = l_children
+            .into_iter()
+            .zip(r_children)
+            .find_map(|(l, r)| first_difference(l, r));
+        if let Some(nested) = nested {
+            return Some(format!("{} > {}", tree_label(l), nested));
+        }
+    }
+    Some(format!("{} vs {}", tree_label(l), tree_label(r)))
+}
+
+/// Renders a parsed program (or any part of one) back as Pascal source code that parses to the
+/// same tree. Only the parentheses needed to keep the tree intact are emitted. The layout is
+/// canonical: uppercase keywords, one statement per line, four spaces per level of nesting and
+/// the colons of a `VAR` section lined up.
+pub fn to_pascal_source(node: &Ast) -> String {
+    let mut output = String::new();
+    write_source(&mut output, node, 0);
+    output
+}
+
+const INDENT: &str = "    ";
+
+fn write_source(output: &mut String, node: &Ast, depth: usize) {
+    match node {
+        Ast::Program { name, block } => {
+            output.push_str(&format!("PROGRAM {};\n", name));
+            write_source(output, block, depth);
+            output.push_str(".\n");
+        }
+        Ast::Block {
+            declarations,
+            compound_statements,
+        } => {
+            let constants: Vec<&Ast> = declarations
+                .iter()
+                .filter(|declaration| matches!(declaration, Ast::ConstantDeclaration { .. }))
+                .collect();
+            if !constants.is_empty() {
+                output.push_str(&INDENT.repeat(depth));
+                output.push_str("CONST\n");
+                for constant in constants {
+                    output.push_str(&INDENT.repeat(depth + 1));
+                    write_source(output, constant, depth + 1);
+                    output.push_str(";\n");
+                }
+            }
+            let types: Vec<&Ast> = declarations
+                .iter()
+                .filter(|declaration| matches!(declaration, Ast::TypeDeclaration { .. }))
+                .collect();
+            if !types.is_empty() {
+                output.push_str(&INDENT.repeat(depth));
+                output.push_str("TYPE\n");
+                for type_declaration in types {
+                    output.push_str(&INDENT.repeat(depth + 1));
+                    write_source(output, type_declaration, depth + 1);
+                    output.push_str(";\n");
+                }
+            }
+            let variables: Vec<&Ast> = declarations
+                .iter()
+                .filter(|declaration| matches!(declaration, Ast::VariableDeclaration { .. }))
+                .collect();
+            if !variables.is_empty() {
+                output.push_str(&INDENT.repeat(depth));
+                output.push_str("VAR\n");
+                let names: Vec<String> = variables
+                    .iter()
+                    .map(|variable| match variable {
+                        Ast::VariableDeclaration { variable, .. } => to_pascal_source(variable),
+                        _ => String::new(),
+                    })
+                    .collect();
+                let width = names.iter().map(String::len).max().unwrap_or(0);
+                for (variable, name) in variables.iter().zip(&names) {
+                    if let Ast::VariableDeclaration { type_spec, .. } = variable {
+                        output.push_str(&INDENT.repeat(depth + 1));
+                        output.push_str(&format!(
+                            "{:width$} : {};\n",
+                            name,
+                            to_pascal_source(type_spec),
+                            width = width
+                        ));
+                    }
+                }
+            }
+            for procedure in declarations
+                .iter()
+                .filter(|declaration| matches!(declaration, Ast::ProcedureDeclaration { .. }))
+            {
+                output.push_str(&INDENT.repeat(depth));
+                write_source(output, procedure, depth);
+                output.push_str(";\n");
+            }
+            output.push_str(&INDENT.repeat(depth));
+            write_source(output, compound_statements, depth);
+        }
+        Ast::ProcedureDeclaration {
+            name,
+            parameters,
+            block,
+        } => {
+            output.push_str(&format!("PROCEDURE {}", name));
+            if !parameters.is_empty() {
+                output.push_str(&format!(
+                    "({})",
+                    parameters
+                        .iter()
+                        .map(to_pascal_source)
+                        .collect::<Vec<String>>()
+                        .join("; ")
+                ));
+            }
+            output.push_str(";\n");
+            write_source(output, block, depth + 1);
+        }
+        Ast::Parameter {
+            variable,
+            type_spec,
+            by_ref,
+        } => output.push_str(&format!(
+            "{}{} : {}",
+            if *by_ref { "VAR " } else { "" },
+            to_pascal_source(variable),
+            to_pascal_source(type_spec)
+        )),
+        Ast::VariableDeclaration {
+            variable,
+            type_spec,
+        } => output.push_str(&format!(
+            "{} : {}",
+            to_pascal_source(variable),
+            to_pascal_source(type_spec)
+        )),
+        Ast::ConstantDeclaration {
+            name,
+            expression: written,
+            ..
+        } => output.push_str(&format!("{} = {}", name, expression(written))),
+        Ast::TypeDeclaration { name, variants } => {
+            output.push_str(&format!("{} = ({})", name, variants.join(", ")))
+        }
+        Ast::Type(type_spec) => output.push_str(&type_spec.to_string()),
+        Ast::Compound { statements } => {
+            output.push_str("BEGIN\n");
+            for (i, statement) in statements.iter().enumerate() {
+                if *statement != Ast::NoOp {
+                    output.push_str(&INDENT.repeat(depth + 1));
+                }
+                write_source(output, statement, depth + 1);
+                if i + 1 < statements.len() {
+                    output.push(';');
+                }
+                output.push('\n');
+            }
+            output.push_str(&INDENT.repeat(depth));
+            output.push_str("END");
+        }
+        Ast::Assign(variable, value) => {
+            output.push_str(&format!("{} := {}", variable.name, expression(value)))
+        }
+        Ast::IndexedAssign {
+            array,
+            index,
+            value,
+        } => output.push_str(&format!(
+            "{}[{}] := {}",
+            array.name,
+            expression(index),
+            expression(value)
+        )),
+        Ast::ProcedureCall { name, arguments } => {
+            output.push_str(&format!("{}({})", name, argument_list(arguments)))
+        }
+        Ast::While { condition, body } => {
+            output.push_str(&format!("WHILE {} DO\n", expression(condition)));
+            output.push_str(&INDENT.repeat(depth + 1));
+            write_source(output, body, depth + 1);
+        }
+        Ast::With { record, body } => {
+            output.push_str(&format!("WITH {} DO\n", record.name));
+            output.push_str(&INDENT.repeat(depth + 1));
+            write_source(output, body, depth + 1);
+        }
+        Ast::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            output.push_str(&format!("IF {} THEN\n", expression(condition)));
+            output.push_str(&INDENT.repeat(depth + 1));
+            write_source(output, then_branch, depth + 1);
+            if let Some(else_branch) = else_branch {
+                output.push('\n');
+                output.push_str(&INDENT.repeat(depth));
+                output.push_str("ELSE\n");
+                output.push_str(&INDENT.repeat(depth + 1));
+                write_source(output, else_branch, depth + 1);
+            }
+        }
+        Ast::Case {
+            selector,
+            branches,
+            otherwise,
+        } => {
+            output.push_str(&format!("CASE {} OF\n", expression(selector)));
+            for branch in branches {
+                output.push_str(&INDENT.repeat(depth + 1));
+                output.push_str(&format!(
+                    "{}: ",
+                    branch
+                        .labels
+                        .iter()
+                        .map(|label| label.to_string())
+                        .collect::<Vec<String>>()
+                        .join(", ")
+                ));
+                write_source(output, &branch.statement, depth + 1);
+                output.push_str(";\n");
+            }
+            if let Some(otherwise) = otherwise {
+                output.push_str(&INDENT.repeat(depth));
+                output.push_str("ELSE\n");
+                output.push_str(&INDENT.repeat(depth + 1));
+                write_source(output, otherwise, depth + 1);
+                output.push('\n');
+            }
+            output.push_str(&INDENT.repeat(depth));
+            output.push_str("END");
+        }
+        Ast::NoOp => {}
+        _ => output.push_str(&expression(node)),
+    }
+}
+
+fn string_literal(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "''"))
+}
+
+fn boolean_literal(b: bool) -> String {
+    if b { "TRUE" } else { "FALSE" }.to_string()
+}
+
+fn argument_list(arguments: &[Ast]) -> String {
+    arguments
+        .iter()
+        .map(expression)
+        .collect::<Vec<String>>()
+        .join(", ")
+}
+
+fn set_literal(set: &[SetElement]) -> String {
+    let elements = set
+        .iter()
+        .map(|element| match element {
+            SetElement::Single(value) => expression(value),
+            SetElement::Range(lower, upper) => {
+                format!("{}..{}", expression(lower), expression(upper))
+            }
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+    format!("[{}]", elements)
+}
+
+/// The Pascal spelling of a comparison
+fn relational_operator(node: &Ast) -> &'static str {
+    match node {
+        Ast::Equal(_, _) => "=",
+        Ast::NotEqual(_, _) => "<>",
+        Ast::LessThan(_, _) => "<",
+        Ast::LessOrEqual(_, _) => "<=",
+        Ast::GreaterThan(_, _) => ">",
+        Ast::GreaterOrEqual(_, _) => ">=",
+        _ => unreachable!("{:?} is not a comparison", node),
+    }
+}
+
+/// How tightly an expression binds; operands binding looser than their operator need parentheses
+fn precedence(node: &Ast) -> u8 {
+    match node {
+        Ast::Equal(_, _)
+        | Ast::NotEqual(_, _)
+        | Ast::LessThan(_, _)
+        | Ast::LessOrEqual(_, _)
+        | Ast::GreaterThan(_, _)
+        | Ast::GreaterOrEqual(_, _)
+        | Ast::In { .. } => 0,
+        Ast::Add(_, _) | Ast::Subtract(_, _) | Ast::Or(_, _) => 1,
+        Ast::Multiply(_, _)
+        | Ast::IntegerDivide(_, _)
+        | Ast::Modulo(_, _)
+        | Ast::RealDivide(_, _)
+        | Ast::And(_, _) => 2,
+        Ast::PositiveUnary(_) | Ast::NegativeUnary(_) => 3,
+        Ast::Power(_, _) => 4,
+        Ast::Located { node, .. } => precedence(node),
+        _ => 5,
+    }
+}
+
+fn operand(node: &Ast, minimum_precedence: u8) -> String {
+    if precedence(node) < minimum_precedence {
+        format!("({})", expression(node))
+    } else {
+        expression(node)
+    }
+}
+
+fn expression(node: &Ast) -> String {
+    // Operators are left associative, so a right operand of equal precedence needs parentheses
+    let binary = |operator: &str, l: &Ast, r: &Ast| {
+        let own = precedence(node);
+        format!("{} {} {}", operand(l, own), operator, operand(r, own + 1))
+    };
+    match node {
+        Ast::Add(l, r) => binary("+", l, r),
+        Ast::Subtract(l, r) => binary("-", l, r),
+        Ast::Multiply(l, r) => binary("*", l, r),
+        Ast::IntegerDivide(l, r) => binary("DIV", l, r),
+        Ast::Modulo(l, r) => binary("MOD", l, r),
+        Ast::RealDivide(l, r) => binary("/", l, r),
+        Ast::And(l, r) => binary("AND", l, r),
+        Ast::Or(l, r) => binary("OR", l, r),
+        // Right associative, and the exponent may be signed
+        Ast::Power(l, r) => format!("{} ** {}", operand(l, 5), operand(r, 3)),
+        // Comparisons don't chain, so neither side may be another comparison
+        Ast::Equal(l, r)
+        | Ast::NotEqual(l, r)
+        | Ast::LessThan(l, r)
+        | Ast::LessOrEqual(l, r)
+        | Ast::GreaterThan(l, r)
+        | Ast::GreaterOrEqual(l, r) => format!(
+            "{} {} {}",
+            operand(l, 1),
+            relational_operator(node),
+            operand(r, 1)
+        ),
+        Ast::In { value, set } => format!("{} IN {}", operand(value, 1), set_literal(set)),
+        Ast::PositiveUnary(nested) => format!("+{}", operand(nested, 4)),
+        Ast::NegativeUnary(nested) => format!("-{}", operand(nested, 4)),
+        // The operand of NOT is a factor, so anything but a literal, variable or call needs
+        // parentheses
+        Ast::Not(nested) => format!("NOT {}", operand(nested, 5)),
+        Ast::IntegerConstant(i) => i.to_string(),
+        Ast::RealConstant(r) => {
+            let digits = r.to_string();
+            if digits.contains('.') {
+                digits
+            } else {
+                format!("{}.0", digits)
+            }
+        }
+        Ast::StringConstant(s) => string_literal(s),
+        Ast::BooleanConstant(b) => boolean_literal(*b),
+        Ast::Variable(variable) => variable.name.clone(),
+        Ast::Index { array, index } => format!("{}[{}]", array.name, expression(index)),
+        Ast::FunctionCall { name, arguments } => format!("{}({})", name, argument_list(arguments)),
+        Ast::Located { node, .. } => expression(node),
+        _ => {
+            let mut output = String::new();
+            write_source(&mut output, node, 0);
+            output
+        }
+    }
+}
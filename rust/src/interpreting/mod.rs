@@ -1,4 +1,10 @@
+pub mod built_ins;
+mod call_stack;
 pub mod interpreter;
 pub mod misc;
+pub mod output;
 pub mod symbol_table;
 pub mod types;
+
+#[cfg(test)]
+mod numeric_tests;
@@ -0,0 +1,119 @@
+//! Pins how the arithmetic operators combine integers and reals: `/` always divides as reals,
+//! `div` and `mod` always give integers, `**` gives an integer only for an integer raised to a
+//! non-negative integer, and the other operators give a real as soon as either operand is one.
+
+use crate::interpreting::interpreter::Interpreter;
+use crate::interpreting::types::NumericType::{self, Integer, Real};
+
+fn eval(code: &str) -> anyhow::Result<NumericType> {
+    Interpreter::default().eval_with(code)
+}
+
+fn eval_strict(code: &str) -> anyhow::Result<NumericType> {
+    let mut interpreter = Interpreter::default();
+    interpreter.strict = true;
+    interpreter.eval_with(code)
+}
+
+#[test]
+fn test_real_division() -> anyhow::Result<()> {
+    assert_eq!(eval("20 / 7")?, Real(20.0 / 7.0));
+    assert_eq!(eval("6 / 3")?, Real(2.0));
+    assert_eq!(eval("7.5 / 2")?, Real(3.75));
+    assert_eq!(eval("1 / 4.0")?, Real(0.25));
+    assert_eq!(eval_strict("20 / 7")?, Real(20.0 / 7.0));
+    // Whatever the variant, the value of a whole quotient is the integer
+    assert!(eval("10 / 2")?.approx_eq(&Integer(5), 0.0));
+    assert!(eval("1 / 3")?.approx_eq(&Real(0.3333), 1e-4));
+    Ok(())
+}
+
+#[test]
+fn test_integer_division() -> anyhow::Result<()> {
+    assert_eq!(eval("20 div 7")?, Integer(2));
+    assert_eq!(eval("21 div 7")?, Integer(3));
+    // div truncates towards zero
+    assert_eq!(eval("-20 div 7")?, Integer(-2));
+    assert_eq!(eval("20 div -7")?, Integer(-2));
+    assert_eq!(
+        eval("(-2147483647 - 1) div -1").unwrap_err().to_string(),
+        "integer overflow in -2147483648 div -1"
+    );
+    assert_eq!(
+        eval("1 div 0").unwrap_err().to_string(),
+        "integer division (div) by zero"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_modulo() -> anyhow::Result<()> {
+    assert_eq!(eval("20 mod 7")?, Integer(6));
+    assert_eq!(eval("21 mod 7")?, Integer(0));
+    // Unlike the remainder, mod is never negative
+    assert_eq!(eval("-20 mod 7")?, Integer(1));
+    assert!(eval("20 mod -7").is_err());
+    assert!(eval("20 mod 0").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_mixed_operands_promote_to_real() -> anyhow::Result<()> {
+    assert_eq!(eval("2 + 3.0")?, Real(5.0));
+    assert_eq!(eval("2.0 + 3")?, Real(5.0));
+    assert_eq!(eval("7 - 2.5")?, Real(4.5));
+    assert_eq!(eval("2 * 3.0")?, Real(6.0));
+    assert_eq!(eval("-2 * -1.5")?, Real(3.0));
+    Ok(())
+}
+
+#[test]
+fn test_integer_operands_stay_integer() -> anyhow::Result<()> {
+    assert_eq!(eval("2 * 3")?, Integer(6));
+    assert_eq!(eval("2 + 3")?, Integer(5));
+    assert_eq!(eval("2 - 3")?, Integer(-1));
+    assert_eq!(eval("-(2)")?, Integer(-2));
+    assert_eq!(eval("+2")?, Integer(2));
+    Ok(())
+}
+
+#[test]
+fn test_real_operands_of_div_and_mod() -> anyhow::Result<()> {
+    // Outside of strict mode reals are truncated towards zero first
+    assert_eq!(eval("7.9 div 2")?, Integer(3));
+    assert_eq!(eval("-7.9 div 2")?, Integer(-3));
+    assert_eq!(eval("7.5 mod 2")?, Integer(1));
+    assert_eq!(eval("20 div 7.0")?, Integer(2));
+    assert_eq!(
+        eval("3000000000.0 div 2").unwrap_err().to_string(),
+        "div of the real 3000000000 is out of the integer range"
+    );
+
+    assert_eq!(
+        eval_strict("7.9 div 2").unwrap_err().to_string(),
+        "div requires integer operands, found 7.9 div 2"
+    );
+    assert!(eval_strict("7.5 mod 2").is_err());
+    assert_eq!(eval_strict("20 div 7")?, Integer(2));
+    Ok(())
+}
+
+#[test]
+fn test_power() -> anyhow::Result<()> {
+    assert_eq!(eval("2 ** 10")?, Integer(1024));
+    assert_eq!(eval("2 ** 0")?, Integer(1));
+    assert_eq!(eval("2 ** -1")?, Real(0.5));
+    assert_eq!(eval("2.0 ** 0.5")?, Real(2f64.sqrt()));
+    assert_eq!(eval("2.0 ** 3")?, Real(8.0));
+    assert_eq!(eval("8 ** (1 / 3)")?, Real(8f64.powf(1.0 / 3.0)));
+    // Right associative, and binding tighter than a sign
+    assert_eq!(eval("2 ** 3 ** 2")?, Integer(512));
+    assert_eq!(eval("-2 ** 2")?, Integer(-4));
+    assert_eq!(eval("2 * 3 ** 2")?, Integer(18));
+    assert_eq!(
+        eval("2 ** 31").unwrap_err().to_string(),
+        "integer overflow in 2 ** 31"
+    );
+    assert_eq!(eval("2.0 ** 31")?, Real(2147483648.0));
+    Ok(())
+}
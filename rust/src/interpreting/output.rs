@@ -0,0 +1,36 @@
+use std::io;
+use std::io::Write;
+
+/// Passes writes on to `inner` until `limit` bytes have been written, then fails every further
+/// write with "output limit exceeded", e.g. to stop a runaway `writeln` loop
+pub struct LimitedOutput<W: Write> {
+    inner: W,
+    limit: usize,
+    written: usize,
+}
+
+impl<W: Write> LimitedOutput<W> {
+    pub fn new(inner: W, limit: usize) -> Self {
+        LimitedOutput {
+            inner,
+            limit,
+            written: 0,
+        }
+    }
+}
+
+impl<W: Write> Write for LimitedOutput<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let remaining = self.limit - self.written;
+        if remaining == 0 && !buf.is_empty() {
+            return Err(io::Error::other("output limit exceeded"));
+        }
+        let written = self.inner.write(&buf[..buf.len().min(remaining)])?;
+        self.written += written;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
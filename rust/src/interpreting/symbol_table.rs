@@ -1,11 +1,14 @@
-use crate::parsing::ast::Ast;
+use crate::interpreting::built_ins::{is_built_in_function, is_built_in_procedure};
+use crate::parsing::ast::{Ast, TypeSpec};
 use anyhow::{bail, Result};
 use case_insensitive_hashmap::CaseInsensitiveHashMap;
 use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Write;
 use std::string::ToString;
 use strum_macros::Display;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum Symbol {
     BuiltIn(BuiltInTypes),
     Variable {
@@ -16,15 +19,28 @@ pub enum Symbol {
         name: String,
         parameters: Vec<Parameter>,
     },
+    Constant {
+        name: String,
+        const_type: String,
+    },
+    /// An enumerated type declared with `TYPE`
+    Type {
+        name: String,
+        variants: Vec<String>,
+    },
 }
 
-#[derive(Display, Debug)]
+/// Displays in uppercase like `TypeSpec`, as types are conventionally written in source
+#[derive(Clone, Display, Debug)]
+#[strum(serialize_all = "UPPERCASE")]
 pub enum BuiltInTypes {
     Integer,
     Real,
+    Boolean,
+    String,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct Parameter {
     name: String,
     var_type: String,
@@ -35,6 +51,12 @@ impl Display for Symbol {
         match self {
             Symbol::BuiltIn(x) => x.fmt(f),
             Symbol::Variable { name, var_type } => format!("<{}:{}>", name, var_type).fmt(f),
+            Symbol::Constant { name, const_type } => {
+                format!("<const {}:{}>", name, const_type).fmt(f)
+            }
+            Symbol::Type { name, variants } => {
+                format!("<type {} = ({})>", name, variants.join(", ")).fmt(f)
+            }
             Symbol::ProcedureSymbol { name, parameters } => format!(
                 "<{}({})>",
                 name,
@@ -55,74 +77,218 @@ impl Symbol {
             Symbol::BuiltIn(x) => x.to_string(),
             Symbol::Variable { name, .. } => name.clone(),
             Symbol::ProcedureSymbol { name, .. } => name.clone(),
+            Symbol::Constant { name, .. } => name.clone(),
+            Symbol::Type { name, .. } => name.clone(),
         }
     }
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub struct SymbolTable {
     pub symbols: CaseInsensitiveHashMap<Symbol>,
     pub scope_name: String,
     pub scope_level: u8,
-    verbose: bool,
+    /// Scopes of the procedures declared directly in this one
+    pub nested_scopes: Vec<SymbolTable>,
 }
 
 impl SymbolTable {
-    pub(crate) fn build_for(program: &Ast, verbose: bool) -> Result<SymbolTable> {
-        let mut symbol_table = SymbolTable {
-            symbols: CaseInsensitiveHashMap::new(),
-            scope_name: "global".to_string(),
-            scope_level: 1,
-            verbose,
+    /// Builds the table, printing every definition and lookup to stdout when `verbose`
+    pub fn build_for(program: &Ast, verbose: bool) -> Result<SymbolTable> {
+        if verbose {
+            SymbolTable::build(program, Some(&mut io::stdout()))
+        } else {
+            SymbolTable::build(program, None)
+        }
+    }
+
+    /// Builds the table, writing every definition and lookup to `log`
+    pub(crate) fn build_logging_to(program: &Ast, log: &mut dyn Write) -> Result<SymbolTable> {
+        SymbolTable::build(program, Some(log))
+    }
+
+    fn build(program: &Ast, log: Option<&mut dyn Write>) -> Result<SymbolTable> {
+        let mut scopes = Scopes {
+            stack: vec![SymbolTable::new("global", 1)],
+            log,
         };
 
-        symbol_table.define(Symbol::BuiltIn(BuiltInTypes::Integer))?;
-        symbol_table.define(Symbol::BuiltIn(BuiltInTypes::Real))?;
+        scopes.define(Symbol::BuiltIn(BuiltInTypes::Integer))?;
+        scopes.define(Symbol::BuiltIn(BuiltInTypes::Real))?;
+        scopes.define(Symbol::BuiltIn(BuiltInTypes::Boolean))?;
+        scopes.define(Symbol::BuiltIn(BuiltInTypes::String))?;
 
-        let result = build_symbol_table(&mut symbol_table, program);
+        build_symbol_table(&mut scopes, program)?;
 
-        result.and(Ok(symbol_table))
+        Ok(scopes.stack.pop().expect("the global scope is never left"))
     }
 
-    fn define(&mut self, symbol: Symbol) -> Result<()> {
-        if self.verbose {
-            println!("Define: {}", symbol);
+    fn new(scope_name: &str, scope_level: u8) -> SymbolTable {
+        SymbolTable {
+            symbols: CaseInsensitiveHashMap::new(),
+            scope_name: scope_name.to_string(),
+            scope_level,
+            nested_scopes: vec![],
         }
+    }
+
+    fn log(&self, log: &mut Option<&mut dyn Write>, message: std::fmt::Arguments) -> Result<()> {
+        if let Some(log) = log {
+            writeln!(
+                log,
+                "[level {}, {}] {}",
+                self.scope_level, self.scope_name, message
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// The chain of scopes enclosing the node being checked, innermost last
+struct Scopes<'a> {
+    stack: Vec<SymbolTable>,
+    log: Option<&'a mut dyn Write>,
+}
+
+impl Scopes<'_> {
+    fn current(&mut self) -> &mut SymbolTable {
+        self.stack
+            .last_mut()
+            .expect("the global scope is never left")
+    }
+
+    fn define(&mut self, symbol: Symbol) -> Result<()> {
+        let Scopes { stack, log } = self;
+        let scope = stack.last_mut().expect("the global scope is never left");
+        scope.log(log, format_args!("Define: {}", symbol))?;
+
         let key = symbol.symbol_table_key();
-        if self.symbols.contains_key(key) {
+        if scope.symbols.contains_key(key) {
             bail!("Duplicate identifier: {:?}", symbol)
         } else {
-            self.symbols.insert(symbol.symbol_table_key(), symbol);
+            scope.symbols.insert(symbol.symbol_table_key(), symbol);
             Ok(())
         }
     }
 
-    fn lookup(&self, name: &str) -> Option<&Symbol> {
-        if self.verbose {
-            println!("Lookup: {}", name);
+    /// Searches from the innermost scope outwards
+    fn lookup(&mut self, name: &str) -> Result<Option<&Symbol>> {
+        let Scopes { stack, log } = self;
+        for scope in stack.iter().rev() {
+            scope.log(log, format_args!("Lookup: {}", name))?;
+            if let Some(symbol) = scope.symbols.get(name) {
+                return Ok(Some(symbol));
+            }
         }
-        self.symbols.get(name)
+        Ok(None)
     }
+
+    fn lookup_current_scope(&mut self, name: &str) -> Result<Option<&Symbol>> {
+        let Scopes { stack, log } = self;
+        let scope = stack.last().expect("the global scope is never left");
+        scope.log(log, format_args!("Lookup: {}", name))?;
+        Ok(scope.symbols.get(name))
+    }
+
+    fn enter(&mut self, scope_name: &str) {
+        let scope_level = self.current().scope_level + 1;
+        self.stack.push(SymbolTable::new(scope_name, scope_level));
+    }
+
+    fn leave(&mut self) {
+        let scope = self.stack.pop().expect("the global scope is never left");
+        self.current().nested_scopes.push(scope);
+    }
+}
+
+fn procedure_parameters(parameters: &[Ast]) -> Result<Vec<Parameter>> {
+    parameters
+        .iter()
+        .map(|parameter| match parameter {
+            Ast::Parameter {
+                variable,
+                type_spec,
+                ..
+            } => Ok(Parameter {
+                name: variable.variable()?.name.clone(),
+                var_type: type_spec.type_spec()?.to_string(),
+            }),
+            node => bail!("Expected a parameter, was {:?}", node),
+        })
+        .collect()
 }
 
-fn build_symbol_table(symbols: &mut SymbolTable, node: &Ast) -> Result<()> {
+fn build_symbol_table(symbols: &mut Scopes, node: &Ast) -> Result<()> {
     match node {
         Ast::Add(l, r)
         | Ast::Subtract(l, r)
         | Ast::Multiply(l, r)
         | Ast::IntegerDivide(l, r)
-        | Ast::RealDivide(l, r) => {
+        | Ast::Modulo(l, r)
+        | Ast::RealDivide(l, r)
+        | Ast::Power(l, r)
+        | Ast::And(l, r)
+        | Ast::Or(l, r)
+        | Ast::Equal(l, r)
+        | Ast::NotEqual(l, r)
+        | Ast::LessThan(l, r)
+        | Ast::LessOrEqual(l, r)
+        | Ast::GreaterThan(l, r)
+        | Ast::GreaterOrEqual(l, r) => {
             build_symbol_table(symbols, l).and_then(|_| build_symbol_table(symbols, r))
         }
-        Ast::IntegerConstant(_) | Ast::RealConstant(_) => Ok(()),
+        Ast::In { .. } => node
+            .children()
+            .into_iter()
+            .try_for_each(|child| build_symbol_table(symbols, child)),
+        Ast::IntegerConstant(_)
+        | Ast::RealConstant(_)
+        | Ast::StringConstant(_)
+        | Ast::BooleanConstant(_) => Ok(()),
         Ast::PositiveUnary(node) => build_symbol_table(symbols, node),
         Ast::NegativeUnary(node) => build_symbol_table(symbols, node),
+        Ast::Not(node) => build_symbol_table(symbols, node),
         Ast::Program { block, .. } => build_symbol_table(symbols, block),
-        Ast::ProcedureDeclaration { .. } => Ok(()), // TODO after part 12
+        Ast::ProcedureDeclaration {
+            name,
+            parameters,
+            block,
+        } => {
+            // The procedure itself was defined with the rest of its block's procedures
+            symbols.enter(name);
+            for parameter in procedure_parameters(parameters)? {
+                symbols.define(Symbol::Variable {
+                    name: parameter.name,
+                    var_type: parameter.var_type,
+                })?;
+            }
+            build_symbol_table(symbols, block)?;
+            symbols.leave();
+            Ok(())
+        }
         Ast::Block {
             declarations,
             compound_statements,
         } => {
+            // Define every procedure first, so a procedure may call one declared after it
+            for declaration in declarations {
+                if let Ast::ProcedureDeclaration {
+                    name, parameters, ..
+                } = declaration
+                {
+                    if symbols.lookup_current_scope(name)?.is_some() {
+                        bail!(
+                            "Duplicate procedure '{}' in scope {}",
+                            name,
+                            symbols.current().scope_name
+                        );
+                    }
+                    symbols.define(Symbol::ProcedureSymbol {
+                        name: name.clone(),
+                        parameters: procedure_parameters(parameters)?,
+                    })?;
+                }
+            }
             let declaration_results: Result<()> = declarations
                 .iter()
                 .try_for_each(|declaration| build_symbol_table(symbols, declaration));
@@ -133,12 +299,16 @@ fn build_symbol_table(symbols: &mut SymbolTable, node: &Ast) -> Result<()> {
             variable,
             type_spec: type_spec_node,
         } => {
-            let variable_type = type_spec_node.type_spec()?.to_string();
-            if symbols.lookup(&variable_type).is_none() {
+            let type_spec = type_spec_node.type_spec()?;
+            let variable_type = type_spec.to_string();
+            if symbols
+                .lookup(&type_spec.element_type().to_string())?
+                .is_none()
+            {
                 bail!("Unknown type: {:?}", variable);
             }
             let name = variable.variable()?.name.clone();
-            if symbols.lookup(&name).is_some() {
+            if symbols.lookup_current_scope(&name)?.is_some() {
                 bail!("Duplicate Identifier: {:?}", variable);
             }
             symbols.define(Symbol::Variable {
@@ -147,22 +317,130 @@ fn build_symbol_table(symbols: &mut SymbolTable, node: &Ast) -> Result<()> {
             })?;
             Ok(())
         }
+        Ast::ConstantDeclaration { name, value, .. } => {
+            let const_type = match value.as_ref() {
+                Ast::IntegerConstant(_) => TypeSpec::Integer,
+                Ast::RealConstant(_) => TypeSpec::Real,
+                Ast::StringConstant(_) => TypeSpec::String,
+                Ast::BooleanConstant(_) => TypeSpec::Boolean,
+                value => bail!("Constant {} was not folded: {:?}", name, value),
+            };
+            symbols.define(Symbol::Constant {
+                name: name.clone(),
+                const_type: const_type.to_string(),
+            })
+        }
+        Ast::TypeDeclaration { name, variants } => {
+            let type_symbol = Symbol::Type {
+                name: name.clone(),
+                variants: variants.clone(),
+            };
+            let constants = variants.iter().map(|variant| Symbol::Constant {
+                name: variant.clone(),
+                const_type: name.clone(),
+            });
+            for symbol in std::iter::once(type_symbol).chain(constants) {
+                if symbols
+                    .lookup_current_scope(&symbol.symbol_table_key())?
+                    .is_some()
+                {
+                    bail!("Duplicate Identifier: {:?}", symbol.symbol_table_key());
+                }
+                symbols.define(symbol)?;
+            }
+            Ok(())
+        }
         Ast::Compound { statements } => statements
             .iter()
             .try_for_each(|statement| build_symbol_table(symbols, statement)),
         Ast::Assign(variable, expr) => {
             build_symbol_table(symbols, expr)?;
-            if symbols.lookup(&variable.name).is_none() {
-                bail!("Unknown variable to assign to: {:?}", variable);
+            match symbols.lookup(&variable.name)? {
+                None => bail!("Unknown variable to assign to: {:?}", variable),
+                Some(Symbol::Constant { name, .. }) => {
+                    bail!("Cannot assign to the constant {}", name)
+                }
+                Some(_) => Ok(()),
+            }
+        }
+        Ast::Index { array, index } => {
+            build_symbol_table(symbols, index)?;
+            if symbols.lookup(&array.name)?.is_none() {
+                bail!("Unknown variable: {:?}", array);
+            }
+            Ok(())
+        }
+        Ast::IndexedAssign {
+            array,
+            index,
+            value,
+        } => {
+            build_symbol_table(symbols, index)?;
+            build_symbol_table(symbols, value)?;
+            if symbols.lookup(&array.name)?.is_none() {
+                bail!("Unknown variable to assign to: {:?}", array);
+            }
+            Ok(())
+        }
+        Ast::While { condition, body } => {
+            build_symbol_table(symbols, condition)?;
+            build_symbol_table(symbols, body)
+        }
+        Ast::With { record, body } => {
+            if symbols.lookup(&record.name)?.is_none() {
+                bail!("Unknown record in WITH: {}", record.name);
+            }
+            build_symbol_table(symbols, body)
+        }
+        Ast::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => {
+            build_symbol_table(symbols, condition)?;
+            build_symbol_table(symbols, then_branch)?;
+            match else_branch {
+                Some(else_branch) => build_symbol_table(symbols, else_branch),
+                None => Ok(()),
+            }
+        }
+        Ast::Case {
+            selector,
+            branches,
+            otherwise,
+        } => {
+            build_symbol_table(symbols, selector)?;
+            for branch in branches {
+                build_symbol_table(symbols, &branch.statement)?;
+            }
+            if let Some(otherwise) = otherwise {
+                build_symbol_table(symbols, otherwise)?;
             }
             Ok(())
         }
+        Ast::ProcedureCall { name, arguments } => {
+            if !is_built_in_procedure(name) && symbols.lookup(name)?.is_none() {
+                bail!("Unknown procedure: {:?}", name);
+            }
+            arguments
+                .iter()
+                .try_for_each(|argument| build_symbol_table(symbols, argument))
+        }
+        Ast::FunctionCall { name, arguments } => {
+            if !is_built_in_function(name) {
+                bail!("Unknown function: {:?}", name);
+            }
+            arguments
+                .iter()
+                .try_for_each(|argument| build_symbol_table(symbols, argument))
+        }
         Ast::Variable(variable) => {
-            if symbols.lookup(&variable.name).is_none() {
+            if symbols.lookup(&variable.name)?.is_none() {
                 bail!("Unknown variable: {:?}", variable);
             }
             Ok(())
         }
+        Ast::Located { node, .. } => build_symbol_table(symbols, node),
         Ast::Type(_) | Ast::NoOp => Ok(()),
         Ast::Parameter { .. } => Ok(()),
     }
@@ -247,3 +525,240 @@ fn test_duplicate_identifiers() {
         .to_string()
         .contains("Duplicate Identifier"));
 }
+
+#[test]
+fn test_nested_scopes() -> anyhow::Result<()> {
+    let code = r#"
+        program Main;
+        var x, y : real;
+
+        procedure Alpha(a : integer);
+        var y : integer;
+            procedure Beta;
+            var z : integer;
+            begin
+                z := a + y + x
+            end;
+        begin
+            x := a + x + y;
+        end;
+
+        begin { Main }
+        end.  { Main }
+    "#;
+
+    use crate::lexing::lexer::Lexer;
+    use crate::parsing::parser::Parser;
+    let ast = Parser::new(Lexer::new(code)).parse()?;
+    let global = SymbolTable::build_for(&ast, true)?;
+
+    assert_eq!(global.scope_level, 1);
+    assert!(global.symbols.contains_key("Alpha"));
+    let alpha = &global.nested_scopes[0];
+    assert_eq!((alpha.scope_name.as_str(), alpha.scope_level), ("Alpha", 2));
+    assert!(alpha.symbols.contains_key("a") && alpha.symbols.contains_key("y"));
+    let beta = &alpha.nested_scopes[0];
+    assert_eq!((beta.scope_name.as_str(), beta.scope_level), ("Beta", 3));
+    assert!(beta.symbols.contains_key("z") && !beta.symbols.contains_key("x"));
+    Ok(())
+}
+
+#[test]
+fn test_procedure_locals_are_not_visible_outside() {
+    let code = r#"
+        program Main;
+        procedure Alpha;
+        var hidden : integer;
+        begin
+        end;
+        begin
+            hidden := 1
+        end.
+    "#;
+
+    use crate::lexing::lexer::Lexer;
+    use crate::parsing::parser::Parser;
+    let ast = Parser::new(Lexer::new(code)).parse().unwrap();
+    assert!(SymbolTable::build_for(&ast, false)
+        .expect_err("Expected hidden to be out of scope")
+        .to_string()
+        .contains("Unknown variable to assign to"));
+}
+
+#[test]
+fn test_verbose_log_is_captured() -> anyhow::Result<()> {
+    use crate::parsing::parser::parse_program;
+
+    let ast = parse_program("program Empty; begin end.")?;
+    let mut log = vec![];
+    SymbolTable::build_logging_to(&ast, &mut log)?;
+
+    assert_eq!(
+        String::from_utf8(log)?.lines().collect::<Vec<&str>>(),
+        vec![
+            "[level 1, global] Define: INTEGER",
+            "[level 1, global] Define: REAL",
+            "[level 1, global] Define: BOOLEAN",
+            "[level 1, global] Define: STRING",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_verbose_log_prefixes_the_scope() -> anyhow::Result<()> {
+    let code = r#"
+        program Logged;
+        var x : integer;
+        procedure Alpha(a : integer);
+        begin
+            x := a
+        end;
+        begin
+        end.
+    "#;
+
+    use crate::lexing::lexer::Lexer;
+    use crate::parsing::parser::Parser;
+    let ast = Parser::new(Lexer::new(code)).parse()?;
+    let mut log = vec![];
+    SymbolTable::build_logging_to(&ast, &mut log)?;
+
+    assert_eq!(
+        String::from_utf8(log)?.lines().collect::<Vec<&str>>(),
+        vec![
+            "[level 1, global] Define: INTEGER",
+            "[level 1, global] Define: REAL",
+            "[level 1, global] Define: BOOLEAN",
+            "[level 1, global] Define: STRING",
+            "[level 1, global] Lookup: Alpha",
+            "[level 1, global] Define: <Alpha(a:INTEGER)>",
+            "[level 1, global] Lookup: INTEGER",
+            "[level 1, global] Lookup: x",
+            "[level 1, global] Define: <x:INTEGER>",
+            "[level 2, Alpha] Define: <a:INTEGER>",
+            "[level 2, Alpha] Lookup: a",
+            "[level 2, Alpha] Lookup: x",
+            "[level 1, global] Lookup: x",
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_duplicate_procedures() -> anyhow::Result<()> {
+    use crate::parsing::parser::parse_program;
+
+    let duplicate = r#"
+        program Main;
+        procedure P; begin end;
+        procedure Q;
+            procedure R; begin end;
+            procedure r(x : integer); begin end;
+        begin end;
+        begin end.
+    "#;
+    assert_eq!(
+        SymbolTable::build_for(&parse_program(duplicate)?, false)
+            .unwrap_err()
+            .to_string(),
+        "Duplicate procedure 'r' in scope Q"
+    );
+
+    let shadowed = r#"
+        program Main;
+        procedure P;
+            procedure P; begin end;
+        begin
+            P
+        end;
+        begin
+            P
+        end.
+    "#;
+    let global = SymbolTable::build_for(&parse_program(shadowed)?, false)?;
+    assert!(global.nested_scopes[0].symbols.contains_key("P"));
+    Ok(())
+}
+
+#[test]
+fn test_type_names_are_uppercase() -> anyhow::Result<()> {
+    use crate::parsing::parser::parse_program;
+
+    let code = r#"
+        program Types;
+        const
+            greeting = 'hi';
+        var
+            i : integer;
+            r : Real;
+            flags : array[1..2] of boolean;
+        begin
+        end.
+    "#;
+    let table = SymbolTable::build(&parse_program(code)?, None)?;
+    let symbols = |names: &[&str]| -> Vec<String> {
+        names
+            .iter()
+            .map(|name| table.symbols.get(*name).unwrap().to_string())
+            .collect()
+    };
+
+    assert_eq!(
+        symbols(&["INTEGER", "REAL", "BOOLEAN", "STRING"]),
+        ["INTEGER", "REAL", "BOOLEAN", "STRING"]
+    );
+    assert_eq!(
+        symbols(&["i", "r", "flags", "greeting"]),
+        [
+            "<i:INTEGER>",
+            "<r:REAL>",
+            "<flags:ARRAY[1..2] OF BOOLEAN>",
+            "<const greeting:STRING>"
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_procedures_may_call_later_siblings() -> anyhow::Result<()> {
+    use crate::parsing::parser::parse_program;
+
+    let code = r#"
+        program Forward;
+        var x : integer;
+        procedure First;
+        begin
+            Second(2)
+        end;
+        procedure Second(value : integer);
+        begin
+            x := value
+        end;
+        begin
+            First
+        end.
+    "#;
+    let global = SymbolTable::build_for(&parse_program(code)?, false)?;
+    assert!(global.symbols.contains_key("Second"));
+
+    // Procedures nested in a sibling stay out of reach
+    let nested = r#"
+        program Forward;
+        procedure First;
+        begin
+            Inner
+        end;
+        procedure Second;
+            procedure Inner; begin end;
+        begin end;
+        begin end.
+    "#;
+    assert_eq!(
+        SymbolTable::build_for(&parse_program(nested)?, false)
+            .unwrap_err()
+            .to_string(),
+        "Unknown procedure: \"Inner\""
+    );
+    Ok(())
+}
@@ -1,6 +1,10 @@
+use crate::parsing::ast::TypeSpec;
 use crate::{IntegerMachineType, RealMachineType};
+use anyhow::{anyhow, bail, Context};
+use std::cmp::Ordering;
 use std::fmt::{Display, Formatter};
 use std::ops::{Add, Mul, Neg, Sub};
+use std::rc::Rc;
 
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum NumericType {
@@ -21,6 +25,254 @@ impl NumericType {
             NumericType::Real(r) => *r as IntegerMachineType,
         }
     }
+
+    pub fn is_integer(&self) -> bool {
+        matches!(self, NumericType::Integer(_))
+    }
+
+    pub fn is_real(&self) -> bool {
+        matches!(self, NumericType::Real(_))
+    }
+
+    /// True for both `Integer(0)` and `Real(0.0)` (including `-0.0`)
+    pub fn is_zero(&self) -> bool {
+        match self {
+            NumericType::Integer(i) => *i == 0,
+            NumericType::Real(r) => *r == 0.0,
+        }
+    }
+
+    /// Compares by value whatever the variants, so `Real(5.0)` is close to `Integer(5)`. Meant
+    /// for tests where promotion makes the variant unimportant; `==` still tells them apart.
+    pub fn approx_eq(&self, other: &Self, epsilon: f64) -> bool {
+        (self.as_real() - other.as_real()).abs() <= epsilon
+    }
+}
+
+/// How integer `+`, `-` and `*` behave when the result doesn't fit an integer
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum OverflowPolicy {
+    Wrap,
+    Saturate,
+    #[default]
+    Error,
+}
+
+impl OverflowPolicy {
+    /// Combines two integers with the `operator` (`+`, `-` or `*`) under this policy
+    pub(super) fn apply(
+        self,
+        operator: char,
+        l: IntegerMachineType,
+        r: IntegerMachineType,
+    ) -> anyhow::Result<IntegerMachineType> {
+        type Checked = fn(IntegerMachineType, IntegerMachineType) -> Option<IntegerMachineType>;
+        type Unchecked = fn(IntegerMachineType, IntegerMachineType) -> IntegerMachineType;
+        let (checked, wrapping, saturating): (Checked, Unchecked, Unchecked) = match operator {
+            '+' => (
+                IntegerMachineType::checked_add,
+                IntegerMachineType::wrapping_add,
+                IntegerMachineType::saturating_add,
+            ),
+            '-' => (
+                IntegerMachineType::checked_sub,
+                IntegerMachineType::wrapping_sub,
+                IntegerMachineType::saturating_sub,
+            ),
+            '*' => (
+                IntegerMachineType::checked_mul,
+                IntegerMachineType::wrapping_mul,
+                IntegerMachineType::saturating_mul,
+            ),
+            _ => bail!("{} is not an integer operator", operator),
+        };
+        match self {
+            OverflowPolicy::Wrap => Ok(wrapping(l, r)),
+            OverflowPolicy::Saturate => Ok(saturating(l, r)),
+            OverflowPolicy::Error => {
+                checked(l, r).ok_or_else(|| anyhow!("integer overflow in {} {} {}", l, operator, r))
+            }
+        }
+    }
+
+    /// Raises `base` to a non-negative integer `exponent` under this policy
+    pub(super) fn power(
+        self,
+        base: IntegerMachineType,
+        exponent: u32,
+    ) -> anyhow::Result<IntegerMachineType> {
+        match self {
+            OverflowPolicy::Wrap => Ok(base.wrapping_pow(exponent)),
+            OverflowPolicy::Saturate => Ok(base.saturating_pow(exponent)),
+            OverflowPolicy::Error => base
+                .checked_pow(exponent)
+                .ok_or_else(|| anyhow!("integer overflow in {} ** {}", base, exponent)),
+        }
+    }
+}
+
+/// The most elements an array may hold, so a huge declared range fails instead of exhausting memory
+pub const MAX_ARRAY_LENGTH: usize = 1 << 24;
+
+/// Any value an expression can evaluate to
+#[derive(Clone, Debug, PartialEq)]
+pub enum Value {
+    Numeric(NumericType),
+    String(String),
+    Boolean(bool),
+    Array {
+        lower: IntegerMachineType,
+        elements: Vec<Value>,
+    },
+    /// One of the variants of an enumerated type, by its position in the declaration
+    Enumerated {
+        ordinal: IntegerMachineType,
+        enumeration: Rc<Enumeration>,
+    },
+}
+
+/// An enumerated type as its values carry it, so they can print by name
+#[derive(Debug, PartialEq)]
+pub struct Enumeration {
+    pub name: String,
+    pub variants: Vec<String>,
+}
+
+impl Enumeration {
+    /// The enumeration of a declared type, `None` unless it is enumerated
+    pub fn declared_by(type_spec: &TypeSpec) -> Option<Enumeration> {
+        match type_spec {
+            TypeSpec::Enumeration { name, variants } => Some(Enumeration {
+                name: name.clone(),
+                variants: variants.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+impl Value {
+    pub fn as_numeric(&self) -> anyhow::Result<NumericType> {
+        match self {
+            Value::Numeric(n) => Ok(*n),
+            _ => bail!("Expected a number, was {}", self.type_name()),
+        }
+    }
+
+    /// The Pascal name of this value's type
+    pub fn type_name(&self) -> &'static str {
+        match self {
+            Value::Numeric(NumericType::Integer(_)) => "integer",
+            Value::Numeric(NumericType::Real(_)) => "real",
+            Value::String(_) => "string",
+            Value::Boolean(_) => "boolean",
+            Value::Array { .. } => "array",
+            Value::Enumerated { .. } => "enumerated",
+        }
+    }
+
+    /// Orders two values of the same kind; integers and reals compare with each other
+    pub fn compare(&self, other: &Value) -> anyhow::Result<Ordering> {
+        let ordering = match (self, other) {
+            (Value::Numeric(NumericType::Integer(l)), Value::Numeric(NumericType::Integer(r))) => {
+                Some(l.cmp(r))
+            }
+            (Value::Numeric(l), Value::Numeric(r)) => l.as_real().partial_cmp(&r.as_real()),
+            (Value::String(l), Value::String(r)) => Some(l.cmp(r)),
+            (Value::Boolean(l), Value::Boolean(r)) => Some(l.cmp(r)),
+            (
+                Value::Enumerated {
+                    ordinal: l,
+                    enumeration: l_type,
+                },
+                Value::Enumerated {
+                    ordinal: r,
+                    enumeration: r_type,
+                },
+            ) if l_type == r_type => Some(l.cmp(r)),
+            _ => bail!(
+                "Cannot compare the {} {} with the {} {}",
+                self.type_name(),
+                self,
+                other.type_name(),
+                other
+            ),
+        };
+        ordering.ok_or_else(|| anyhow!("Cannot compare {} with {}", self, other))
+    }
+
+    /// The value a freshly declared variable of this type starts with. Fails for arrays of more
+    /// than `MAX_ARRAY_LENGTH` elements.
+    pub fn default_for(type_spec: &TypeSpec) -> anyhow::Result<Value> {
+        Ok(match type_spec {
+            TypeSpec::Integer => Value::Numeric(NumericType::Integer(0)),
+            TypeSpec::Real => Value::Numeric(NumericType::Real(0.0)),
+            TypeSpec::Boolean => Value::Boolean(false),
+            TypeSpec::String => Value::String(String::new()),
+            TypeSpec::Array {
+                lower,
+                upper,
+                element_type,
+            } => {
+                let length = upper
+                    .checked_sub(*lower)
+                    .and_then(|span| span.checked_add(1))
+                    .and_then(|length| usize::try_from(length).ok())
+                    .filter(|length| *length <= MAX_ARRAY_LENGTH)
+                    .with_context(|| {
+                        format!(
+                            "array {}..{} is too large, arrays hold at most {} elements",
+                            lower, upper, MAX_ARRAY_LENGTH
+                        )
+                    })?;
+                Value::Array {
+                    lower: *lower,
+                    elements: vec![Value::default_for(element_type)?; length],
+                }
+            }
+            TypeSpec::Enumeration { name, variants } => Value::Enumerated {
+                ordinal: 0,
+                enumeration: Rc::new(Enumeration {
+                    name: name.clone(),
+                    variants: variants.clone(),
+                }),
+            },
+        })
+    }
+}
+
+impl From<NumericType> for Value {
+    fn from(n: NumericType) -> Self {
+        Value::Numeric(n)
+    }
+}
+
+/// A precision, e.g. `{:.2}`, sets the decimals of reals, including those in arrays, and leaves
+/// the other values alone
+impl Display for Value {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Numeric(n) => Display::fmt(n, f),
+            Value::String(s) => f.write_str(s),
+            Value::Boolean(b) => f.write_str(if *b { "TRUE" } else { "FALSE" }),
+            Value::Array { elements, .. } => write!(
+                f,
+                "[{}]",
+                elements
+                    .iter()
+                    .map(|element| match f.precision() {
+                        Some(precision) => format!("{:.*}", precision, element),
+                        None => element.to_string(),
+                    })
+                    .collect::<Vec<String>>()
+                    .join(", ")
+            ),
+            Value::Enumerated {
+                ordinal,
+                enumeration,
+            } => f.write_str(&enumeration.variants[*ordinal as usize]),
+        }
+    }
 }
 
 impl Display for NumericType {
@@ -78,3 +330,29 @@ impl Neg for NumericType {
         }
     }
 }
+
+#[test]
+fn test_numeric_predicates() {
+    assert!(NumericType::Integer(3).is_integer());
+    assert!(!NumericType::Integer(3).is_real());
+    assert!(NumericType::Real(3.0).is_real());
+    assert!(!NumericType::Real(3.0).is_integer());
+
+    assert!(NumericType::Integer(0).is_zero());
+    assert!(NumericType::Real(0.0).is_zero());
+    assert!(NumericType::Real(-0.0).is_zero());
+    assert!(!NumericType::Integer(-1).is_zero());
+    assert!(!NumericType::Real(0.1).is_zero());
+    assert!(!NumericType::Real(f64::NAN).is_zero());
+}
+
+#[test]
+fn test_approx_eq() {
+    assert!(NumericType::Real(5.0).approx_eq(&NumericType::Integer(5), 0.0));
+    assert!(NumericType::Integer(5).approx_eq(&NumericType::Real(5.0), 0.0));
+    assert!(NumericType::Real(0.1 + 0.2).approx_eq(&NumericType::Real(0.3), 1e-9));
+    assert!(!NumericType::Real(0.1 + 0.2).approx_eq(&NumericType::Real(0.3), 0.0));
+    assert!(!NumericType::Integer(5).approx_eq(&NumericType::Integer(6), 0.5));
+    assert!(!NumericType::Real(f64::NAN).approx_eq(&NumericType::Real(f64::NAN), 1.0));
+    assert_ne!(NumericType::Real(5.0), NumericType::Integer(5));
+}
@@ -0,0 +1,83 @@
+use crate::lexing::lexer::Lexer;
+use crate::lexing::token::Token;
+use colored::Colorize;
+
+/// Re-emits `source` with ANSI colors by kind of token: keywords bold blue, numbers green,
+/// operators yellow and comments dimmed. Whether colors are used at all follows
+/// `colored::control`, so `NO_COLOR` or an override turning them off gives back `source` as is.
+pub fn highlight(source: &str) -> anyhow::Result<String> {
+    let mut output = String::with_capacity(source.len());
+    let mut end = 0;
+    for spanned in Lexer::new(source).spanned() {
+        let spanned = spanned?;
+        output.push_str(&dim_comments(&source[end..spanned.offset]));
+        let lexeme = spanned.lexeme;
+        match spanned.token {
+            Token::Eof => break,
+            Token::Keyword(_) => output.push_str(&lexeme.blue().bold().to_string()),
+            Token::IntegerConstant(_) | Token::RealConstant(_) => {
+                output.push_str(&lexeme.green().to_string())
+            }
+            Token::Plus
+            | Token::Minus
+            | Token::Multiply
+            | Token::Power
+            | Token::RealDivision
+            | Token::Assign
+            | Token::Equal
+            | Token::NotEqual
+            | Token::LessThan
+            | Token::LessEqual
+            | Token::GreaterThan
+            | Token::GreaterEqual
+            | Token::Caret
+            | Token::At => output.push_str(&lexeme.yellow().to_string()),
+            _ => output.push_str(lexeme),
+        }
+        end = spanned.offset + lexeme.len();
+    }
+    Ok(output)
+}
+
+/// The text between two tokens is whitespace around any comments
+fn dim_comments(gap: &str) -> String {
+    let comments = gap.trim();
+    if comments.is_empty() {
+        return gap.to_string();
+    }
+    let start = gap.len() - gap.trim_start().len();
+    format!(
+        "{}{}{}",
+        &gap[..start],
+        comments.dimmed(),
+        &gap[start + comments.len()..]
+    )
+}
+
+#[test]
+fn test_highlight() -> anyhow::Result<()> {
+    let source = "BEGIN { note } x := 1 + 2.5 END. (* done *)\n";
+
+    colored::control::set_override(true);
+    let highlighted = highlight(source);
+    let expected = format!(
+        "{} {} x {} {} {} {} {}. {}\n",
+        "BEGIN".blue().bold(),
+        "{ note }".dimmed(),
+        ":=".yellow(),
+        "1".green(),
+        "+".yellow(),
+        "2.5".green(),
+        "END".blue().bold(),
+        "(* done *)".dimmed()
+    );
+    colored::control::set_override(false);
+    let plain = highlight(source);
+    colored::control::unset_override();
+
+    assert_eq!(highlighted?, expected);
+    assert!(expected.contains("\x1b["));
+    assert_eq!(plain?, source);
+    assert!(highlight("x := 'unterminated").is_err());
+    Ok(())
+}
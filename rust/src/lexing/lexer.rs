@@ -1,93 +1,315 @@
 use crate::lexing::token::{Keyword, Token};
 use crate::{IntegerMachineType, RealMachineType};
-use anyhow::{bail, Context};
+use anyhow::{anyhow, bail, Context};
+use std::borrow::Cow;
+use std::cell::Cell;
+use std::fmt::{Display, Formatter};
+use std::rc::Rc;
 use std::str::FromStr;
 
-pub struct Lexer {
-    text: Vec<char>,
+/// Where a token starts in the source, counting lines and columns from 1
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Position {
+    pub line: usize,
+    pub column: usize,
+}
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}, column {}", self.line, self.column)
+    }
+}
+
+/// A token along with the source text it came from, in its original casing
+#[derive(Debug, PartialEq)]
+pub struct SpannedToken<'a> {
+    pub token: Token,
+    pub lexeme: &'a str,
+    /// Byte offset of the lexeme in the source
+    pub offset: usize,
+}
+
+/// Tokenizes source text in place, without copying it
+pub struct Lexer<'a> {
+    text: &'a str,
+    /// Byte offset of `current_char` in `text`
     pos: usize,
+    /// Byte offset where the token being read begins
+    token_start: usize,
+    /// Line where the token being read begins
+    token_line: usize,
     current_char: Option<char>,
+    line: usize,
+    /// Updated to the position of every token produced, see `track_positions`
+    position: Option<Rc<Cell<Position>>>,
+    /// Whether to record `{$...}` compiler directives instead of discarding them with comments
+    pub capture_directives: bool,
+    directives: Vec<String>,
+    /// Whether comments may contain other comments, e.g. `{ outer { inner } }`
+    pub nested_comments: bool,
+    /// Whether integers may be written in any base from 2 to 16 as `base#digits`, e.g. `16#FF`
+    pub radix_literals: bool,
 }
 
-impl Lexer {
-    pub fn new(text: &str) -> Lexer {
+impl<'a> Lexer<'a> {
+    pub fn new(text: &'a str) -> Lexer<'a> {
         Lexer {
-            text: text.chars().collect(),
+            text,
             pos: 0,
+            token_start: 0,
+            token_line: 1,
             current_char: text.chars().next(),
+            line: 1,
+            position: None,
+            capture_directives: false,
+            directives: vec![],
+            nested_comments: false,
+            radix_literals: false,
         }
     }
 
+    /// The directives seen so far, like `$mode objfpc`, if `capture_directives` is set. They
+    /// never affect the tokens produced.
+    pub fn directives(&self) -> &[String] {
+        &self.directives
+    }
+
+    /// A cell holding the position of the token produced last, so a parser consuming the tokens
+    /// can tell where each one came from
+    pub fn track_positions(&mut self) -> Rc<Cell<Position>> {
+        self.position
+            .get_or_insert_with(|| Rc::new(Cell::new(Position::default())))
+            .clone()
+    }
+
+    /// Tokens along with the exact source text each was read from, e.g. for syntax highlighting.
+    /// Iterating the lexer directly skips this.
+    pub fn spanned(mut self) -> impl Iterator<Item = anyhow::Result<SpannedToken<'a>>> {
+        std::iter::from_fn(move || {
+            Some(self.get_next_token().map(|token| SpannedToken {
+                token,
+                lexeme: &self.text[self.token_start..self.pos],
+                offset: self.token_start,
+            }))
+        })
+    }
+
     fn advance(&mut self) {
-        self.pos += 1;
-        if self.pos > self.text.len() - 1 {
-            self.current_char = None;
-        } else {
-            self.current_char = Some(*self.text.get(self.pos).unwrap());
+        if let Some(ch) = self.current_char {
+            if ch == '\n' {
+                self.line += 1;
+            }
+            self.pos += ch.len_utf8();
         }
+        self.current_char = self.text[self.pos..].chars().next();
     }
 
-    fn integer(&mut self) -> String {
-        let mut num = String::from(self.current_char.unwrap());
-        self.advance();
+    /// Skips over a run of digits, which may be grouped with single underscores, e.g. `1_000_000`
+    fn digits(&mut self, start: usize) -> anyhow::Result<()> {
         while let Some(i) = self.current_char {
-            if !i.is_numeric() {
+            if i == '_' {
+                let follows_digit = self.text[..self.pos].ends_with(|c: char| c.is_numeric());
+                let precedes_digit = self.peek().filter(|c| c.is_numeric()).is_some();
+                if !follows_digit || !precedes_digit {
+                    bail!(
+                        "Misplaced digit separator after {:?}",
+                        &self.text[start..self.pos]
+                    );
+                }
+            } else if !i.is_numeric() {
                 break;
             }
-            num.push(i);
             self.advance();
         }
-        num
+        Ok(())
     }
 
-    fn constant_number(&mut self) -> Token {
-        let mut num = self.integer();
+    fn constant_number(&mut self) -> anyhow::Result<Token> {
+        let start = self.pos;
+        self.digits(start)?;
+        if self.radix_literals && self.current_char == Some('#') {
+            return self.radix_literal(start);
+        }
+
+        // `1..5` is a range rather than the real `1.` followed by `.5`
+        let is_decimal_point = self.current_char == Some('.') && self.peek() != Some('.');
+        if is_decimal_point {
+            self.advance();
+            self.digits(start)?;
+        }
 
-        if let Some('.') = self.current_char {
-            num.push_str(&self.integer());
-            Token::RealConstant(num.parse::<RealMachineType>().unwrap())
+        // Only literals using separators need copying to parse them
+        let literal = &self.text[start..self.pos];
+        let num = if literal.contains('_') {
+            Cow::Owned(literal.replace('_', ""))
+        } else {
+            Cow::Borrowed(literal)
+        };
+        Ok(if is_decimal_point {
+            Token::RealConstant(
+                num.parse::<RealMachineType>()
+                    .with_context(|| format!("invalid real literal {}", num))?,
+            )
         } else {
-            Token::IntegerConstant(num.parse::<IntegerMachineType>().unwrap())
+            Token::IntegerConstant(num.parse::<IntegerMachineType>().map_err(|_| {
+                anyhow!(
+                    "integer literal {} out of range for {}",
+                    num,
+                    std::any::type_name::<IntegerMachineType>()
+                )
+            })?)
+        })
+    }
+
+    /// The digits after the `#` of a `base#digits` literal whose base starts at `start`
+    fn radix_literal(&mut self, start: usize) -> anyhow::Result<Token> {
+        let base = &self.text[start..self.pos];
+        let radix = base
+            .replace('_', "")
+            .parse::<u32>()
+            .ok()
+            .filter(|radix| (2..=16).contains(radix))
+            .with_context(|| format!("base {} of a radix literal must be from 2 to 16", base))?;
+        self.advance(); // skip the #
+
+        let digits_start = self.pos;
+        while let Some(digit) = self.current_char.filter(char::is_ascii_alphanumeric) {
+            if !digit.is_digit(radix) {
+                bail!("invalid digit {:?} for base {}", digit, radix);
+            }
+            self.advance();
+        }
+        let digits = &self.text[digits_start..self.pos];
+        if digits.is_empty() {
+            bail!("missing digits after {}#", radix);
         }
+        Ok(Token::IntegerConstant(
+            IntegerMachineType::from_str_radix(digits, radix).map_err(|_| {
+                anyhow!(
+                    "integer literal {}#{} out of range for {}",
+                    radix,
+                    digits,
+                    std::any::type_name::<IntegerMachineType>()
+                )
+            })?,
+        ))
     }
 
-    fn id(&mut self) -> String {
-        let mut name = String::new();
+    /// A quoted string where `''` stands for a single quote, e.g. `'don''t'`
+    fn string_constant(&mut self) -> anyhow::Result<Token> {
+        let start_line = self.line;
+        self.advance(); // skip the opening '
+        let mut value = String::new();
+        loop {
+            match self.current_char {
+                Some('\'') if self.peek() == Some('\'') => {
+                    value.push('\'');
+                    self.advance();
+                    self.advance();
+                }
+                Some('\'') => {
+                    self.advance();
+                    return Ok(Token::StringConstant(value));
+                }
+                Some('\n') | None => {
+                    bail!("Unterminated string starting at line {}", start_line)
+                }
+                Some(ch) => {
+                    value.push(ch);
+                    self.advance();
+                }
+            }
+        }
+    }
+
+    fn id(&mut self) -> &'a str {
+        let start = self.pos;
 
         // Allow for starting underscore
         if let Some('_') = self.current_char {
-            name.push('_');
             self.advance();
         }
 
         while self.current_char.filter(|c| c.is_alphanumeric()).is_some() {
-            name.push(self.current_char.unwrap());
             self.advance();
         }
-        name
+        &self.text[start..self.pos]
     }
 
-    fn skip_until_comment_ends(&mut self) {
-        let mut current_char = self.current_char;
-        while current_char.unwrap() != '}' {
-            self.advance();
-            current_char = self.current_char;
+    /// Skips the rest of a comment opened with `opener`, either `{` or `(*`. Only the matching
+    /// closer ends it, so crossed delimiters don't silently swallow code. With `nested_comments`
+    /// every opener inside needs its own closer first.
+    fn skip_until_comment_ends(&mut self, opener: &'static str) -> anyhow::Result<()> {
+        let start = self.pos;
+        let (line, column) = (self.line, self.column(start - opener.len()));
+        let mut open = vec![opener];
+        loop {
+            let delimiter = match (self.current_char, self.peek()) {
+                (None, _) => bail!(
+                    "unterminated comment opened at line {}, column {}",
+                    line,
+                    column
+                ),
+                (Some('}'), _) => "}",
+                (Some('*'), Some(')')) => "*)",
+                (Some('{'), _) if self.nested_comments => "{",
+                (Some('('), Some('*')) if self.nested_comments => "(*",
+                _ => {
+                    self.advance();
+                    continue;
+                }
+            };
+            if let "}" | "*)" = delimiter {
+                let innermost = open.pop().unwrap_or(opener);
+                let expected = if innermost == "{" { "}" } else { "*)" };
+                if delimiter != expected {
+                    bail!(
+                        "comment opened with '{}' but closed with '{}' on line {}",
+                        innermost,
+                        delimiter,
+                        self.line
+                    );
+                }
+            } else {
+                open.push(delimiter);
+            }
+            if open.is_empty() {
+                let comment = self.text[start..self.pos].trim();
+                if self.capture_directives && comment.starts_with('$') {
+                    self.directives.push(comment.to_string());
+                }
+            }
+            for _ in delimiter.chars() {
+                self.advance();
+            }
+            if open.is_empty() {
+                return Ok(());
+            }
         }
-        self.advance(); // skip }
     }
 
-    fn peek(&self) -> Option<&char> {
-        self.text.get(self.pos + 1)
+    /// The 1-based column of the character at byte offset `pos`
+    fn column(&self, pos: usize) -> usize {
+        let line_start = self.text[..pos]
+            .rfind('\n')
+            .map_or(0, |newline| newline + 1);
+        self.text[line_start..pos].chars().count() + 1
+    }
+
+    fn peek(&self) -> Option<char> {
+        let mut rest = self.text[self.pos..].chars();
+        rest.next();
+        rest.next()
     }
 
     fn get_next_token(&mut self) -> anyhow::Result<Token> {
-        if self.current_char.is_none() {
-            return anyhow::Ok(Token::Eof);
-        }
         loop {
-            let current_char = self
-                .current_char
-                .with_context(|| "Expecting another character")?;
+            self.token_start = self.pos;
+            self.token_line = self.line;
+            let current_char = match self.current_char {
+                Some(ch) => ch,
+                None => return anyhow::Ok(Token::Eof),
+            };
 
             match current_char {
                 ch if ch.is_whitespace() => {
@@ -95,10 +317,22 @@ impl Lexer {
                 }
                 '{' => {
                     self.advance();
-                    self.skip_until_comment_ends();
+                    self.skip_until_comment_ends("{")?;
+                }
+                '(' if self.peek() == Some('*') => {
+                    self.advance();
+                    self.advance();
+                    self.skip_until_comment_ends("(*")?;
                 }
                 ch if ch.is_numeric() => {
-                    return anyhow::Ok(self.constant_number());
+                    return self.constant_number();
+                }
+                // A digit separator can't start a number, so `_1` is neither number nor name
+                '_' if self.peek().is_some_and(|c| c.is_numeric()) => {
+                    bail!("Misplaced digit separator at the start of {:?}", self.id());
+                }
+                '\'' => {
+                    return self.string_constant();
                 }
                 '+' => {
                     self.advance();
@@ -108,6 +342,11 @@ impl Lexer {
                     self.advance();
                     return anyhow::Ok(Token::Minus);
                 }
+                '*' if self.peek() == Some('*') => {
+                    self.advance();
+                    self.advance();
+                    return anyhow::Ok(Token::Power);
+                }
                 '*' => {
                     self.advance();
                     return anyhow::Ok(Token::Multiply);
@@ -124,14 +363,22 @@ impl Lexer {
                     self.advance();
                     return anyhow::Ok(Token::ParenthesisEnd);
                 }
+                '[' => {
+                    self.advance();
+                    return anyhow::Ok(Token::SquareBracketStart);
+                }
+                ']' => {
+                    self.advance();
+                    return anyhow::Ok(Token::SquareBracketEnd);
+                }
                 ch if ch.is_alphabetic() || '_' == ch => {
                     let name = self.id();
-                    return match Keyword::from_str(&name) {
+                    return match Keyword::from_str(name) {
                         std::result::Result::Ok(keyword) => anyhow::Ok(Token::Keyword(keyword)),
-                        _ => anyhow::Ok(Token::Identifier(name)),
+                        _ => anyhow::Ok(Token::Identifier(name.to_string())),
                     };
                 }
-                ':' if self.peek().filter(|ch| *ch == &'=').is_some() => {
+                ':' if self.peek() == Some('=') => {
                     self.advance();
                     self.advance();
                     return anyhow::Ok(Token::Assign);
@@ -144,6 +391,11 @@ impl Lexer {
                     self.advance();
                     return anyhow::Ok(Token::Semi);
                 }
+                '.' if self.peek() == Some('.') => {
+                    self.advance();
+                    self.advance();
+                    return anyhow::Ok(Token::Range);
+                }
                 '.' => {
                     self.advance();
                     return anyhow::Ok(Token::Dot);
@@ -152,17 +404,59 @@ impl Lexer {
                     self.advance();
                     return anyhow::Ok(Token::Comma);
                 }
+                '=' => {
+                    self.advance();
+                    return anyhow::Ok(Token::Equal);
+                }
+                '<' => {
+                    self.advance();
+                    let token = match self.current_char {
+                        Some('=') => Token::LessEqual,
+                        Some('>') => Token::NotEqual,
+                        _ => return anyhow::Ok(Token::LessThan),
+                    };
+                    self.advance();
+                    return anyhow::Ok(token);
+                }
+                '>' => {
+                    self.advance();
+                    if self.current_char == Some('=') {
+                        self.advance();
+                        return anyhow::Ok(Token::GreaterEqual);
+                    }
+                    return anyhow::Ok(Token::GreaterThan);
+                }
+                '^' => {
+                    self.advance();
+                    return anyhow::Ok(Token::Caret);
+                }
+                '@' => {
+                    self.advance();
+                    return anyhow::Ok(Token::At);
+                }
+                ch if ch.is_control() => bail!(
+                    "unexpected control character U+{:04X} at line {}",
+                    ch as u32,
+                    self.line
+                ),
                 ch => bail!("Unable to parse {:?}", ch),
             }
         }
     }
 }
 
-impl Iterator for Lexer {
+impl Iterator for Lexer<'_> {
     type Item = anyhow::Result<Token>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        Some(self.get_next_token())
+        let token = self.get_next_token();
+        if let Some(position) = &self.position {
+            position.set(Position {
+                line: self.token_line,
+                column: self.column(self.token_start),
+            });
+        }
+        Some(token)
     }
 }
 
@@ -190,3 +484,333 @@ fn test_lexer() -> anyhow::Result<()> {
     }
     anyhow::Ok(())
 }
+
+#[test]
+fn test_digit_separators() -> anyhow::Result<()> {
+    assert_eq!(
+        Lexer::new("1_000").next().unwrap()?,
+        Token::IntegerConstant(1000)
+    );
+    assert_eq!(
+        Lexer::new("1_000_000").next().unwrap()?,
+        Token::IntegerConstant(1_000_000)
+    );
+    assert_eq!(
+        Lexer::new("2.500_25").next().unwrap()?,
+        Token::RealConstant(2.50025)
+    );
+
+    for malformed in ["1__0", "1_", "1_.5", "1._5"] {
+        assert!(
+            Lexer::new(malformed)
+                .next()
+                .unwrap()
+                .expect_err(malformed)
+                .to_string()
+                .contains("Misplaced digit separator"),
+            "{}",
+            malformed
+        );
+    }
+
+    assert_eq!(
+        Lexer::new("_1").next().unwrap().unwrap_err().to_string(),
+        "Misplaced digit separator at the start of \"_1\""
+    );
+    assert_eq!(
+        Lexer::new("_tmp").next().unwrap()?,
+        Token::Identifier("_tmp".to_string())
+    );
+    anyhow::Ok(())
+}
+
+#[test]
+fn test_out_of_range_integer_literal() {
+    assert_eq!(
+        Lexer::new("2147483647").next().unwrap().unwrap(),
+        Token::IntegerConstant(i32::MAX)
+    );
+    assert_eq!(
+        Lexer::new("99999999999")
+            .next()
+            .unwrap()
+            .expect_err("Expected the literal to overflow")
+            .to_string(),
+        "integer literal 99999999999 out of range for i32"
+    );
+}
+
+#[test]
+fn test_array_tokens() -> anyhow::Result<()> {
+    let expected_tokens = vec![
+        Token::Keyword(Keyword::Array),
+        Token::SquareBracketStart,
+        Token::IntegerConstant(1),
+        Token::Range,
+        Token::IntegerConstant(10),
+        Token::SquareBracketEnd,
+        Token::Keyword(Keyword::Of),
+        Token::Keyword(Keyword::Real),
+        Token::Identifier("a".to_string()),
+        Token::SquareBracketStart,
+        Token::RealConstant(1.5),
+        Token::SquareBracketEnd,
+        Token::Eof,
+    ];
+
+    let actual_tokens = Lexer::new("array[1..10] of real a[1.5]")
+        .take(expected_tokens.len())
+        .collect::<anyhow::Result<Vec<Token>>>()?;
+    assert_eq!(actual_tokens, expected_tokens);
+    anyhow::Ok(())
+}
+
+#[test]
+fn test_end_of_text_boundaries() -> anyhow::Result<()> {
+    assert_eq!(Lexer::new("").next().unwrap()?, Token::Eof);
+    assert_eq!(Lexer::new("{x}").next().unwrap()?, Token::Eof);
+    assert_eq!(
+        Lexer::new("END.")
+            .take(3)
+            .collect::<anyhow::Result<Vec<Token>>>()?,
+        vec![Token::Keyword(Keyword::End), Token::Dot, Token::Eof]
+    );
+    assert!(Lexer::new("END. {x").nth(2).unwrap().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_control_characters() -> anyhow::Result<()> {
+    assert_eq!(
+        Lexer::new("\tBEGIN\x0c")
+            .take(2)
+            .collect::<anyhow::Result<Vec<Token>>>()?,
+        vec![Token::Keyword(Keyword::Begin), Token::Eof]
+    );
+
+    let error = Lexer::new("BEGIN\n  x := 1;\n  \0")
+        .nth(5)
+        .unwrap()
+        .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "unexpected control character U+0000 at line 3"
+    );
+    assert_eq!(
+        Lexer::new("?").next().unwrap().unwrap_err().to_string(),
+        "Unable to parse '?'"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_multi_byte_characters() -> anyhow::Result<()> {
+    assert_eq!(
+        Lexer::new("{ größe } straße := 1")
+            .take(4)
+            .collect::<anyhow::Result<Vec<Token>>>()?,
+        vec![
+            Token::Identifier("straße".to_string()),
+            Token::Assign,
+            Token::IntegerConstant(1),
+            Token::Eof
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_string_constants() -> anyhow::Result<()> {
+    assert_eq!(
+        Lexer::new("'it''s' '' true")
+            .take(4)
+            .collect::<anyhow::Result<Vec<Token>>>()?,
+        vec![
+            Token::StringConstant("it's".to_string()),
+            Token::StringConstant("".to_string()),
+            Token::Keyword(Keyword::True),
+            Token::Eof
+        ]
+    );
+    assert_eq!(
+        Lexer::new("x := 'open\n'")
+            .nth(2)
+            .unwrap()
+            .unwrap_err()
+            .to_string(),
+        "Unterminated string starting at line 1"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_relational_operators() -> anyhow::Result<()> {
+    assert_eq!(
+        Lexer::new("= <> < <= > >= <=>")
+            .take(9)
+            .collect::<anyhow::Result<Vec<Token>>>()?,
+        vec![
+            Token::Equal,
+            Token::NotEqual,
+            Token::LessThan,
+            Token::LessEqual,
+            Token::GreaterThan,
+            Token::GreaterEqual,
+            Token::LessEqual,
+            Token::GreaterThan,
+            Token::Eof
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_pointer_symbols() -> anyhow::Result<()> {
+    assert_eq!(
+        Lexer::new("p^ @x")
+            .take(5)
+            .collect::<anyhow::Result<Vec<Token>>>()?,
+        vec![
+            Token::Identifier("p".to_string()),
+            Token::Caret,
+            Token::At,
+            Token::Identifier("x".to_string()),
+            Token::Eof
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_directives() -> anyhow::Result<()> {
+    let source = "{$mode objfpc} PROGRAM P; { not a directive } BEGIN END.";
+    let mut lexer = Lexer::new(source);
+    lexer.capture_directives = true;
+    crate::parsing::parser::Parser::new(&mut lexer).parse()?;
+    assert_eq!(lexer.directives(), ["$mode objfpc"]);
+
+    let mut lexer = Lexer::new(source);
+    crate::parsing::parser::Parser::new(&mut lexer).parse()?;
+    assert!(lexer.directives().is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_comment_delimiters() -> anyhow::Result<()> {
+    let tokens = |code: &str| {
+        Lexer::new(code)
+            .take(3)
+            .collect::<anyhow::Result<Vec<Token>>>()
+    };
+
+    let expected = vec![
+        Token::Identifier("a".to_string()),
+        Token::Identifier("b".to_string()),
+        Token::Eof,
+    ];
+    assert_eq!(tokens("a { x } b")?, expected);
+    assert_eq!(tokens("a (* x * ) *) b")?, expected);
+    assert_eq!(tokens("a (*{x*) b")?, expected);
+    assert_eq!(
+        tokens("a { x *) b }").unwrap_err().to_string(),
+        "comment opened with '{' but closed with '*)' on line 1"
+    );
+    assert_eq!(
+        tokens("a\n(* x } b *)").unwrap_err().to_string(),
+        "comment opened with '(*' but closed with '}' on line 2"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_radix_literals() -> anyhow::Result<()> {
+    let token = |code: &str| {
+        let mut lexer = Lexer::new(code);
+        lexer.radix_literals = true;
+        lexer.get_next_token()
+    };
+    let error = |code: &str| token(code).unwrap_err().to_string();
+
+    assert_eq!(token("2#1010")?, Token::IntegerConstant(10));
+    assert_eq!(token("16#ff")?, Token::IntegerConstant(255));
+    assert_eq!(token("16#FF")?, Token::IntegerConstant(255));
+    assert_eq!(token("8#17")?, Token::IntegerConstant(15));
+    assert_eq!(error("2#102"), "invalid digit '2' for base 2");
+    assert_eq!(
+        error("17#1"),
+        "base 17 of a radix literal must be from 2 to 16"
+    );
+    assert_eq!(
+        error("1#0"),
+        "base 1 of a radix literal must be from 2 to 16"
+    );
+    assert_eq!(error("16#"), "missing digits after 16#");
+    assert_eq!(
+        error("16#100000000"),
+        "integer literal 16#100000000 out of range for i32"
+    );
+    // Without the option `#` is still not a token
+    assert!(Lexer::new("2#1010").nth(1).unwrap().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_nested_comments() -> anyhow::Result<()> {
+    let tokens = |code: &str| {
+        let mut lexer = Lexer::new(code);
+        lexer.nested_comments = true;
+        lexer.take(3).collect::<anyhow::Result<Vec<Token>>>()
+    };
+
+    assert_eq!(
+        tokens("a { x (* y { z } *) } b")?,
+        vec![
+            Token::Identifier("a".to_string()),
+            Token::Identifier("b".to_string()),
+            Token::Eof,
+        ]
+    );
+    assert_eq!(
+        tokens("BEGIN\n  { outer\n    { inner }\nEND.")
+            .unwrap_err()
+            .to_string(),
+        "unterminated comment opened at line 2, column 3"
+    );
+    assert_eq!(
+        tokens("a { x (* y } *)").unwrap_err().to_string(),
+        "comment opened with '(*' but closed with '}' on line 1"
+    );
+    assert_eq!(
+        Lexer::new("a\n\t(* x")
+            .nth(1)
+            .unwrap()
+            .unwrap_err()
+            .to_string(),
+        "unterminated comment opened at line 2, column 2"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_lexemes() -> anyhow::Result<()> {
+    let lexemes = Lexer::new("Begin x1 := 1_000 + 2.50; { note } 'it''s' (* c *) END.")
+        .spanned()
+        .take(11)
+        .map(|spanned| spanned.map(|spanned| spanned.lexeme))
+        .collect::<anyhow::Result<Vec<&str>>>()?;
+    assert_eq!(
+        lexemes,
+        ["Begin", "x1", ":=", "1_000", "+", "2.50", ";", "'it''s'", "END", ".", ""]
+    );
+
+    let spanned = Lexer::new("  MyVar").spanned().next().unwrap()?;
+    assert_eq!(
+        spanned,
+        SpannedToken {
+            token: Token::Identifier("MyVar".to_string()),
+            lexeme: "MyVar",
+            offset: 2
+        }
+    );
+    Ok(())
+}
@@ -1,2 +1,3 @@
+pub mod highlight;
 pub mod lexer;
 pub mod token;
@@ -1,36 +1,113 @@
 use crate::{IntegerMachineType, RealMachineType};
-use strum_macros::EnumString;
+use std::fmt::{Display, Formatter};
+use strum_macros::{Display, EnumString, EnumVariantNames};
 
 #[derive(Debug, PartialEq)]
 pub enum Token {
     IntegerConstant(IntegerMachineType),
     RealConstant(RealMachineType),
+    StringConstant(String),
     Plus,
     Minus,
     Multiply,
+    Power,
     RealDivision,
     ParenthesisStart,
     ParenthesisEnd,
+    SquareBracketStart,
+    SquareBracketEnd,
     Eof,
     Keyword(Keyword),
     Identifier(String),
     Semi,
     Assign,
     Dot,
+    Range,
     Colon,
     Comma,
+    Equal,
+    NotEqual,
+    LessThan,
+    LessEqual,
+    GreaterThan,
+    GreaterEqual,
+    Caret,
+    At,
 }
 
-#[derive(Debug, EnumString, PartialEq)]
-#[strum(ascii_case_insensitive)]
+/// Displays as the keyword is spelled in source code, e.g. `div`
+#[derive(Debug, Display, EnumString, EnumVariantNames, PartialEq)]
+#[strum(ascii_case_insensitive, serialize_all = "lowercase")]
 pub enum Keyword {
     Begin,
     End,
     #[strum(serialize = "div")]
     IntegerDiv,
+    #[strum(serialize = "mod")]
+    Modulo,
     Var,
     Integer,
     Real,
     Program,
     Procedure,
+    Array,
+    Of,
+    Case,
+    Else,
+    Boolean,
+    String,
+    True,
+    False,
+    While,
+    Do,
+    If,
+    Then,
+    With,
+    In,
+    And,
+    Or,
+    Not,
+    Const,
+    Type,
+    /// Reserved so programs using it get a clear error, records are not supported yet
+    Record,
+    /// Reserved so programs using it get a clear error, labels are not supported yet
+    Goto,
+}
+
+/// Displays as the token is spelled in source code, for error messages
+impl Display for Token {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::IntegerConstant(i) => write!(f, "{}", i),
+            Token::RealConstant(r) => write!(f, "{:?}", r),
+            Token::StringConstant(s) => write!(f, "'{}'", s.replace('\'', "''")),
+            Token::Keyword(keyword) => write!(f, "{}", keyword),
+            Token::Identifier(name) => write!(f, "{}", name),
+            Token::Eof => write!(f, "end of file"),
+            Token::Plus => write!(f, "+"),
+            Token::Minus => write!(f, "-"),
+            Token::Multiply => write!(f, "*"),
+            Token::Power => write!(f, "**"),
+            Token::RealDivision => write!(f, "/"),
+            Token::ParenthesisStart => write!(f, "("),
+            Token::ParenthesisEnd => write!(f, ")"),
+            Token::SquareBracketStart => write!(f, "["),
+            Token::SquareBracketEnd => write!(f, "]"),
+            Token::Semi => write!(f, ";"),
+            Token::Assign => write!(f, ":="),
+            Token::Dot => write!(f, "."),
+            Token::Range => write!(f, ".."),
+            Token::Colon => write!(f, ":"),
+            Token::Comma => write!(f, ","),
+            Token::Equal => write!(f, "="),
+            Token::NotEqual => write!(f, "<>"),
+            Token::LessThan => write!(f, "<"),
+            Token::LessEqual => write!(f, "<="),
+            Token::GreaterThan => write!(f, ">"),
+            Token::GreaterEqual => write!(f, ">="),
+            Token::Caret => write!(f, "^"),
+            Token::At => write!(f, "@"),
+        }
+    }
 }
@@ -1,6 +1,36 @@
+use crate::interpreting::built_ins::{BUILT_IN_FUNCTIONS, BUILT_IN_PROCEDURES};
+use crate::lexing::token::Keyword;
+use std::sync::OnceLock;
+use strum::VariantNames;
+
 pub mod interpreting;
 pub mod lexing;
 pub mod parsing;
 
 type IntegerMachineType = i32;
 type RealMachineType = f64;
+
+/// Every reserved keyword as spelled in source code, e.g. `begin` or `div`
+pub fn keywords() -> &'static [&'static str] {
+    Keyword::VARIANTS
+}
+
+/// The names of all built-in procedures and functions, e.g. for editor autocompletion
+pub fn builtins() -> &'static [&'static str] {
+    static BUILT_INS: OnceLock<Vec<&'static str>> = OnceLock::new();
+    BUILT_INS.get_or_init(|| {
+        let mut built_ins = [BUILT_IN_PROCEDURES, BUILT_IN_FUNCTIONS].concat();
+        built_ins.sort_unstable();
+        built_ins
+    })
+}
+
+#[test]
+fn test_keywords_and_builtins() {
+    assert!(keywords().contains(&"begin"));
+    assert!(keywords().contains(&"div"));
+    assert!(!keywords().contains(&"IntegerDiv"));
+    assert!(builtins().contains(&"writeln"));
+    assert!(builtins().contains(&"abs"));
+    assert!(builtins().windows(2).all(|pair| pair[0] < pair[1]));
+}
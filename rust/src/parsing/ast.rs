@@ -1,16 +1,42 @@
+use crate::lexing::lexer::Position;
+use crate::parsing::visitor::AstVisitor;
 use crate::{IntegerMachineType, RealMachineType};
 use anyhow::{bail, Result};
+use std::fmt::{Display, Formatter};
+use strum_macros::IntoStaticStr;
 
-#[derive(PartialEq, Debug)]
+#[derive(Clone, PartialEq, Debug, IntoStaticStr)]
 pub enum Ast {
     Add(Box<Ast>, Box<Ast>),
     Subtract(Box<Ast>, Box<Ast>),
     Multiply(Box<Ast>, Box<Ast>),
     IntegerDivide(Box<Ast>, Box<Ast>),
+    Modulo(Box<Ast>, Box<Ast>),
     RealDivide(Box<Ast>, Box<Ast>),
+    Power(Box<Ast>, Box<Ast>),
+
+    /// Logical on booleans, evaluating `r` only if `l` is true, and bitwise on integers
+    And(Box<Ast>, Box<Ast>),
+    /// Logical on booleans, evaluating `r` only if `l` is false, and bitwise on integers
+    Or(Box<Ast>, Box<Ast>),
+    Not(Box<Ast>),
+
+    Equal(Box<Ast>, Box<Ast>),
+    NotEqual(Box<Ast>, Box<Ast>),
+    LessThan(Box<Ast>, Box<Ast>),
+    LessOrEqual(Box<Ast>, Box<Ast>),
+    GreaterThan(Box<Ast>, Box<Ast>),
+    GreaterOrEqual(Box<Ast>, Box<Ast>),
+    /// Whether the integer `value` is one of the `set`'s elements
+    In {
+        value: Box<Ast>,
+        set: Vec<SetElement>,
+    },
 
     IntegerConstant(IntegerMachineType),
     RealConstant(RealMachineType),
+    StringConstant(String),
+    BooleanConstant(bool),
 
     PositiveUnary(Box<Ast>),
     NegativeUnary(Box<Ast>),
@@ -31,22 +57,95 @@ pub enum Ast {
     Parameter {
         variable: Box<Ast>,
         type_spec: Box<Ast>,
+        /// Declared with VAR, so the argument is passed by reference
+        by_ref: bool,
     },
     VariableDeclaration {
         variable: Box<Ast>,
         type_spec: Box<Ast>,
     },
+    /// A `CONST` declaration, whose value has been folded into a single literal. The
+    /// `expression` it was folded from is kept as written, for printing the source back.
+    ConstantDeclaration {
+        name: String,
+        value: Box<Ast>,
+        expression: Box<Ast>,
+    },
+    /// A `TYPE` declaration of an enumerated type, whose values are named by `variants` in order
+    TypeDeclaration {
+        name: String,
+        variants: Vec<String>,
+    },
     Type(TypeSpec),
 
     Compound {
         statements: Vec<Ast>,
     },
     Variable(Variable),
+    Index {
+        array: Variable,
+        index: Box<Ast>,
+    },
     Assign(Variable, Box<Ast>),
+    IndexedAssign {
+        array: Variable,
+        index: Box<Ast>,
+        value: Box<Ast>,
+    },
+    ProcedureCall {
+        name: String,
+        arguments: Vec<Ast>,
+    },
+    While {
+        condition: Box<Ast>,
+        body: Box<Ast>,
+    },
+    /// `IF condition THEN then_branch ELSE else_branch`, where an `ELSE IF` chain nests further
+    /// `If`s in the `else_branch`
+    If {
+        condition: Box<Ast>,
+        then_branch: Box<Ast>,
+        else_branch: Option<Box<Ast>>,
+    },
+    /// `WITH record DO body`, where the record's fields can be named without qualification
+    With {
+        record: Variable,
+        body: Box<Ast>,
+    },
+    Case {
+        selector: Box<Ast>,
+        branches: Vec<CaseBranch>,
+        otherwise: Option<Box<Ast>>,
+    },
+    FunctionCall {
+        name: String,
+        arguments: Vec<Ast>,
+    },
+    /// An expression along with where it starts in the source, only produced by a parser
+    /// created with `Parser::with_positions`
+    Located {
+        position: Position,
+        node: Box<Ast>,
+    },
     NoOp,
 }
 
 impl Ast {
+    /// Shorthand for an `Ast::IntegerConstant`, mostly for writing trees by hand
+    pub fn int(value: IntegerMachineType) -> Ast {
+        Ast::IntegerConstant(value)
+    }
+
+    /// Shorthand for an `Ast::RealConstant`
+    pub fn real(value: RealMachineType) -> Ast {
+        Ast::RealConstant(value)
+    }
+
+    /// Shorthand for an `Ast::Variable` named `name`
+    pub fn var(name: impl Into<String>) -> Ast {
+        Ast::Variable(Variable::new(name))
+    }
+
     pub fn variable(&self) -> Result<&Variable> {
         if let Ast::Variable(variable) = self {
             Ok(variable)
@@ -61,21 +160,378 @@ impl Ast {
             bail!("Expected a type spec, was {:?}", self)
         }
     }
+    pub fn statements(&self) -> Result<&[Ast]> {
+        if let Ast::Compound { statements } = self {
+            Ok(statements)
+        } else {
+            bail!("Expected a compound statement, was {:?}", self)
+        }
+    }
+
+    /// Calls the `visitor` method handling this kind of node
+    pub fn accept<V: AstVisitor + ?Sized>(&self, visitor: &mut V) -> Result<()> {
+        match self {
+            Ast::Program { name, block } => visitor.visit_program(name, block),
+            Ast::Block {
+                declarations,
+                compound_statements,
+            } => visitor.visit_block(declarations, compound_statements),
+            Ast::ProcedureDeclaration {
+                name,
+                parameters,
+                block,
+            } => visitor.visit_procedure_declaration(name, parameters, block),
+            Ast::Parameter {
+                variable,
+                type_spec,
+                by_ref,
+            } => visitor.visit_parameter(variable, type_spec, *by_ref),
+            Ast::VariableDeclaration {
+                variable,
+                type_spec,
+            } => visitor.visit_variable_declaration(variable, type_spec),
+            Ast::ConstantDeclaration { name, value, .. } => {
+                visitor.visit_constant_declaration(name, value)
+            }
+            Ast::TypeDeclaration { name, variants } => {
+                visitor.visit_type_declaration(name, variants)
+            }
+            Ast::Type(type_spec) => visitor.visit_type(type_spec),
+            Ast::Compound { statements } => visitor.visit_compound(statements),
+            Ast::Assign(variable, value) => visitor.visit_assign(variable, value),
+            Ast::IndexedAssign {
+                array,
+                index,
+                value,
+            } => visitor.visit_indexed_assign(array, index, value),
+            Ast::ProcedureCall { name, arguments } => visitor.visit_procedure_call(name, arguments),
+            Ast::While { condition, body } => visitor.visit_while(condition, body),
+            Ast::With { record, body } => visitor.visit_with(record, body),
+            Ast::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => visitor.visit_if(condition, then_branch, else_branch.as_deref()),
+            Ast::Case {
+                selector,
+                branches,
+                otherwise,
+            } => visitor.visit_case(selector, branches, otherwise.as_deref()),
+            Ast::NoOp => visitor.visit_no_op(),
+            Ast::Add(_, _)
+            | Ast::Subtract(_, _)
+            | Ast::Multiply(_, _)
+            | Ast::IntegerDivide(_, _)
+            | Ast::Modulo(_, _)
+            | Ast::RealDivide(_, _)
+            | Ast::Power(_, _)
+            | Ast::And(_, _)
+            | Ast::Or(_, _)
+            | Ast::Not(_)
+            | Ast::Equal(_, _)
+            | Ast::NotEqual(_, _)
+            | Ast::LessThan(_, _)
+            | Ast::LessOrEqual(_, _)
+            | Ast::GreaterThan(_, _)
+            | Ast::GreaterOrEqual(_, _)
+            | Ast::In { .. }
+            | Ast::IntegerConstant(_)
+            | Ast::RealConstant(_)
+            | Ast::StringConstant(_)
+            | Ast::BooleanConstant(_)
+            | Ast::PositiveUnary(_)
+            | Ast::NegativeUnary(_)
+            | Ast::Variable(_)
+            | Ast::Index { .. }
+            | Ast::FunctionCall { .. }
+            | Ast::Located { .. } => visitor.visit_expression(self),
+        }
+    }
+
+    /// The name of this node's variant, e.g. `"Assign"`
+    pub fn kind(&self) -> &'static str {
+        self.into()
+    }
+
+    /// The nodes directly nested in this one, in source order. Generic passes like `node_counts`
+    /// rely on this, so it must list the nested nodes of every variant.
+    pub fn children(&self) -> Vec<&Ast> {
+        match self {
+            Ast::Add(l, r)
+            | Ast::Subtract(l, r)
+            | Ast::Multiply(l, r)
+            | Ast::IntegerDivide(l, r)
+            | Ast::Modulo(l, r)
+            | Ast::RealDivide(l, r)
+            | Ast::Power(l, r)
+            | Ast::And(l, r)
+            | Ast::Or(l, r)
+            | Ast::Equal(l, r)
+            | Ast::NotEqual(l, r)
+            | Ast::LessThan(l, r)
+            | Ast::LessOrEqual(l, r)
+            | Ast::GreaterThan(l, r)
+            | Ast::GreaterOrEqual(l, r) => vec![l, r],
+            Ast::In { value, set } => std::iter::once(value.as_ref())
+                .chain(set.iter().flat_map(SetElement::bounds))
+                .collect(),
+            Ast::PositiveUnary(nested) | Ast::NegativeUnary(nested) | Ast::Not(nested) => {
+                vec![nested]
+            }
+            Ast::Program { block, .. } => vec![block],
+            Ast::Block {
+                declarations,
+                compound_statements,
+            } => declarations
+                .iter()
+                .chain(std::iter::once(compound_statements.as_ref()))
+                .collect(),
+            Ast::ProcedureDeclaration {
+                parameters, block, ..
+            } => parameters
+                .iter()
+                .chain(std::iter::once(block.as_ref()))
+                .collect(),
+            Ast::Parameter {
+                variable,
+                type_spec,
+                ..
+            }
+            | Ast::VariableDeclaration {
+                variable,
+                type_spec,
+            } => vec![variable, type_spec],
+            Ast::ConstantDeclaration { value, .. } => vec![value],
+            Ast::Compound { statements } => statements.iter().collect(),
+            Ast::Index { index, .. } => vec![index],
+            Ast::Assign(_, value) => vec![value],
+            Ast::IndexedAssign { index, value, .. } => vec![index, value],
+            Ast::ProcedureCall { arguments, .. } | Ast::FunctionCall { arguments, .. } => {
+                arguments.iter().collect()
+            }
+            Ast::While { condition, body } => vec![condition, body],
+            Ast::With { body, .. } => vec![body],
+            Ast::Located { node, .. } => vec![node],
+            Ast::If {
+                condition,
+                then_branch,
+                else_branch,
+            } => std::iter::once(condition.as_ref())
+                .chain(std::iter::once(then_branch.as_ref()))
+                .chain(else_branch.as_deref())
+                .collect(),
+            Ast::Case {
+                selector,
+                branches,
+                otherwise,
+            } => std::iter::once(selector.as_ref())
+                .chain(branches.iter().map(|branch| &branch.statement))
+                .chain(otherwise.as_deref())
+                .collect(),
+            Ast::IntegerConstant(_)
+            | Ast::RealConstant(_)
+            | Ast::StringConstant(_)
+            | Ast::BooleanConstant(_)
+            | Ast::TypeDeclaration { .. }
+            | Ast::Type(_)
+            | Ast::Variable(_)
+            | Ast::NoOp => vec![],
+        }
+    }
 }
 
-#[derive(strum_macros::Display, PartialEq, Debug, Clone)]
+/// One `labels: statement` arm of a `CASE` statement
+#[derive(Clone, PartialEq, Debug)]
+pub struct CaseBranch {
+    pub labels: Vec<CaseLabel>,
+    pub statement: Ast,
+}
+
+/// A constant `CASE` label, either a single value or an inclusive range like `1..5`
+#[derive(Clone, PartialEq, Debug)]
+pub enum CaseLabel {
+    Single(IntegerMachineType),
+    Range(IntegerMachineType, IntegerMachineType),
+    /// A non-standard real label like `0.3`, for real selectors
+    Real(RealMachineType),
+}
+
+impl CaseLabel {
+    pub fn matches(&self, value: IntegerMachineType) -> bool {
+        match self {
+            CaseLabel::Single(label) => *label == value,
+            CaseLabel::Range(lower, upper) => (*lower..=*upper).contains(&value),
+            CaseLabel::Real(label) => *label == value as RealMachineType,
+        }
+    }
+
+    /// Whether a real selector is within `epsilon` of this label
+    pub fn matches_real(&self, value: RealMachineType, epsilon: RealMachineType) -> bool {
+        match self {
+            CaseLabel::Single(label) => (*label as RealMachineType - value).abs() <= epsilon,
+            CaseLabel::Range(lower, upper) => (*lower as RealMachineType - epsilon
+                ..=*upper as RealMachineType + epsilon)
+                .contains(&value),
+            CaseLabel::Real(label) => (label - value).abs() <= epsilon,
+        }
+    }
+}
+
+impl Display for CaseLabel {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            CaseLabel::Single(label) => Display::fmt(label, f),
+            CaseLabel::Range(lower, upper) => write!(f, "{}..{}", lower, upper),
+            CaseLabel::Real(label) => write!(f, "{:?}", label),
+        }
+    }
+}
+
+/// An element of the set after `in`, either a single value or an inclusive range like `1..10`
+#[derive(Clone, PartialEq, Debug)]
+pub enum SetElement {
+    Single(Ast),
+    Range(Ast, Ast),
+}
+
+impl SetElement {
+    /// The expressions making up this element
+    pub fn bounds(&self) -> Vec<&Ast> {
+        match self {
+            SetElement::Single(value) => vec![value],
+            SetElement::Range(lower, upper) => vec![lower, upper],
+        }
+    }
+}
+
+/// Strips the `NoOp`s that trailing semicolons leave at the end of compound statements, so
+/// programs differing only in those semicolons compare equal. An empty compound keeps one `NoOp`.
+pub fn normalize(node: Ast) -> Ast {
+    match node {
+        Ast::Program { name, block } => Ast::Program {
+            name,
+            block: Box::from(normalize(*block)),
+        },
+        Ast::Block {
+            declarations,
+            compound_statements,
+        } => Ast::Block {
+            declarations: declarations.into_iter().map(normalize).collect(),
+            compound_statements: Box::from(normalize(*compound_statements)),
+        },
+        Ast::ProcedureDeclaration {
+            name,
+            parameters,
+            block,
+        } => Ast::ProcedureDeclaration {
+            name,
+            parameters,
+            block: Box::from(normalize(*block)),
+        },
+        Ast::Compound { statements } => {
+            let mut statements: Vec<Ast> = statements.into_iter().map(normalize).collect();
+            while statements.len() > 1 && statements.last() == Some(&Ast::NoOp) {
+                statements.pop();
+            }
+            Ast::Compound { statements }
+        }
+        Ast::While { condition, body } => Ast::While {
+            condition,
+            body: Box::from(normalize(*body)),
+        },
+        Ast::With { record, body } => Ast::With {
+            record,
+            body: Box::from(normalize(*body)),
+        },
+        Ast::If {
+            condition,
+            then_branch,
+            else_branch,
+        } => Ast::If {
+            condition,
+            then_branch: Box::from(normalize(*then_branch)),
+            else_branch: else_branch.map(|statement| Box::from(normalize(*statement))),
+        },
+        Ast::Case {
+            selector,
+            branches,
+            otherwise,
+        } => Ast::Case {
+            selector,
+            branches: branches
+                .into_iter()
+                .map(|branch| CaseBranch {
+                    labels: branch.labels,
+                    statement: normalize(branch.statement),
+                })
+                .collect(),
+            otherwise: otherwise.map(|statement| Box::from(normalize(*statement))),
+        },
+        _ => node,
+    }
+}
+
+/// Displays in uppercase like `BuiltInTypes`, with arrays spelled out and enumerated types by name
+#[derive(PartialEq, Debug, Clone, IntoStaticStr)]
+#[strum(serialize_all = "UPPERCASE")]
 pub enum TypeSpec {
     Integer,
     Real,
+    Boolean,
+    String,
+    Array {
+        lower: IntegerMachineType,
+        upper: IntegerMachineType,
+        element_type: Box<TypeSpec>,
+    },
+    /// A type declared by a `TYPE` declaration, e.g. `Color = (Red, Green, Blue)`
+    Enumeration {
+        name: String,
+        variants: Vec<String>,
+    },
 }
 
 impl TypeSpec {
     pub(crate) fn to_ast_clone(&self) -> Ast {
         Ast::Type(self.clone())
     }
+
+    /// The type of the individual values stored, e.g. `Integer` for an `ARRAY[1..5] OF INTEGER`
+    pub fn element_type(&self) -> &TypeSpec {
+        match self {
+            TypeSpec::Array { element_type, .. } => element_type.element_type(),
+            _ => self,
+        }
+    }
+}
+
+impl Display for TypeSpec {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TypeSpec::Array {
+                lower,
+                upper,
+                element_type,
+            } => write!(f, "ARRAY[{}..{}] OF {}", lower, upper, element_type),
+            TypeSpec::Enumeration { name, .. } => f.write_str(name),
+            built_in => f.write_str(built_in.into()),
+        }
+    }
 }
 
 #[derive(Clone, PartialEq, Debug)]
 pub struct Variable {
     pub name: String,
 }
+
+impl Variable {
+    pub fn new(name: impl Into<String>) -> Variable {
+        Variable { name: name.into() }
+    }
+}
+
+impl From<&str> for Variable {
+    fn from(name: &str) -> Self {
+        Variable::new(name)
+    }
+}
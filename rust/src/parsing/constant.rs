@@ -0,0 +1,119 @@
+use crate::parsing::ast::Ast;
+use crate::{IntegerMachineType, RealMachineType};
+use anyhow::{bail, Context, Result};
+use case_insensitive_hashmap::CaseInsensitiveHashMap;
+
+/// Evaluates the value of a `CONST` declaration down to a single literal. Besides literals it
+/// may only refer to the `constants` declared before it.
+pub(crate) fn fold_constant(node: &Ast, constants: &CaseInsensitiveHashMap<Ast>) -> Result<Ast> {
+    Ok(match node {
+        Ast::IntegerConstant(_)
+        | Ast::RealConstant(_)
+        | Ast::StringConstant(_)
+        | Ast::BooleanConstant(_) => node.clone(),
+        Ast::Variable(variable) => constants
+            .get(variable.name.clone())
+            .cloned()
+            .with_context(|| format!("{} is not a constant", variable.name))?,
+        Ast::Located { node, .. } => fold_constant(node, constants)?,
+        Ast::PositiveUnary(nested) => match fold_constant(nested, constants)? {
+            number @ (Ast::IntegerConstant(_) | Ast::RealConstant(_)) => number,
+            other => bail!("Cannot apply unary + to {:?}", other),
+        },
+        Ast::NegativeUnary(nested) => match fold_constant(nested, constants)? {
+            Ast::IntegerConstant(i) => Ast::IntegerConstant(
+                i.checked_neg()
+                    .with_context(|| format!("-({}) overflows an integer", i))?,
+            ),
+            Ast::RealConstant(r) => Ast::RealConstant(-r),
+            other => bail!("Cannot apply unary - to {:?}", other),
+        },
+        Ast::Add(l, r)
+        | Ast::Subtract(l, r)
+        | Ast::Multiply(l, r)
+        | Ast::RealDivide(l, r)
+        | Ast::IntegerDivide(l, r)
+        | Ast::Modulo(l, r) => {
+            let (l, r) = (fold_constant(l, constants)?, fold_constant(r, constants)?);
+            binary(node, l, r)?
+        }
+        _ => bail!("{} is not allowed in a constant", node.kind()),
+    })
+}
+
+/// Applies the operator of `node` to its folded operands
+fn binary(node: &Ast, l: Ast, r: Ast) -> Result<Ast> {
+    if let Ast::IntegerDivide(_, _) | Ast::Modulo(_, _) = node {
+        return integer_binary(node, integer_operand(node, l)?, integer_operand(node, r)?);
+    }
+    let real = |l: RealMachineType, r: RealMachineType| {
+        Ok(Ast::RealConstant(match node {
+            Ast::Add(_, _) => l + r,
+            Ast::Subtract(_, _) => l - r,
+            Ast::Multiply(_, _) => l * r,
+            Ast::RealDivide(_, _) => l / r,
+            _ => bail!("{} requires integer constants", node.kind()),
+        }))
+    };
+    match (l, r) {
+        (Ast::StringConstant(l), Ast::StringConstant(r)) if matches!(node, Ast::Add(_, _)) => {
+            Ok(Ast::StringConstant(l + &r))
+        }
+        (Ast::IntegerConstant(l), Ast::IntegerConstant(r)) => match node {
+            Ast::RealDivide(_, _) => real(l as RealMachineType, r as RealMachineType),
+            _ => integer_binary(node, l, r),
+        },
+        (Ast::IntegerConstant(l), Ast::RealConstant(r)) => real(l as RealMachineType, r),
+        (Ast::RealConstant(l), Ast::IntegerConstant(r)) => real(l, r as RealMachineType),
+        (Ast::RealConstant(l), Ast::RealConstant(r)) => real(l, r),
+        (l, r) => bail!("Cannot {} {:?} and {:?}", node.kind(), l, r),
+    }
+}
+
+/// An operand of `div` or `mod`, with reals truncated as the interpreter does outside of strict
+/// mode
+fn integer_operand(node: &Ast, operand: Ast) -> Result<IntegerMachineType> {
+    match operand {
+        Ast::IntegerConstant(i) => Ok(i),
+        Ast::RealConstant(r)
+            if r.trunc() < IntegerMachineType::MIN as RealMachineType
+                || r.trunc() > IntegerMachineType::MAX as RealMachineType
+                || r.is_nan() =>
+        {
+            bail!(
+                "{} of the real {} is out of the integer range",
+                node.kind(),
+                r
+            )
+        }
+        Ast::RealConstant(r) => Ok(r as IntegerMachineType),
+        operand => bail!(
+            "{} requires numeric constants, found {:?}",
+            node.kind(),
+            operand
+        ),
+    }
+}
+
+/// Applies an integer operator, failing the way the interpreter does at runtime
+fn integer_binary(node: &Ast, l: IntegerMachineType, r: IntegerMachineType) -> Result<Ast> {
+    let folded = match node {
+        Ast::Add(_, _) => l.checked_add(r),
+        Ast::Subtract(_, _) => l.checked_sub(r),
+        Ast::Multiply(_, _) => l.checked_mul(r),
+        Ast::IntegerDivide(_, _) if r == 0 => bail!("integer division (div) by zero"),
+        Ast::IntegerDivide(_, _) => l.checked_div(r),
+        Ast::Modulo(_, _) if r == 0 => bail!("modulo (mod) by zero"),
+        Ast::Modulo(_, _) if r < 0 => bail!("modulo (mod) by the negative number {}", r),
+        Ast::Modulo(_, _) => Some(l.rem_euclid(r)),
+        _ => bail!("{} is not an integer operator", node.kind()),
+    };
+    folded.map(Ast::IntegerConstant).with_context(|| {
+        format!(
+            "{} of the constants {} and {} is out of range",
+            node.kind(),
+            l,
+            r
+        )
+    })
+}
@@ -1,5 +1,7 @@
 pub mod ast;
+mod constant;
 pub mod parser;
+pub mod visitor;
 
 #[cfg(test)]
 mod tests;
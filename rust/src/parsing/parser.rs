@@ -1,51 +1,157 @@
+use crate::lexing::lexer::{Lexer, Position};
 use crate::lexing::token::{Keyword, Token};
 use crate::parsing::ast::Ast::{Block, Program};
-use crate::parsing::ast::{Ast, TypeSpec, Variable};
-use anyhow::bail;
+use crate::parsing::ast::{Ast, CaseBranch, CaseLabel, SetElement, TypeSpec, Variable};
+use crate::parsing::constant::fold_constant;
+use crate::IntegerMachineType;
+use anyhow::{bail, Context};
+use case_insensitive_hashmap::CaseInsensitiveHashMap;
+use std::cell::Cell;
+use std::rc::Rc;
+
+/// Lexes and parses a whole program
+pub fn parse_program(source: &str) -> anyhow::Result<Ast> {
+    Parser::new(Lexer::new(source)).parse()
+}
+
+/// Lexes and parses a single expression, rejecting anything after it
+pub fn parse_expr(source: &str) -> anyhow::Result<Ast> {
+    Parser::new(Lexer::new(source)).parse_expression()
+}
+
+/// How deeply parentheses, unary operators and arguments may nest before parsing gives up,
+/// keeping pathological input from overflowing the stack
+pub const MAX_NESTING_DEPTH: usize = 128;
+
+/// Reported wherever a pointer type, dereference (`^`) or address (`@`) shows up
+const POINTERS_UNSUPPORTED: &str = "pointers are not yet supported";
+const RECORDS_UNSUPPORTED: &str = "record fields are not yet supported";
 
 pub struct Parser<I: Iterator<Item = anyhow::Result<Token>>> {
     current_token: Token,
     tokens: I,
+    /// Where the tokens come from, if the token stream tracks it
+    positions: Option<Rc<Cell<Position>>>,
+    current_position: Position,
+    warnings: Vec<String>,
+    /// Accepts a declaration missing its `;` right before `BEGIN` or `PROCEDURE`, with a
+    /// warning, as some dialects allow. Off by default.
+    pub lenient: bool,
+    depth: usize,
+    /// Folded values of the constants declared in the enclosing blocks
+    constants: CaseInsensitiveHashMap<Ast>,
+    /// Types declared in the enclosing blocks
+    types: CaseInsensitiveHashMap<TypeSpec>,
 }
 
+/// The token stream of a parser built with `Parser::from_tokens`
+pub type TokenVecIter =
+    std::iter::Map<std::vec::IntoIter<Token>, fn(Token) -> anyhow::Result<Token>>;
+
 macro_rules! eat {
-    ( $self:ident, $token:pat ) => {
-        match &$self.current_token {
-            $token => {
-                $self.advance()?;
-            }
-            t => bail!("Expected {:?}, found {:?}", stringify!($token), t),
-        };
+    ( $self:ident, $token:expr ) => {
+        if $self.current_token == $token {
+            $self.advance()?;
+        } else {
+            bail!("Expected {}, found {}", $token, $self.current_token);
+        }
     };
 }
 
+impl Parser<TokenVecIter> {
+    /// Parses tokens that were already lexed, e.g. by tooling. A missing trailing `Token::Eof`
+    /// is implied.
+    pub fn from_tokens(tokens: Vec<Token>) -> Self {
+        Parser::new(
+            tokens
+                .into_iter()
+                .map(Ok as fn(Token) -> anyhow::Result<Token>),
+        )
+    }
+}
+
+impl<'a> Parser<Lexer<'a>> {
+    /// Parses like `new`, but wraps the operators, calls and indexing in expressions in
+    /// `Ast::Located` nodes, so runtime errors can point at the source
+    pub fn with_positions(mut lexer: Lexer<'a>) -> Self {
+        let positions = lexer.track_positions();
+        let mut parser = Parser::new(lexer);
+        parser.positions = Some(positions);
+        parser
+    }
+}
+
 impl<I: Iterator<Item = anyhow::Result<Token>>> Parser<I> {
     pub fn new(tokens: I) -> Parser<I> {
         Parser {
             current_token: Token::Eof,
             tokens,
+            positions: None,
+            current_position: Position::default(),
+            warnings: vec![],
+            lenient: false,
+            depth: 0,
+            constants: CaseInsensitiveHashMap::new(),
+            types: CaseInsensitiveHashMap::new(),
         }
     }
 
     fn advance(&mut self) -> anyhow::Result<()> {
-        self.current_token = self
-            .tokens
-            .next()
-            .unwrap_or(Ok(Token::Eof))
-            .unwrap_or(Token::Eof);
+        self.current_token = self.tokens.next().unwrap_or(Ok(Token::Eof))?;
+        if let Some(positions) = &self.positions {
+            self.current_position = positions.get();
+        }
         Ok(())
     }
 
-    /// factor : (PLUS | MINUS) factor | INTEGER_CONST | REAL_CONST | LPAREN expr RPAREN | variable
+    /// Records that `node` was found at `position`, if positions are tracked
+    fn located(&self, position: Position, node: Ast) -> Ast {
+        match self.positions {
+            Some(_) => Ast::Located {
+                position,
+                node: Box::from(node),
+            },
+            None => node,
+        }
+    }
+
+    /// Runs `parse` one nesting level deeper, failing once `MAX_NESTING_DEPTH` is exceeded
+    fn nested(&mut self, parse: fn(&mut Self) -> anyhow::Result<Ast>) -> anyhow::Result<Ast> {
+        if self.depth >= MAX_NESTING_DEPTH {
+            bail!("maximum expression nesting depth exceeded");
+        }
+        self.depth += 1;
+        let output = parse(self);
+        self.depth -= 1;
+        output
+    }
+
+    /// factor : (PLUS | MINUS) power
+    ///          | NOT factor
+    ///          | INTEGER_CONST | REAL_CONST | STRING_CONST | TRUE | FALSE
+    ///          | LPAREN expr RPAREN
+    ///          | variable
+    ///          | indexed_variable
+    ///          | function_call
     fn factor(&mut self) -> anyhow::Result<Ast> {
         match self.current_token {
             Token::Plus => {
+                let position = self.current_position;
                 self.advance()?;
-                Ok(Ast::PositiveUnary(Box::from(self.factor()?)))
+                let nested = Ast::PositiveUnary(Box::from(self.nested(Self::power)?));
+                Ok(self.located(position, nested))
             }
             Token::Minus => {
+                let position = self.current_position;
+                self.advance()?;
+                let nested = Ast::NegativeUnary(Box::from(self.nested(Self::power)?));
+                Ok(self.located(position, nested))
+            }
+            Token::Keyword(Keyword::Not) => {
+                let position = self.current_position;
                 self.advance()?;
-                Ok(Ast::NegativeUnary(Box::from(self.factor()?)))
+                let nested = Ast::Not(Box::from(self.nested(Self::factor)?));
+                Ok(self.located(position, nested))
             }
             Token::IntegerConstant(i) => {
                 self.advance()?;
@@ -55,63 +161,147 @@ impl<I: Iterator<Item = anyhow::Result<Token>>> Parser<I> {
                 self.advance()?;
                 Ok(Ast::RealConstant(r))
             }
+            Token::StringConstant(ref s) => {
+                let s = s.clone();
+                self.advance()?;
+                Ok(Ast::StringConstant(s))
+            }
+            Token::Keyword(Keyword::True) => {
+                self.advance()?;
+                Ok(Ast::BooleanConstant(true))
+            }
+            Token::Keyword(Keyword::False) => {
+                self.advance()?;
+                Ok(Ast::BooleanConstant(false))
+            }
             Token::ParenthesisStart => {
                 self.advance()?;
-                let nested_result = self.expr();
+                let nested_result = self.nested(Self::expr)?;
                 eat!(self, Token::ParenthesisEnd);
-                nested_result
+                Ok(nested_result)
             }
-            Token::Identifier(_) => self.variable(),
+            Token::Identifier(_) => {
+                let position = self.current_position;
+                let var_node = self.variable()?;
+                let node = match &self.current_token {
+                    Token::ParenthesisStart => self.function_call(var_node)?,
+                    Token::SquareBracketStart => self.indexed_variable(var_node)?,
+                    Token::Caret => bail!(POINTERS_UNSUPPORTED),
+                    _ => return Ok(var_node),
+                };
+                Ok(self.located(position, node))
+            }
+            Token::At => bail!(POINTERS_UNSUPPORTED),
             _ => bail!(
-                "Expected integer, parenthesis, or variable instead of {:?}",
+                "Expected integer, parenthesis, or variable instead of {}",
                 self.current_token
             ),
         }
     }
 
-    /// term : factor ((MUL | INTEGER_DIV | REAL_DIV) factor)*
+    /// power : factor (POWER power)?
+    ///
+    /// Right associative, so `2 ** 3 ** 2` is `2 ** 9`, and binds tighter than a sign, so
+    /// `-2 ** 2` is `-(2 ** 2)`
+    fn power(&mut self) -> anyhow::Result<Ast> {
+        let base = self.factor()?;
+        if self.current_token != Token::Power {
+            return Ok(base);
+        }
+        let position = self.current_position;
+        self.advance()?;
+        let exponent = self.nested(Self::power)?;
+        Ok(self.located(position, Ast::Power(Box::from(base), Box::from(exponent))))
+    }
+
+    /// term : power ((MUL | INTEGER_DIV | INTEGER_MOD | REAL_DIV | AND) power)*
     fn term(&mut self) -> anyhow::Result<Ast> {
-        let mut result = self.factor()?;
+        let mut result = self.power()?;
 
         loop {
-            match self.current_token {
-                Token::Multiply => {
-                    self.advance()?;
-                    result = Ast::Multiply(Box::from(result), Box::from(self.factor()?));
-                }
-                Token::Keyword(Keyword::IntegerDiv) => {
-                    self.advance()?;
-                    result = Ast::IntegerDivide(Box::from(result), Box::from(self.factor()?));
-                }
-                Token::RealDivision => {
-                    self.advance()?;
-                    result = Ast::RealDivide(Box::from(result), Box::from(self.factor()?));
-                }
-                _ => {
-                    break;
-                }
-            }
+            let operator: fn(Box<Ast>, Box<Ast>) -> Ast = match self.current_token {
+                Token::Multiply => Ast::Multiply,
+                Token::Keyword(Keyword::IntegerDiv) => Ast::IntegerDivide,
+                Token::Keyword(Keyword::Modulo) => Ast::Modulo,
+                Token::RealDivision => Ast::RealDivide,
+                Token::Keyword(Keyword::And) => Ast::And,
+                _ => break,
+            };
+            let position = self.current_position;
+            self.advance()?;
+            let node = operator(Box::from(result), Box::from(self.power()?));
+            result = self.located(position, node);
         }
         Ok(result)
     }
 
+    /// expr : simple_expression ((EQUAL | NOT_EQUAL | LESS | LESS_EQUAL | GREATER | GREATER_EQUAL)
+    ///         simple_expression | IN set)?
     fn expr(&mut self) -> anyhow::Result<Ast> {
+        let left = self.simple_expression()?;
+        let position = self.current_position;
+        let comparison: fn(Box<Ast>, Box<Ast>) -> Ast = match self.current_token {
+            Token::Keyword(Keyword::In) => {
+                self.advance()?;
+                let membership = Ast::In {
+                    value: Box::from(left),
+                    set: self.set()?,
+                };
+                return Ok(self.located(position, membership));
+            }
+            Token::Equal => Ast::Equal,
+            Token::NotEqual => Ast::NotEqual,
+            Token::LessThan => Ast::LessThan,
+            Token::LessEqual => Ast::LessOrEqual,
+            Token::GreaterThan => Ast::GreaterThan,
+            Token::GreaterEqual => Ast::GreaterOrEqual,
+            _ => return Ok(left),
+        };
+        self.advance()?;
+        let node = comparison(Box::from(left), Box::from(self.simple_expression()?));
+        Ok(self.located(position, node))
+    }
+
+    /// set : LBRACKET (set_element (COMMA set_element)*)? RBRACKET
+    /// set_element : expr (RANGE expr)?
+    fn set(&mut self) -> anyhow::Result<Vec<SetElement>> {
+        eat!(self, Token::SquareBracketStart);
+        let mut elements = vec![];
+        if self.current_token != Token::SquareBracketEnd {
+            elements.push(self.set_element()?);
+            while let Token::Comma = &self.current_token {
+                self.advance()?;
+                elements.push(self.set_element()?);
+            }
+        }
+        eat!(self, Token::SquareBracketEnd);
+        Ok(elements)
+    }
+
+    fn set_element(&mut self) -> anyhow::Result<SetElement> {
+        let lower = self.nested(Self::expr)?;
+        if let Token::Range = &self.current_token {
+            self.advance()?;
+            return Ok(SetElement::Range(lower, self.nested(Self::expr)?));
+        }
+        Ok(SetElement::Single(lower))
+    }
+
+    /// simple_expression : term ((PLUS | MINUS | OR) term)*
+    fn simple_expression(&mut self) -> anyhow::Result<Ast> {
         let mut result = self.term()?;
 
         loop {
-            match self.current_token {
-                Token::Plus => {
-                    self.advance()?;
-                    result = Ast::Add(Box::from(result), Box::from(self.term()?));
-                }
-                Token::Minus => {
-                    self.advance()?;
-                    result = Ast::Subtract(Box::from(result), Box::from(self.term()?));
-                }
-                _ => {
-                    break;
-                }
-            }
+            let operator: fn(Box<Ast>, Box<Ast>) -> Ast = match self.current_token {
+                Token::Plus => Ast::Add,
+                Token::Minus => Ast::Subtract,
+                Token::Keyword(Keyword::Or) => Ast::Or,
+                _ => break,
+            };
+            let position = self.current_position;
+            self.advance()?;
+            let node = operator(Box::from(result), Box::from(self.term()?));
+            result = self.located(position, node);
         }
 
         Ok(result)
@@ -128,27 +318,223 @@ impl<I: Iterator<Item = anyhow::Result<Token>>> Parser<I> {
             let name = variable_name.clone();
             self.advance()?;
             Ok(Ast::Variable(Variable { name }))
+        } else if let Token::Keyword(keyword) = &self.current_token {
+            bail!(
+                "'{}' is a reserved keyword and cannot be used as a variable name",
+                keyword
+            )
         } else {
-            bail!("Expected a variable, found {:?}", self.current_token)
+            bail!("Expected a variable, found {}", self.current_token)
         }
     }
 
-    /// assignment_statement : variable ASSIGN expr
-    fn assignment_statement(&mut self) -> anyhow::Result<Ast> {
-        let var_node = self.variable()?;
+    /// indexed_variable : ID LBRACKET expr RBRACKET
+    fn indexed_variable(&mut self, var_node: Ast) -> anyhow::Result<Ast> {
+        let array = var_node.variable()?.clone();
+        eat!(self, Token::SquareBracketStart);
+        let index = self.nested(Self::expr)?;
+        eat!(self, Token::SquareBracketEnd);
+        Ok(Ast::Index {
+            array,
+            index: Box::from(index),
+        })
+    }
 
+    /// assignment_statement : (variable | indexed_variable) ASSIGN expr
+    fn assignment_statement(&mut self, target: Ast) -> anyhow::Result<Ast> {
+        if self.current_token == Token::Equal {
+            bail!("did you mean ':=' (assignment) instead of '=' (comparison)?");
+        }
         eat!(self, Token::Assign);
-        let variable = var_node.variable()?.clone();
-        Ok(Ast::Assign(variable, Box::from(self.expr()?)))
+        let value = Box::from(self.expr()?);
+        match target {
+            Ast::Variable(variable) => Ok(Ast::Assign(variable, value)),
+            Ast::Index { array, index } => Ok(Ast::IndexedAssign {
+                array,
+                index,
+                value,
+            }),
+            target => bail!("Cannot assign to {:?}", target),
+        }
+    }
+
+    /// argument_list : LPAREN (expr (COMMA expr)*)? RPAREN
+    fn argument_list(&mut self) -> anyhow::Result<Vec<Ast>> {
+        eat!(self, Token::ParenthesisStart);
+        let mut arguments = vec![];
+        if !matches!(&self.current_token, Token::ParenthesisEnd) {
+            arguments.push(self.nested(Self::expr)?);
+            while let Token::Comma = &self.current_token {
+                self.advance()?;
+                arguments.push(self.nested(Self::expr)?);
+            }
+        }
+        eat!(self, Token::ParenthesisEnd);
+        Ok(arguments)
+    }
+
+    /// function_call : ID argument_list
+    fn function_call(&mut self, var_node: Ast) -> anyhow::Result<Ast> {
+        let name = var_node.variable()?.name.clone();
+        Ok(Ast::FunctionCall {
+            name,
+            arguments: self.argument_list()?,
+        })
+    }
+
+    /// procedure_call_statement : ID argument_list?
+    fn procedure_call_statement(&mut self, var_node: Ast) -> anyhow::Result<Ast> {
+        let name = var_node.variable()?.name.clone();
+
+        let arguments = if let Token::ParenthesisStart = &self.current_token {
+            self.argument_list()?
+        } else {
+            vec![]
+        };
+
+        Ok(Ast::ProcedureCall { name, arguments })
+    }
+
+    /// case_label : signed_integer (RANGE signed_integer)?
+    ///              | (PLUS | MINUS)? REAL_CONST
+    fn case_label(&mut self) -> anyhow::Result<CaseLabel> {
+        let negative = self.current_token == Token::Minus;
+        if let Token::Plus | Token::Minus = &self.current_token {
+            self.advance()?;
+        }
+        if let Token::RealConstant(r) = self.current_token {
+            self.advance()?;
+            return Ok(CaseLabel::Real(if negative { -r } else { r }));
+        }
+        let lower = self.integer_constant()?;
+        let lower = if negative { -lower } else { lower };
+        if let Token::Range = &self.current_token {
+            self.advance()?;
+            let upper = self.signed_integer()?;
+            if lower > upper {
+                bail!("Case label range {}..{} is reversed", lower, upper);
+            }
+            return Ok(CaseLabel::Range(lower, upper));
+        }
+        Ok(CaseLabel::Single(lower))
+    }
+
+    /// case_statement : CASE expr OF (case_labels COLON statement SEMI?)*
+    ///                  (ELSE statement SEMI?)? END
+    /// case_labels : case_label (COMMA case_label)*
+    fn case_statement(&mut self) -> anyhow::Result<Ast> {
+        eat!(self, Token::Keyword(Keyword::Case));
+        let selector = Box::from(self.expr()?);
+        eat!(self, Token::Keyword(Keyword::Of));
+
+        let mut branches = vec![];
+        while !matches!(
+            &self.current_token,
+            Token::Keyword(Keyword::Else) | Token::Keyword(Keyword::End)
+        ) {
+            let mut labels = vec![self.case_label()?];
+            while let Token::Comma = &self.current_token {
+                self.advance()?;
+                labels.push(self.case_label()?);
+            }
+            eat!(self, Token::Colon);
+            let statement = self.statement()?;
+            branches.push(CaseBranch { labels, statement });
+            if let Token::Semi = &self.current_token {
+                self.advance()?;
+            }
+        }
+
+        let mut otherwise = None;
+        if let Token::Keyword(Keyword::Else) = &self.current_token {
+            self.advance()?;
+            otherwise = Some(Box::from(self.statement()?));
+            if let Token::Semi = &self.current_token {
+                self.advance()?;
+            }
+        }
+        eat!(self, Token::Keyword(Keyword::End));
+
+        Ok(Ast::Case {
+            selector,
+            branches,
+            otherwise,
+        })
+    }
+
+    /// while_statement : WHILE expr DO statement
+    fn while_statement(&mut self) -> anyhow::Result<Ast> {
+        eat!(self, Token::Keyword(Keyword::While));
+        let condition = Box::from(self.expr()?);
+        eat!(self, Token::Keyword(Keyword::Do));
+        Ok(Ast::While {
+            condition,
+            body: Box::from(self.statement()?),
+        })
+    }
+
+    /// if_statement : IF expr THEN statement (ELSE statement)?
+    ///
+    /// A dangling ELSE belongs to the innermost IF, so `ELSE IF` chains nest to the right
+    fn if_statement(&mut self) -> anyhow::Result<Ast> {
+        eat!(self, Token::Keyword(Keyword::If));
+        let condition = Box::from(self.expr()?);
+        eat!(self, Token::Keyword(Keyword::Then));
+        let then_branch = Box::from(self.statement()?);
+        let else_branch = if let Token::Keyword(Keyword::Else) = &self.current_token {
+            self.advance()?;
+            Some(Box::from(self.statement()?))
+        } else {
+            None
+        };
+        Ok(Ast::If {
+            condition,
+            then_branch,
+            else_branch,
+        })
+    }
+
+    /// with_statement : WITH variable DO statement
+    fn with_statement(&mut self) -> anyhow::Result<Ast> {
+        eat!(self, Token::Keyword(Keyword::With));
+        let record = self.variable()?.variable()?.clone();
+        eat!(self, Token::Keyword(Keyword::Do));
+        Ok(Ast::With {
+            record,
+            body: Box::from(self.statement()?),
+        })
     }
 
     /// statement : compound_statement
+    ///               | procedure_call_statement
     ///               | assignment_statement
+    ///               | case_statement
+    ///               | while_statement
+    ///               | if_statement
+    ///               | with_statement
     ///               | empty
     fn statement(&mut self) -> anyhow::Result<Ast> {
         match &self.current_token {
             Token::Keyword(Keyword::Begin) => self.compound_statement(),
-            Token::Identifier(_) => self.assignment_statement(),
+            Token::Keyword(Keyword::While) => self.while_statement(),
+            Token::Keyword(Keyword::If) => self.if_statement(),
+            Token::Keyword(Keyword::With) => self.with_statement(),
+            Token::Keyword(Keyword::Case) => self.case_statement(),
+            Token::Keyword(keyword @ Keyword::Goto) => bail!("'{}' is not yet supported", keyword),
+            Token::Identifier(_) => {
+                let var_node = self.variable()?;
+                match &self.current_token {
+                    // `=` is a common typo for `:=`, reported as such by assignment_statement
+                    Token::Assign | Token::Equal => self.assignment_statement(var_node),
+                    Token::Caret => bail!(POINTERS_UNSUPPORTED),
+                    Token::Dot => bail!(RECORDS_UNSUPPORTED),
+                    Token::SquareBracketStart => {
+                        let target = self.indexed_variable(var_node)?;
+                        self.assignment_statement(target)
+                    }
+                    _ => self.procedure_call_statement(var_node),
+                }
+            }
             _ => self.empty(),
         }
     }
@@ -161,6 +547,28 @@ impl<I: Iterator<Item = anyhow::Result<Token>>> Parser<I> {
             self.advance()?;
             statements.push(self.statement()?);
         }
+        // Another statement right away means the `;` separating them was forgotten
+        match &self.current_token {
+            Token::Keyword(
+                keyword @ (Keyword::Begin
+                | Keyword::While
+                | Keyword::If
+                | Keyword::With
+                | Keyword::Case
+                | Keyword::Goto),
+            ) => bail!(
+                "expected ';' between statements, found {}",
+                keyword.to_string().to_uppercase()
+            ),
+            Token::Identifier(name) => {
+                bail!("expected ';' between statements, found {}", name)
+            }
+            _ => {}
+        }
+        if statements.len() > 1 && statements.last() == Some(&Ast::NoOp) {
+            self.warnings
+                .push("Trailing semicolon before END adds an empty statement".to_string());
+        }
         Ok(statements)
     }
 
@@ -173,17 +581,74 @@ impl<I: Iterator<Item = anyhow::Result<Token>>> Parser<I> {
         Ok(Ast::Compound { statements })
     }
 
-    /// type_spec : INTEGER | REAL
+    /// type_spec : INTEGER | REAL | BOOLEAN | STRING | array_type
     fn type_spec(&mut self) -> anyhow::Result<TypeSpec> {
         let output = Ok(match &self.current_token {
             Token::Keyword(Keyword::Integer) => TypeSpec::Integer,
             Token::Keyword(Keyword::Real) => TypeSpec::Real,
-            token => bail!("Unknown type: {:?}", token),
+            Token::Keyword(Keyword::Boolean) => TypeSpec::Boolean,
+            Token::Keyword(Keyword::String) => TypeSpec::String,
+            Token::Keyword(Keyword::Array) => return self.array_type(),
+            Token::Keyword(keyword @ Keyword::Record) => {
+                bail!("'{}' is not yet supported", keyword)
+            }
+            Token::Caret => bail!(POINTERS_UNSUPPORTED),
+            Token::Identifier(name) => match self.types.get(name.as_str()) {
+                Some(type_spec) => type_spec.clone(),
+                None => bail!("Unknown type: {}", name),
+            },
+            token => bail!("Unknown type: {}", token),
         });
         self.advance()?;
         output
     }
 
+    /// array_type : ARRAY LBRACKET signed_integer RANGE signed_integer RBRACKET OF type_spec
+    fn array_type(&mut self) -> anyhow::Result<TypeSpec> {
+        eat!(self, Token::Keyword(Keyword::Array));
+        eat!(self, Token::SquareBracketStart);
+        let lower = self.signed_integer()?;
+        eat!(self, Token::Range);
+        let upper = self.signed_integer()?;
+        eat!(self, Token::SquareBracketEnd);
+        eat!(self, Token::Keyword(Keyword::Of));
+
+        if lower > upper {
+            bail!("Array bounds {}..{} are reversed", lower, upper);
+        }
+        let element_type = self.type_spec()?;
+        if let TypeSpec::Array { .. } = element_type {
+            bail!("Arrays of arrays are not supported");
+        }
+
+        Ok(TypeSpec::Array {
+            lower,
+            upper,
+            element_type: Box::from(element_type),
+        })
+    }
+
+    /// signed_integer : (PLUS | MINUS)? INTEGER_CONST
+    fn signed_integer(&mut self) -> anyhow::Result<IntegerMachineType> {
+        let negative = match &self.current_token {
+            Token::Minus => true,
+            Token::Plus => false,
+            _ => return self.integer_constant(),
+        };
+        self.advance()?;
+        let i = self.integer_constant()?;
+        Ok(if negative { -i } else { i })
+    }
+
+    fn integer_constant(&mut self) -> anyhow::Result<IntegerMachineType> {
+        if let Token::IntegerConstant(i) = self.current_token {
+            self.advance()?;
+            Ok(i)
+        } else {
+            bail!("Expected an integer constant, found {}", self.current_token)
+        }
+    }
+
     /// ID (COMMA ID)* COLON type_spec
     fn variable_declaration(&mut self) -> anyhow::Result<Vec<Ast>> {
         let mut variable_names = vec![self.variable()?];
@@ -203,16 +668,39 @@ impl<I: Iterator<Item = anyhow::Result<Token>>> Parser<I> {
         Ok(output)
     }
 
-    /// declarations : VAR (variable_declaration SEMI)+
+    /// declarations : CONST (constant_declaration SEMI)+
+    ///                | TYPE (type_declaration SEMI)+
+    ///                | VAR (variable_declaration SEMI)+
     ///                | (PROCEDURE ID (LPAREN formal_parameter_list RPAREN)? SEMI block SEMI)*
     ///                | empty
     fn declarations(&mut self) -> anyhow::Result<Vec<Ast>> {
         let mut declarations = vec![];
-        while let Token::Keyword(Keyword::Var) = &self.current_token {
+        while let Token::Keyword(Keyword::Const) = &self.current_token {
             self.advance()?;
             while let Token::Identifier(_) = &self.current_token {
+                declarations.push(self.constant_declaration()?);
+                self.end_of_declaration()?;
+            }
+        }
+        while let Token::Keyword(Keyword::Type) = &self.current_token {
+            self.advance()?;
+            loop {
+                declarations.push(self.type_declaration()?);
+                self.end_of_declaration()?;
+                if !matches!(&self.current_token, Token::Identifier(_)) {
+                    break;
+                }
+            }
+        }
+        while let Token::Keyword(Keyword::Var) = &self.current_token {
+            self.advance()?;
+            // At least one declaration, so a keyword used as the first name is reported as such
+            loop {
                 declarations.extend(self.variable_declaration()?);
-                eat!(self, Token::Semi);
+                self.end_of_declaration()?;
+                if !matches!(&self.current_token, Token::Identifier(_)) {
+                    break;
+                }
             }
         }
         while let Token::Keyword(Keyword::Procedure) = &self.current_token {
@@ -230,17 +718,46 @@ impl<I: Iterator<Item = anyhow::Result<Token>>> Parser<I> {
             eat!(self, Token::Semi);
 
             let block_node = self.block()?;
+            if let Block {
+                compound_statements,
+                ..
+            } = &block_node
+            {
+                if compound_statements.statements()? == [Ast::NoOp] {
+                    self.warnings.push(format!(
+                        "Procedure {} has an empty body",
+                        procedure_name.variable()?.name
+                    ));
+                }
+            }
             declarations.push(Ast::ProcedureDeclaration {
                 name: procedure_name.variable()?.name.clone(),
                 parameters,
                 block: Box::from(block_node),
             });
-            eat!(self, Token::Semi);
+            self.end_of_declaration()?;
         }
 
         Ok(declarations)
     }
 
+    /// Eats the `;` ending a declaration, which in lenient mode may be missing before the
+    /// `BEGIN` or `PROCEDURE` that follows
+    fn end_of_declaration(&mut self) -> anyhow::Result<()> {
+        if let Token::Keyword(keyword @ (Keyword::Begin | Keyword::Procedure)) = &self.current_token
+        {
+            if self.lenient {
+                self.warnings.push(format!(
+                    "Missing ';' before {}",
+                    keyword.to_string().to_uppercase()
+                ));
+                return Ok(());
+            }
+        }
+        eat!(self, Token::Semi);
+        Ok(())
+    }
+
     /// formal_parameter_list : formal_parameters
     ///                       | | formal_parameters SEMI formal_parameter_list
     fn formal_parameter_list(&mut self) -> anyhow::Result<Vec<Ast>> {
@@ -254,8 +771,12 @@ impl<I: Iterator<Item = anyhow::Result<Token>>> Parser<I> {
         Ok(output)
     }
 
-    /// formal_parameters : ID (COMMA ID)* COLON type_spec
+    /// formal_parameters : VAR? ID (COMMA ID)* COLON type_spec
     fn formal_parameters(&mut self) -> anyhow::Result<Vec<Ast>> {
+        let by_ref = matches!(&self.current_token, Token::Keyword(Keyword::Var));
+        if by_ref {
+            self.advance()?;
+        }
         let mut parameter_names = vec![self.variable()?];
         while let Token::Comma = &self.current_token {
             self.advance()?;
@@ -269,16 +790,59 @@ impl<I: Iterator<Item = anyhow::Result<Token>>> Parser<I> {
             .map(|variable| Ast::Parameter {
                 variable: Box::from(variable),
                 type_spec: Box::from(type_spec.to_ast_clone()),
+                by_ref,
             })
             .collect())
     }
 
+    /// constant_declaration : ID EQUAL expr
+    fn constant_declaration(&mut self) -> anyhow::Result<Ast> {
+        let name = self.variable()?.variable()?.name.clone();
+        eat!(self, Token::Equal);
+        let expression = self.expr()?;
+        let value = fold_constant(&expression, &self.constants)
+            .with_context(|| format!("Invalid value for the constant {}", name))?;
+        self.constants.insert(name.clone(), value.clone());
+        Ok(Ast::ConstantDeclaration {
+            name,
+            value: Box::from(value),
+            expression: Box::from(expression),
+        })
+    }
+
+    /// type_declaration : ID EQUAL LPAREN ID (COMMA ID)* RPAREN
+    fn type_declaration(&mut self) -> anyhow::Result<Ast> {
+        let name = self.variable()?.variable()?.name.clone();
+        eat!(self, Token::Equal);
+        eat!(self, Token::ParenthesisStart);
+        let mut variants = vec![self.variable()?.variable()?.name.clone()];
+        while let Token::Comma = &self.current_token {
+            self.advance()?;
+            variants.push(self.variable()?.variable()?.name.clone());
+        }
+        eat!(self, Token::ParenthesisEnd);
+        self.types.insert(
+            name.clone(),
+            TypeSpec::Enumeration {
+                name: name.clone(),
+                variants: variants.clone(),
+            },
+        );
+        Ok(Ast::TypeDeclaration { name, variants })
+    }
+
     /// block : declarations compound_statement
     fn block(&mut self) -> anyhow::Result<Ast> {
-        Ok(Block {
+        // Constants and types declared in this block go out of scope with it
+        let outer_constants = self.constants.clone();
+        let outer_types = self.types.clone();
+        let block = Block {
             declarations: self.declarations()?,
             compound_statements: Box::from(self.compound_statement()?),
-        })
+        };
+        self.constants = outer_constants;
+        self.types = outer_types;
+        Ok(block)
     }
 
     /// program : PROGRAM variable SEMI block DOT
@@ -301,16 +865,75 @@ impl<I: Iterator<Item = anyhow::Result<Token>>> Parser<I> {
         })
     }
 
+    /// Parses a single expression, rejecting anything after it so typos like `1 + 2 3` are
+    /// reported rather than ignored
     pub fn parse_expression(&mut self) -> anyhow::Result<Ast> {
         self.advance()?;
-        self.expr()
+        let output = self.expr()?;
+        if self.current_token != Token::Eof {
+            bail!("Unexpected {} after the expression", self.current_token);
+        }
+
+        Ok(output)
+    }
+
+    /// Parses a single statement outside of any program, e.g. `BEGIN x := 1 END`
+    pub fn parse_statement(&mut self) -> anyhow::Result<Ast> {
+        self.advance()?;
+        self.statement()
+    }
+
+    /// Parses `;`-separated statements outside of any program one at a time, as they are asked
+    /// for, so a long generated script never has to be held whole. Ends after the last statement
+    /// or the first error.
+    pub fn statements(mut self) -> impl Iterator<Item = anyhow::Result<Ast>> {
+        let mut started = false;
+        let mut finished = false;
+        std::iter::from_fn(move || {
+            if finished {
+                return None;
+            }
+            let statement = self.next_statement(!started);
+            started = true;
+            finished = statement.is_err() || self.current_token == Token::Eof;
+            Some(statement)
+        })
+    }
+
+    fn next_statement(&mut self, first: bool) -> anyhow::Result<Ast> {
+        if first {
+            self.advance()?;
+        }
+        let statement = self.statement()?;
+        if self.current_token != Token::Eof {
+            eat!(self, Token::Semi);
+        }
+        Ok(statement)
+    }
+
+    /// Ensures nothing follows what has been parsed so far, for tests parsing piece by piece
+    #[cfg(test)]
+    pub(crate) fn expect_eof(&mut self) -> anyhow::Result<()> {
+        eat!(self, Token::Eof);
+        Ok(())
     }
 
     pub fn parse(&mut self) -> anyhow::Result<Ast> {
         self.advance()?;
         let output = self.program()?;
-        eat!(self, Token::Eof);
+        if self.current_token != Token::Eof {
+            bail!(
+                "Expected the end of the file after the program, found {}",
+                self.current_token
+            );
+        }
 
         Ok(output)
     }
+
+    /// Like `parse`, but also returns non-fatal warnings about the program, e.g. stray semicolons
+    pub fn parse_with_warnings(&mut self) -> anyhow::Result<(Ast, Vec<String>)> {
+        let output = self.parse()?;
+        Ok((output, std::mem::take(&mut self.warnings)))
+    }
 }
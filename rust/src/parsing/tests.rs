@@ -1,14 +1,46 @@
+use crate::interpreting::misc::{lisp_notation, node_counts, rpn, to_pascal_source, tree_string};
 use crate::lexing::lexer::Lexer;
-use crate::lexing::token::Token;
-use crate::parsing::ast::{Ast, TypeSpec, Variable};
-use crate::parsing::parser::Parser;
+use crate::lexing::token::{Keyword, Token};
+use crate::parsing::ast::{normalize, Ast, CaseBranch, CaseLabel, SetElement, TypeSpec, Variable};
+use crate::parsing::parser::{parse_expr, parse_program, Parser, MAX_NESTING_DEPTH};
+
+/// Wraps each token in `Ok` and terminates the stream with `Token::Eof`
+fn tokens(tokens: Vec<Token>) -> impl Iterator<Item = anyhow::Result<Token>> {
+    tokens
+        .into_iter()
+        .chain(std::iter::once(Token::Eof))
+        .map(Ok)
+}
+
+#[test]
+fn test_from_tokens() -> anyhow::Result<()> {
+    let tokens = Lexer::new("PROGRAM P; VAR x : INTEGER; BEGIN x := 2 * 3 END.")
+        .take_while(|token| !matches!(token, Ok(Token::Eof)))
+        .collect::<anyhow::Result<Vec<Token>>>()?;
+    assert_eq!(
+        Parser::from_tokens(tokens).parse()?,
+        parse_program("PROGRAM P; VAR x : INTEGER; BEGIN x := 2 * 3 END.")?
+    );
+
+    let expression = Parser::from_tokens(vec![
+        Token::IntegerConstant(1),
+        Token::Plus,
+        Token::IntegerConstant(2),
+        Token::Eof,
+    ])
+    .parse_expression()?;
+    assert_eq!(
+        expression,
+        Ast::Add(Box::from(Ast::int(1)), Box::from(Ast::int(2)))
+    );
+    Ok(())
+}
 
 #[test]
 fn test_simple() -> anyhow::Result<()> {
     assert_eq!(
-        Parser::new(vec![Ok(Token::IntegerConstant(4)), Ok(Token::Eof)].into_iter())
-            .parse_expression()?,
-        Ast::IntegerConstant(4),
+        Parser::new(tokens(vec![Token::IntegerConstant(4)])).parse_expression()?,
+        Ast::int(4),
     );
     Ok(())
 }
@@ -16,20 +48,13 @@ fn test_simple() -> anyhow::Result<()> {
 #[test]
 fn test_one_operation() -> anyhow::Result<()> {
     assert_eq!(
-        Parser::new(
-            vec![
-                Ok(Token::IntegerConstant(4)),
-                Ok(Token::Plus),
-                Ok(Token::IntegerConstant(6)),
-                Ok(Token::Eof),
-            ]
-            .into_iter()
-        )
+        Parser::new(tokens(vec![
+            Token::IntegerConstant(4),
+            Token::Plus,
+            Token::IntegerConstant(6),
+        ]))
         .parse_expression()?,
-        Ast::Add(
-            Box::from(Ast::IntegerConstant(4)),
-            Box::from(Ast::IntegerConstant(6)),
-        ),
+        Ast::Add(Box::from(Ast::int(4)), Box::from(Ast::int(6)),),
     );
     Ok(())
 }
@@ -37,29 +62,22 @@ fn test_one_operation() -> anyhow::Result<()> {
 #[test]
 fn test_multiple_operations() -> anyhow::Result<()> {
     assert_eq!(
-        Parser::new(
-            vec![
-                Ok(Token::IntegerConstant(1)),
-                Ok(Token::Plus),
-                Ok(Token::IntegerConstant(2)),
-                Ok(Token::Plus),
-                Ok(Token::IntegerConstant(3)),
-                Ok(Token::Plus),
-                Ok(Token::IntegerConstant(4)),
-                Ok(Token::Eof),
-            ]
-            .into_iter()
-        )
+        Parser::new(tokens(vec![
+            Token::IntegerConstant(1),
+            Token::Plus,
+            Token::IntegerConstant(2),
+            Token::Plus,
+            Token::IntegerConstant(3),
+            Token::Plus,
+            Token::IntegerConstant(4),
+        ]))
         .parse_expression()?,
         Ast::Add(
             Box::from(Ast::Add(
-                Box::from(Ast::Add(
-                    Box::from(Ast::IntegerConstant(1)),
-                    Box::from(Ast::IntegerConstant(2)),
-                )),
-                Box::from(Ast::IntegerConstant(3)),
+                Box::from(Ast::Add(Box::from(Ast::int(1)), Box::from(Ast::int(2)),)),
+                Box::from(Ast::int(3)),
             )),
-            Box::from(Ast::IntegerConstant(4)),
+            Box::from(Ast::int(4)),
         ),
     );
     Ok(())
@@ -85,12 +103,12 @@ fn test_overriding_precedence() -> anyhow::Result<()> {
         )
         .parse_expression()?,
         Ast::Multiply(
-            Box::from(Ast::IntegerConstant(1)),
+            Box::from(Ast::int(1)),
             Box::from(Ast::Add(
-                Box::from(Ast::IntegerConstant(2)),
+                Box::from(Ast::int(2)),
                 Box::from(Ast::Multiply(
-                    Box::from(Ast::IntegerConstant(3)),
-                    Box::from(Ast::IntegerConstant(4)),
+                    Box::from(Ast::int(3)),
+                    Box::from(Ast::int(4)),
                 )),
             )),
         ),
@@ -121,65 +139,34 @@ fn test_program() -> anyhow::Result<()> {
                     statements: vec![
                         Ast::Compound {
                             statements: vec![
+                                Ast::Assign(Variable::from("number"), Box::from(Ast::int(2)),),
+                                Ast::Assign(Variable::from("a"), Box::from(Ast::var("number")),),
                                 Ast::Assign(
-                                    Variable {
-                                        name: "number".to_string()
-                                    },
-                                    Box::from(Ast::IntegerConstant(2)),
-                                ),
-                                Ast::Assign(
-                                    Variable {
-                                        name: "a".to_string()
-                                    },
-                                    Box::from(Ast::Variable(Variable {
-                                        name: "number".to_string()
-                                    })),
-                                ),
-                                Ast::Assign(
-                                    Variable {
-                                        name: "b".to_string()
-                                    },
+                                    Variable::from("b"),
                                     Box::from(Ast::Add(
                                         Box::from(Ast::Multiply(
-                                            Box::from(Ast::IntegerConstant(10)),
-                                            Box::from(Ast::Variable(Variable {
-                                                name: "a".to_string()
-                                            })),
+                                            Box::from(Ast::int(10)),
+                                            Box::from(Ast::var("a")),
                                         )),
                                         Box::from(Ast::IntegerDivide(
                                             Box::from(Ast::Multiply(
-                                                Box::from(Ast::IntegerConstant(10)),
-                                                Box::from(Ast::Variable(Variable {
-                                                    name: "number".to_string()
-                                                })),
+                                                Box::from(Ast::int(10)),
+                                                Box::from(Ast::var("number")),
                                             )),
-                                            Box::from(Ast::IntegerConstant(4)),
+                                            Box::from(Ast::int(4)),
                                         )),
                                     )),
                                 ),
                                 Ast::Assign(
-                                    Variable {
-                                        name: "c".to_string()
-                                    },
+                                    Variable::from("c"),
                                     Box::from(Ast::Subtract(
-                                        Box::from(Ast::Variable(Variable {
-                                            name: "a".to_string()
-                                        })),
-                                        Box::from(Ast::NegativeUnary(Box::from(Ast::Variable(
-                                            Variable {
-                                                name: "b".to_string()
-                                            }
-                                        )))),
+                                        Box::from(Ast::var("a")),
+                                        Box::from(Ast::NegativeUnary(Box::from(Ast::var("b")))),
                                     )),
                                 ),
                             ]
                         },
-                        Ast::Assign(
-                            Variable {
-                                name: "x".to_string()
-                            },
-                            Box::from(Ast::IntegerConstant(11)),
-                        ),
+                        Ast::Assign(Variable::from("x"), Box::from(Ast::int(11)),),
                         Ast::NoOp,
                     ]
                 }),
@@ -190,9 +177,7 @@ fn test_program() -> anyhow::Result<()> {
     Ok(())
 }
 
-#[test]
-fn test_program2() {
-    let code = r#"
+const PART10_AST: &str = r#"
             PROGRAM Part10AST;
         VAR
            a, b : INTEGER;
@@ -204,71 +189,56 @@ fn test_program2() {
            y := 20 / 7 + 3.14;
         END.  {Part10AST}
     "#;
-    let result = Parser::new(Lexer::new(code)).parse().unwrap();
+
+#[test]
+#[allow(clippy::approx_constant)]
+fn test_program2() {
+    let result = Parser::new(Lexer::new(PART10_AST)).parse().unwrap();
     assert_eq!(
         Ast::Program {
             name: "Part10AST".to_string(),
             block: Box::from(Ast::Block {
                 declarations: vec![
                     Ast::VariableDeclaration {
-                        variable: Box::from(Ast::Variable(Variable {
-                            name: "a".to_string()
-                        })),
+                        variable: Box::from(Ast::var("a")),
                         type_spec: Box::from(Ast::Type(TypeSpec::Integer))
                     },
                     Ast::VariableDeclaration {
-                        variable: Box::from(Ast::Variable(Variable {
-                            name: "b".to_string()
-                        })),
+                        variable: Box::from(Ast::var("b")),
                         type_spec: Box::from(Ast::Type(TypeSpec::Integer))
                     },
                     Ast::VariableDeclaration {
-                        variable: Box::from(Ast::Variable(Variable {
-                            name: "y".to_string()
-                        })),
+                        variable: Box::from(Ast::var("y")),
                         type_spec: Box::from(Ast::Type(TypeSpec::Real))
                     },
                 ],
                 compound_statements: Box::from(Ast::Compound {
                     statements: vec![
+                        Ast::Assign(Variable::from("a"), Box::from(Ast::int(2))),
                         Ast::Assign(
-                            Variable {
-                                name: "a".to_string()
-                            },
-                            Box::from(Ast::IntegerConstant(2))
-                        ),
-                        Ast::Assign(
-                            Variable {
-                                name: "b".to_string()
-                            },
+                            Variable::from("b"),
                             Box::from(Ast::Add(
                                 Box::from(Ast::Multiply(
-                                    Box::from(Ast::IntegerConstant(10)),
-                                    Box::from(Ast::Variable(Variable {
-                                        name: "a".to_string()
-                                    }))
+                                    Box::from(Ast::int(10)),
+                                    Box::from(Ast::var("a"))
                                 )),
                                 Box::from(Ast::IntegerDivide(
                                     Box::from(Ast::Multiply(
-                                        Box::from(Ast::IntegerConstant(10)),
-                                        Box::from(Ast::Variable(Variable {
-                                            name: "a".to_string()
-                                        }))
+                                        Box::from(Ast::int(10)),
+                                        Box::from(Ast::var("a"))
                                     )),
-                                    Box::from(Ast::IntegerConstant(4))
+                                    Box::from(Ast::int(4))
                                 ))
                             ))
                         ),
                         Ast::Assign(
-                            Variable {
-                                name: "y".to_string()
-                            },
+                            Variable::from("y"),
                             Box::from(Ast::Add(
                                 Box::from(Ast::RealDivide(
-                                    Box::from(Ast::IntegerConstant(20)),
-                                    Box::from(Ast::IntegerConstant(7))
+                                    Box::from(Ast::int(20)),
+                                    Box::from(Ast::int(7))
                                 )),
-                                Box::from(Ast::RealConstant(3.14))
+                                Box::from(Ast::real(3.14))
                             ))
                         ),
                         Ast::NoOp,
@@ -279,3 +249,1034 @@ fn test_program2() {
         result
     );
 }
+
+#[test]
+fn test_procedure_calls() -> anyhow::Result<()> {
+    let code = "PROGRAM calls; BEGIN readln; readln(); readln(a, b) END.";
+    let result = Parser::new(Lexer::new(code)).parse()?;
+
+    assert_eq!(
+        result,
+        Ast::Program {
+            name: "calls".to_string(),
+            block: Box::from(Ast::Block {
+                declarations: vec![],
+                compound_statements: Box::from(Ast::Compound {
+                    statements: vec![
+                        Ast::ProcedureCall {
+                            name: "readln".to_string(),
+                            arguments: vec![],
+                        },
+                        Ast::ProcedureCall {
+                            name: "readln".to_string(),
+                            arguments: vec![],
+                        },
+                        Ast::ProcedureCall {
+                            name: "readln".to_string(),
+                            arguments: vec![Ast::var("a"), Ast::var("b"),],
+                        },
+                    ]
+                }),
+            }),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_comments_after_final_dot() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Trailing;
+        BEGIN
+        END.  {Trailing}
+
+        { a second comment }
+        {
+            and one spanning
+            several lines
+        }
+
+    "#;
+    let result = Parser::new(Lexer::new(code)).parse()?;
+
+    assert_eq!(
+        result,
+        Ast::Program {
+            name: "Trailing".to_string(),
+            block: Box::from(Ast::Block {
+                declarations: vec![],
+                compound_statements: Box::from(Ast::Compound {
+                    statements: vec![Ast::NoOp]
+                }),
+            }),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_tokens_after_final_dot() {
+    let code = r#"
+        PROGRAM Trailing;
+        BEGIN
+        END.  {Trailing}
+        x := 1
+    "#;
+
+    assert!(Parser::new(Lexer::new(code))
+        .parse()
+        .expect_err("Expected the trailing assignment to be rejected")
+        .to_string()
+        .contains("Expected the end of the file after the program, found x"));
+}
+
+#[test]
+fn test_indexed_assignment_and_calls() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Arrays;
+        VAR a : ARRAY[1..10] OF INTEGER;
+        BEGIN
+            a[2] := 7;
+            p(1)
+        END.
+    "#;
+    let result = Parser::new(Lexer::new(code)).parse()?;
+
+    assert_eq!(
+        result,
+        Ast::Program {
+            name: "Arrays".to_string(),
+            block: Box::from(Ast::Block {
+                declarations: vec![Ast::VariableDeclaration {
+                    variable: Box::from(Ast::var("a")),
+                    type_spec: Box::from(Ast::Type(TypeSpec::Array {
+                        lower: 1,
+                        upper: 10,
+                        element_type: Box::from(TypeSpec::Integer),
+                    })),
+                }],
+                compound_statements: Box::from(Ast::Compound {
+                    statements: vec![
+                        Ast::IndexedAssign {
+                            array: Variable::from("a"),
+                            index: Box::from(Ast::int(2)),
+                            value: Box::from(Ast::int(7)),
+                        },
+                        Ast::ProcedureCall {
+                            name: "p".to_string(),
+                            arguments: vec![Ast::int(1)],
+                        },
+                    ]
+                }),
+            }),
+        }
+    );
+    Ok(())
+}
+
+#[test]
+fn test_final_dot_without_trailing_newline() -> anyhow::Result<()> {
+    let fixtures = [
+        include_str!("../../tests/fixtures/end_dot_no_newline.pas"),
+        include_str!("../../tests/fixtures/end_dot_comment_no_newline.pas"),
+    ];
+    for code in fixtures {
+        assert!(!code.ends_with('\n'));
+        let result = Parser::new(Lexer::new(code)).parse()?;
+
+        assert_eq!(
+            result,
+            Ast::Program {
+                name: "NoNewline".to_string(),
+                block: Box::from(Ast::Block {
+                    declarations: vec![Ast::VariableDeclaration {
+                        variable: Box::from(Ast::var("x")),
+                        type_spec: Box::from(Ast::Type(TypeSpec::Integer)),
+                    }],
+                    compound_statements: Box::from(Ast::Compound {
+                        statements: vec![Ast::Assign(Variable::from("x"), Box::from(Ast::int(1)))]
+                    }),
+                }),
+            }
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn test_parse_with_warnings() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Warnings;
+        VAR x : INTEGER;
+        PROCEDURE Nothing;
+        BEGIN
+        END;
+        BEGIN
+            x := 1;
+        END.
+    "#;
+    let (result, warnings) = Parser::new(Lexer::new(code)).parse_with_warnings()?;
+
+    assert_eq!(result, Parser::new(Lexer::new(code)).parse()?);
+    assert_eq!(
+        warnings,
+        vec![
+            "Procedure Nothing has an empty body".to_string(),
+            "Trailing semicolon before END adds an empty statement".to_string(),
+        ]
+    );
+
+    let (_, warnings) =
+        Parser::new(Lexer::new("PROGRAM Clean; BEGIN END.")).parse_with_warnings()?;
+    assert!(warnings.is_empty());
+    Ok(())
+}
+
+#[test]
+fn test_type_declarations() -> anyhow::Result<()> {
+    let code = "PROGRAM Types;
+        TYPE Color = (Red, Green, Blue); Answer = (Yes, No);
+        VAR c : Color; a : ARRAY[1..2] OF Answer;
+        BEGIN c := Blue END.";
+    let ast = parse_program(code)?;
+    let color = TypeSpec::Enumeration {
+        name: "Color".to_string(),
+        variants: vec!["Red".to_string(), "Green".to_string(), "Blue".to_string()],
+    };
+    match &ast {
+        Ast::Program { block, .. } => match block.as_ref() {
+            Ast::Block { declarations, .. } => {
+                assert_eq!(
+                    declarations[0],
+                    Ast::TypeDeclaration {
+                        name: "Color".to_string(),
+                        variants: vec!["Red".to_string(), "Green".to_string(), "Blue".to_string()],
+                    }
+                );
+                assert_eq!(
+                    declarations[2],
+                    Ast::VariableDeclaration {
+                        variable: Box::from(Ast::var("c")),
+                        type_spec: Box::from(Ast::Type(color)),
+                    }
+                );
+                assert_eq!(
+                    declarations[3].children()[1].type_spec()?.to_string(),
+                    "ARRAY[1..2] OF Answer"
+                );
+            }
+            block => panic!("Expected a block, was {:?}", block),
+        },
+        node => panic!("Expected a program, was {:?}", node),
+    }
+    assert_eq!(parse_program(&to_pascal_source(&ast))?, ast);
+
+    // Types go out of scope with the block declaring them
+    assert_eq!(
+        parse_program(
+            "PROGRAM P;
+            PROCEDURE Q; TYPE Inner = (A); BEGIN END;
+            PROCEDURE R; VAR i : Inner; BEGIN END;
+            BEGIN END."
+        )
+        .unwrap_err()
+        .to_string(),
+        "Unknown type: Inner"
+    );
+    assert_eq!(
+        parse_program("PROGRAM P; TYPE Empty = (); BEGIN END.")
+            .unwrap_err()
+            .to_string(),
+        "Expected a variable, found )"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_lenient_declarations() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Lenient;
+        VAR x : INTEGER
+        PROCEDURE Set;
+        CONST one = 1
+        BEGIN
+            x := one
+        END
+        BEGIN
+            Set
+        END.
+    "#;
+    assert_eq!(
+        parse_program(code).unwrap_err().to_string(),
+        "Expected ;, found procedure"
+    );
+
+    let mut parser = Parser::new(Lexer::new(code));
+    parser.lenient = true;
+    let (result, warnings) = parser.parse_with_warnings()?;
+    assert_eq!(
+        result,
+        parse_program(
+            "PROGRAM Lenient;
+            VAR x : INTEGER;
+            PROCEDURE Set; CONST one = 1; BEGIN x := one END;
+            BEGIN Set END."
+        )?
+    );
+    assert_eq!(
+        warnings,
+        vec![
+            "Missing ';' before PROCEDURE".to_string(),
+            "Missing ';' before BEGIN".to_string(),
+            "Missing ';' before BEGIN".to_string(),
+        ]
+    );
+
+    // Only the `;` right before BEGIN or PROCEDURE may be left out
+    let mut parser = Parser::new(Lexer::new(
+        "PROGRAM P; VAR x : INTEGER y : REAL; BEGIN END.",
+    ));
+    parser.lenient = true;
+    assert_eq!(
+        parser.parse().unwrap_err().to_string(),
+        "Expected ;, found y"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_normalize_ignores_trailing_semicolons() -> anyhow::Result<()> {
+    let with_semicolons = r#"
+        PROGRAM Semicolons;
+        PROCEDURE p;
+        BEGIN
+            BEGIN ; END;
+        END;
+        BEGIN
+            p();
+            p();
+        END.
+    "#;
+    let without_semicolons = r#"
+        PROGRAM Semicolons;
+        PROCEDURE p;
+        BEGIN
+            BEGIN END
+        END;
+        BEGIN
+            p();
+            p()
+        END.
+    "#;
+    let with_semicolons = Parser::new(Lexer::new(with_semicolons)).parse()?;
+    let without_semicolons = Parser::new(Lexer::new(without_semicolons)).parse()?;
+    assert_ne!(with_semicolons, without_semicolons);
+
+    assert_eq!(normalize(with_semicolons), normalize(without_semicolons));
+    Ok(())
+}
+
+#[test]
+fn test_signed_array_bounds_and_case_labels() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Signed;
+        VAR a : ARRAY[-5..+5] OF REAL;
+        BEGIN
+            CASE a[-5] OF
+                -1, +2: ;
+                3: a[0] := 1
+            END
+        END.
+    "#;
+    let result = Parser::new(Lexer::new(code)).parse()?;
+
+    assert_eq!(
+        result,
+        Ast::Program {
+            name: "Signed".to_string(),
+            block: Box::from(Ast::Block {
+                declarations: vec![Ast::VariableDeclaration {
+                    variable: Box::from(Ast::var("a")),
+                    type_spec: Box::from(Ast::Type(TypeSpec::Array {
+                        lower: -5,
+                        upper: 5,
+                        element_type: Box::from(TypeSpec::Real),
+                    })),
+                }],
+                compound_statements: Box::from(Ast::Compound {
+                    statements: vec![Ast::Case {
+                        selector: Box::from(Ast::Index {
+                            array: Variable::from("a"),
+                            index: Box::from(Ast::NegativeUnary(Box::from(Ast::int(5)))),
+                        }),
+                        branches: vec![
+                            CaseBranch {
+                                labels: vec![CaseLabel::Single(-1), CaseLabel::Single(2)],
+                                statement: Ast::NoOp,
+                            },
+                            CaseBranch {
+                                labels: vec![CaseLabel::Single(3)],
+                                statement: Ast::IndexedAssign {
+                                    array: Variable::from("a"),
+                                    index: Box::from(Ast::int(0)),
+                                    value: Box::from(Ast::int(1)),
+                                },
+                            },
+                        ],
+                        otherwise: None,
+                    }]
+                }),
+            }),
+        }
+    );
+
+    assert!(Parser::new(Lexer::new(
+        "PROGRAM Reversed; VAR a : ARRAY[1..-1] OF INTEGER; BEGIN END."
+    ))
+    .parse()
+    .is_err());
+    Ok(())
+}
+
+#[test]
+fn test_case_label_ranges() -> anyhow::Result<()> {
+    let mut parser = Parser::new(Lexer::new("CASE x OF -3..3, 7: y := 1 END"));
+    match parser.parse_statement()? {
+        Ast::Case { branches, .. } => assert_eq!(
+            branches[0].labels,
+            vec![CaseLabel::Range(-3, 3), CaseLabel::Single(7)]
+        ),
+        node => panic!("Expected a CASE statement, was {:?}", node),
+    }
+
+    assert_eq!(
+        parse_program("PROGRAM Reversed; BEGIN CASE 1 OF 5..1: END END.")
+            .unwrap_err()
+            .to_string(),
+        "Case label range 5..1 is reversed"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_node_counts() -> anyhow::Result<()> {
+    let result = Parser::new(Lexer::new(PART10_AST)).parse()?;
+
+    assert_eq!(
+        node_counts(&result).into_iter().collect::<Vec<_>>(),
+        vec![
+            ("Add", 2),
+            ("Assign", 3),
+            ("Block", 1),
+            ("Compound", 1),
+            ("IntegerConstant", 6),
+            ("IntegerDivide", 1),
+            ("Multiply", 2),
+            ("NoOp", 1),
+            ("Program", 1),
+            ("RealConstant", 1),
+            ("RealDivide", 1),
+            ("Type", 3),
+            ("Variable", 5),
+            ("VariableDeclaration", 3),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_children() -> anyhow::Result<()> {
+    let addition = parse_expr("1 + x")?;
+    assert_eq!(addition.children(), [&Ast::int(1), &Ast::var("x")]);
+    assert!(Ast::int(1).children().is_empty());
+
+    let program = parse_program(
+        "PROGRAM P;
+        VAR a, b : INTEGER;
+        PROCEDURE Show(x : INTEGER; VAR y : REAL);
+        BEGIN writeln(x) END;
+        BEGIN a := 1 END.",
+    )?;
+    let block = program.children()[0];
+    // Two variable declarations, the procedure and the compound statement
+    assert_eq!(block.children().len(), 4);
+    let procedure = block.children()[2];
+    assert_eq!(procedure.kind(), "ProcedureDeclaration");
+    // Two parameters and the body
+    assert_eq!(procedure.children().len(), 3);
+    Ok(())
+}
+
+#[test]
+fn test_clone_parsed_tree() -> anyhow::Result<()> {
+    let result = Parser::new(Lexer::new(PART10_AST)).parse()?;
+    let copy = result.clone();
+    assert_eq!(copy, result);
+
+    let normalized = normalize(copy);
+    assert_ne!(normalized, result);
+    Ok(())
+}
+
+#[test]
+fn test_parse_from_source() -> anyhow::Result<()> {
+    assert_eq!(
+        parse_program(PART10_AST)?,
+        Parser::new(Lexer::new(PART10_AST)).parse()?
+    );
+    assert_eq!(
+        parse_program("PROGRAM Empty; BEGIN END.")?,
+        Ast::Program {
+            name: "Empty".to_string(),
+            block: Box::from(Ast::Block {
+                declarations: vec![],
+                compound_statements: Box::from(Ast::Compound {
+                    statements: vec![Ast::NoOp]
+                }),
+            }),
+        }
+    );
+
+    assert_eq!(
+        parse_expr("1 + 2")?,
+        Ast::Add(Box::from(Ast::int(1)), Box::from(Ast::int(2)))
+    );
+    assert_eq!(
+        parse_expr("1 + 2 3").unwrap_err().to_string(),
+        "Unexpected 3 after the expression"
+    );
+    assert_eq!(
+        Parser::new(Lexer::new("1 + 2 garbage"))
+            .parse_expression()
+            .unwrap_err()
+            .to_string(),
+        "Unexpected garbage after the expression"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_nesting_depth_is_bounded() -> anyhow::Result<()> {
+    let nested = |depth: usize, open: &str, close: &str| {
+        format!("{}1{}", open.repeat(depth), close.repeat(depth))
+    };
+
+    let error = parse_expr(&nested(20_000, "(", ")")).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "maximum expression nesting depth exceeded"
+    );
+    assert!(parse_expr(&nested(20_000, "-", "")).is_err());
+    assert!(parse_expr(&nested(20_000, "abs(", ")")).is_err());
+
+    parse_expr(&nested(MAX_NESTING_DEPTH, "(", ")"))?;
+    assert!(parse_expr(&nested(MAX_NESTING_DEPTH + 1, "(", ")")).is_err());
+    Ok(())
+}
+
+#[test]
+fn test_var_parameters() -> anyhow::Result<()> {
+    let program = parse_program(
+        "PROGRAM P; PROCEDURE Swap(VAR a, b : INTEGER; c : REAL); BEGIN END; BEGIN END.",
+    )?;
+    let parameters = match &program {
+        Ast::Program { block, .. } => match block.as_ref() {
+            Ast::Block { declarations, .. } => match &declarations[0] {
+                Ast::ProcedureDeclaration { parameters, .. } => parameters.clone(),
+                node => panic!("Expected a procedure declaration, was {:?}", node),
+            },
+            node => panic!("Expected a block, was {:?}", node),
+        },
+        node => panic!("Expected a program, was {:?}", node),
+    };
+
+    let parameter = |name: &str, type_spec: TypeSpec, by_ref: bool| Ast::Parameter {
+        variable: Box::from(Ast::var(name)),
+        type_spec: Box::from(Ast::Type(type_spec)),
+        by_ref,
+    };
+    assert_eq!(
+        parameters,
+        vec![
+            parameter("a", TypeSpec::Integer, true),
+            parameter("b", TypeSpec::Integer, true),
+            parameter("c", TypeSpec::Real, false),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_errors_spell_tokens_as_written() {
+    let error = |code: &str| parse_program(code).unwrap_err().to_string();
+
+    assert_eq!(
+        error("PROGRAM P; BEGIN writeln(x) = 1 END."),
+        "Expected end, found ="
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN a[1 := 2 END."),
+        "Expected ], found :="
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN x := 1 div END."),
+        "Expected integer, parenthesis, or variable instead of end"
+    );
+    assert_eq!(
+        error("PROGRAM P; VAR x INTEGER; BEGIN END."),
+        "Expected :, found integer"
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN END"),
+        "Expected ., found end of file"
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN x := (1 'a' END."),
+        "Expected ), found 'a'"
+    );
+
+    assert_eq!(Token::Keyword(Keyword::IntegerDiv).to_string(), "div");
+    assert_eq!(Token::ParenthesisStart.to_string(), "(");
+    assert_eq!(Token::IntegerConstant(4).to_string(), "4");
+    assert_eq!(Token::RealConstant(2.0).to_string(), "2.0");
+    assert_eq!(
+        Token::StringConstant("it's".to_string()).to_string(),
+        "'it''s'"
+    );
+}
+
+#[test]
+fn test_statements_of_a_program_body() -> anyhow::Result<()> {
+    let program = parse_program("PROGRAM P; BEGIN x := 1; writeln(x); END.")?;
+    let body = match &program {
+        Ast::Program { block, .. } => match block.as_ref() {
+            Ast::Block {
+                compound_statements,
+                ..
+            } => compound_statements.statements()?,
+            node => panic!("Expected a block, was {:?}", node),
+        },
+        node => panic!("Expected a program, was {:?}", node),
+    };
+
+    assert_eq!(
+        body.iter().map(Ast::kind).collect::<Vec<_>>(),
+        vec!["Assign", "ProcedureCall", "NoOp"]
+    );
+    assert!(program.statements().is_err());
+    Ok(())
+}
+
+#[test]
+fn test_tree_string() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Outline;
+        VAR
+            a : ARRAY[1..3] OF REAL;
+        PROCEDURE Show(VAR x : INTEGER);
+        BEGIN
+            writeln('x', x)
+        END;
+        BEGIN
+            a[2] := -1.5 * 2;
+            CASE 2 OF
+                1, 3..4: Show(n)
+            ELSE
+            END
+        END.
+    "#;
+    let expected = "\
+Program Outline
+  Block
+    VariableDeclaration
+      Variable a
+      Type ARRAY[1..3] OF REAL
+    ProcedureDeclaration Show
+      Parameter VAR
+        Variable x
+        Type INTEGER
+      Block
+        Compound
+          ProcedureCall writeln
+            StringConstant 'x'
+            Variable x
+    Compound
+      IndexedAssign a
+        IntegerConstant 2
+        Multiply
+          NegativeUnary
+            RealConstant 1.5
+          IntegerConstant 2
+      Case
+        IntegerConstant 2
+        Branch 1, 3..4
+          ProcedureCall Show
+            Variable n
+        Else
+          NoOp
+";
+    assert_eq!(tree_string(&parse_program(code)?, 0), expected);
+    assert_eq!(
+        tree_string(&parse_expr("1 + x")?, 1),
+        "  Add\n    IntegerConstant 1\n    Variable x\n"
+    );
+    Ok(())
+}
+
+#[test]
+fn test_in_operator() -> anyhow::Result<()> {
+    let x = || Box::from(Ast::var("x"));
+    assert_eq!(
+        parse_expr("x in [1, 3, 5]")?,
+        Ast::In {
+            value: x(),
+            set: vec![
+                SetElement::Single(Ast::int(1)),
+                SetElement::Single(Ast::int(3)),
+                SetElement::Single(Ast::int(5)),
+            ],
+        }
+    );
+    assert_eq!(
+        parse_expr("x + 1 IN [1..10, -2]")?,
+        Ast::In {
+            value: Box::from(Ast::Add(x(), Box::from(Ast::int(1)))),
+            set: vec![
+                SetElement::Range(Ast::int(1), Ast::int(10)),
+                SetElement::Single(Ast::NegativeUnary(Box::from(Ast::int(2)))),
+            ],
+        }
+    );
+    assert_eq!(
+        parse_expr("x in []")?,
+        Ast::In {
+            value: x(),
+            set: vec![],
+        }
+    );
+    assert!(parse_expr("x in 1").is_err());
+    assert!(parse_expr("x in [1,]").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_parse_statement() -> anyhow::Result<()> {
+    let mut parser = Parser::new(Lexer::new("BEGIN x := 1 END"));
+    assert_eq!(
+        parser.parse_statement()?,
+        Ast::Compound {
+            statements: vec![Ast::Assign(Variable::from("x"), Box::from(Ast::int(1)))]
+        }
+    );
+    parser.expect_eof()?;
+
+    let mut parser = Parser::new(Lexer::new("WHILE x < 3 DO x := x + 1"));
+    assert_eq!(parser.parse_statement()?.kind(), "While");
+    Ok(())
+}
+
+#[test]
+fn test_constants_are_folded() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Constants;
+        CONST
+            Greeting = 'Hello, ' + 'World';
+            Answer = 6 * 7;
+            Half = -Answer / 84;
+            Shout = Greeting + '!';
+            Truncated = 7.0 div 2;
+        BEGIN
+        END.
+    "#;
+    let declarations = match parse_program(code)? {
+        Ast::Program { block, .. } => match *block {
+            Ast::Block { declarations, .. } => declarations,
+            node => panic!("Expected a block, was {:?}", node),
+        },
+        node => panic!("Expected a program, was {:?}", node),
+    };
+    let folded: Vec<(&str, &Ast)> = declarations
+        .iter()
+        .map(|declaration| match declaration {
+            Ast::ConstantDeclaration { name, value, .. } => (name.as_str(), value.as_ref()),
+            node => panic!("Expected a constant, was {:?}", node),
+        })
+        .collect();
+    assert_eq!(
+        folded,
+        vec![
+            ("Greeting", &Ast::StringConstant("Hello, World".to_string())),
+            ("Answer", &Ast::int(42)),
+            ("Half", &Ast::real(-0.5)),
+            ("Shout", &Ast::StringConstant("Hello, World!".to_string())),
+            ("Truncated", &Ast::int(3)),
+        ]
+    );
+    Ok(())
+}
+
+#[test]
+fn test_constants_print_as_written() -> anyhow::Result<()> {
+    let code = "PROGRAM Derived;
+CONST
+    Limit = 10;
+    Half = Limit / 4;
+    Mask = -(Limit * 2) DIV 3;
+BEGIN
+    writeln(Half)
+END.
+";
+    assert_eq!(to_pascal_source(&parse_program(code)?), code);
+    Ok(())
+}
+
+#[test]
+fn test_constants_reject_non_constant_operands() {
+    let error = |code: &str| format!("{:#}", parse_program(code).unwrap_err());
+
+    assert_eq!(
+        error("PROGRAM P; CONST Greeting = 'Hello, ' + name; VAR name : STRING; BEGIN END."),
+        "Invalid value for the constant Greeting: name is not a constant"
+    );
+    assert_eq!(
+        error("PROGRAM P; CONST Mixed = 'a' + 1; BEGIN END."),
+        "Invalid value for the constant Mixed: Cannot Add StringConstant(\"a\") and IntegerConstant(1)"
+    );
+    assert!(error("PROGRAM P; CONST Call = abs(1); BEGIN END.").contains("FunctionCall"));
+    assert!(error("PROGRAM P; CONST Big = 2147483647 + 1; BEGIN END.").contains("out of range"));
+    assert_eq!(
+        error("PROGRAM P; CONST X = 5 div 0; BEGIN END."),
+        "Invalid value for the constant X: integer division (div) by zero"
+    );
+    assert_eq!(
+        error("PROGRAM P; CONST X = 5 mod 0; BEGIN END."),
+        "Invalid value for the constant X: modulo (mod) by zero"
+    );
+    // Constants go out of scope with the procedure declaring them
+    assert!(error(
+        "PROGRAM P; PROCEDURE Q; CONST Inner = 1; BEGIN END; PROCEDURE R; CONST Outer = Inner; BEGIN END; BEGIN END."
+    )
+    .contains("Inner is not a constant"));
+}
+
+#[test]
+fn test_keywords_are_not_variable_names() {
+    let error = |code: &str| parse_program(code).unwrap_err().to_string();
+
+    assert_eq!(
+        error("PROGRAM P; VAR end : INTEGER; BEGIN end := 1 END."),
+        "'end' is a reserved keyword and cannot be used as a variable name"
+    );
+    assert_eq!(
+        error("PROGRAM P; VAR x, Begin : INTEGER; BEGIN END."),
+        "'begin' is a reserved keyword and cannot be used as a variable name"
+    );
+    assert_eq!(
+        error("PROGRAM div; BEGIN END."),
+        "'div' is a reserved keyword and cannot be used as a variable name"
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN x := 1 + ; END."),
+        "Expected integer, parenthesis, or variable instead of ;"
+    );
+}
+
+#[test]
+fn test_missing_semicolon_between_statements() {
+    let error = |code: &str| parse_program(code).unwrap_err().to_string();
+
+    assert_eq!(
+        error("PROGRAM P; BEGIN BEGIN a := 1 END BEGIN b := 2 END END."),
+        "expected ';' between statements, found BEGIN"
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN a := 1\n b := 2 END."),
+        "expected ';' between statements, found b"
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN a := 1 WHILE a < 2 DO a := 2 END."),
+        "expected ';' between statements, found WHILE"
+    );
+    assert!(parse_program("PROGRAM P; BEGIN BEGIN a := 1 END; BEGIN b := 2 END END.").is_ok());
+}
+
+#[test]
+fn test_equals_sign_instead_of_assignment() {
+    let error = |code: &str| parse_program(code).unwrap_err().to_string();
+    let hint = "did you mean ':=' (assignment) instead of '=' (comparison)?";
+
+    assert_eq!(error("PROGRAM P; VAR x : INTEGER; BEGIN x = 5 END."), hint);
+    assert_eq!(
+        error("PROGRAM P; VAR a : ARRAY[1..2] OF INTEGER; BEGIN a[1] = 5 END."),
+        hint
+    );
+    assert!(parse_program("PROGRAM P; VAR x : INTEGER; BEGIN x := 5 END.").is_ok());
+}
+
+#[test]
+fn test_unsupported_keywords() {
+    let error = |code: &str| parse_program(code).unwrap_err().to_string();
+
+    assert_eq!(
+        error("PROGRAM P; VAR p : RECORD x : INTEGER END; BEGIN END."),
+        "'record' is not yet supported"
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN GoTo 10 END."),
+        "'goto' is not yet supported"
+    );
+    assert_eq!(
+        error("PROGRAM P; VAR p : ^INTEGER; BEGIN END."),
+        "pointers are not yet supported"
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN p^ := 1 END."),
+        "pointers are not yet supported"
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN x := p^ + 1 END."),
+        "pointers are not yet supported"
+    );
+    assert_eq!(
+        error("PROGRAM P; BEGIN p := @x END."),
+        "pointers are not yet supported"
+    );
+    assert_eq!(
+        error("PROGRAM P; VAR goto : INTEGER; BEGIN END."),
+        "'goto' is a reserved keyword and cannot be used as a variable name"
+    );
+}
+
+#[test]
+fn test_rpn_and_lisp_notation() -> anyhow::Result<()> {
+    let notations = |code: &str| -> anyhow::Result<(String, String)> {
+        let expression = parse_expr(code)?;
+        Ok((rpn(&expression), lisp_notation(&expression)))
+    };
+
+    assert_eq!(
+        notations("-3.14")?,
+        ("-3.14".to_string(), "-3.14".to_string())
+    );
+    assert_eq!(notations("-7")?, ("-7".to_string(), "-7".to_string()));
+    assert_eq!(
+        notations("-0.1234567890123456")?,
+        (
+            "-0.1234567890123456".to_string(),
+            "-0.1234567890123456".to_string()
+        )
+    );
+    assert_eq!(
+        notations("2 * -1.5")?,
+        ("2 -1.5 *".to_string(), "(* 2 -1.5)".to_string())
+    );
+    assert_eq!(
+        notations("-(1 + 2)")?,
+        ("0 1 2 + -".to_string(), "(- (+ 1 2))".to_string())
+    );
+    assert_eq!(
+        notations("- -4")?,
+        ("0 -4 -".to_string(), "(- -4)".to_string())
+    );
+    assert_eq!(
+        notations("NOT a OR b AND c")?,
+        (
+            "a not b c and or".to_string(),
+            "(or (not a) (and b c))".to_string()
+        )
+    );
+    assert_eq!(
+        notations("2 ** 3 ** 2")?,
+        ("2 3 2 ** **".to_string(), "(** 2 (** 3 2))".to_string())
+    );
+    assert_eq!(
+        notations("7 div 2 / 4")?,
+        ("7 2 / 4 /".to_string(), "(/ (/ 7 2) 4)".to_string())
+    );
+    assert_eq!(
+        notations("a[i + 1] * 2")?,
+        (
+            "a i 1 + [] 2 *".to_string(),
+            "(* (index a (+ i 1)) 2)".to_string()
+        )
+    );
+    Ok(())
+}
+
+#[test]
+fn test_else_if_chain() -> anyhow::Result<()> {
+    let program = parse_program(
+        "PROGRAM P; BEGIN IF x < 0 THEN y := 1 ELSE IF x = 0 THEN y := 2 ELSE y := 3 END.",
+    )?;
+    let statements = match program {
+        Ast::Program { block, .. } => match *block {
+            Ast::Block {
+                compound_statements,
+                ..
+            } => compound_statements.statements()?.to_vec(),
+            block => panic!("Expected a block, was {:?}", block),
+        },
+        program => panic!("Expected a program, was {:?}", program),
+    };
+
+    let variable = |name: &str| Box::from(Ast::var(name));
+    let assign_y = |value| Box::from(Ast::Assign(Variable::from("y"), Box::from(Ast::int(value))));
+    assert_eq!(
+        statements,
+        [Ast::If {
+            condition: Box::from(Ast::LessThan(variable("x"), Box::from(Ast::int(0)))),
+            then_branch: assign_y(1),
+            else_branch: Some(Box::from(Ast::If {
+                condition: Box::from(Ast::Equal(variable("x"), Box::from(Ast::int(0)))),
+                then_branch: assign_y(2),
+                else_branch: Some(assign_y(3)),
+            })),
+        }]
+    );
+
+    // A dangling ELSE belongs to the innermost IF
+    let nested = parse_program("PROGRAM P; BEGIN IF a THEN IF b THEN y := 1 ELSE y := 2 END.")?;
+    let tree = tree_string(&nested, 0);
+    assert!(
+        tree.contains("      If\n        Variable a\n        If\n          Variable b\n"),
+        "{}",
+        tree
+    );
+    assert!(parse_program("PROGRAM P; BEGIN IF a y := 1 END.").is_err());
+    Ok(())
+}
+
+#[test]
+fn test_with_statement() -> anyhow::Result<()> {
+    let mut parser = Parser::new(Lexer::new("WITH r DO x := 1"));
+    assert_eq!(
+        parser.parse_statement()?,
+        Ast::With {
+            record: Variable::from("r"),
+            body: Box::from(Ast::Assign(Variable::from("x"), Box::from(Ast::int(1)))),
+        }
+    );
+
+    let mut parser = Parser::new(Lexer::new("WITH r DO BEGIN x := 1; y := 2 END"));
+    match parser.parse_statement()? {
+        Ast::With { record, body } => {
+            assert_eq!(record.name, "r");
+            assert_eq!(body.statements()?.len(), 2);
+        }
+        node => panic!("Expected a WITH statement, was {:?}", node),
+    }
+
+    assert!(Parser::new(Lexer::new("WITH 1 DO x := 1"))
+        .parse_statement()
+        .is_err());
+
+    // Qualified field names wait for RECORD types
+    assert_eq!(
+        Parser::new(Lexer::new("WITH r DO r.x := 1"))
+            .parse_statement()
+            .unwrap_err()
+            .to_string(),
+        "record fields are not yet supported"
+    );
+    Ok(())
+}
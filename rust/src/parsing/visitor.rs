@@ -0,0 +1,120 @@
+use crate::parsing::ast::{Ast, CaseBranch, TypeSpec, Variable};
+use anyhow::Result;
+
+/// Walks a syntax tree through `Ast::accept`, one method per kind of statement or declaration.
+/// Every method visits the nested nodes in source order by default, so an implementation like a
+/// linter only overrides the nodes it cares about. The `Interpreter` is one such visitor.
+pub trait AstVisitor {
+    fn visit_program(&mut self, _name: &str, block: &Ast) -> Result<()> {
+        block.accept(self)
+    }
+
+    fn visit_block(&mut self, declarations: &[Ast], compound_statements: &Ast) -> Result<()> {
+        for declaration in declarations {
+            declaration.accept(self)?;
+        }
+        compound_statements.accept(self)
+    }
+
+    fn visit_procedure_declaration(
+        &mut self,
+        _name: &str,
+        parameters: &[Ast],
+        block: &Ast,
+    ) -> Result<()> {
+        for parameter in parameters {
+            parameter.accept(self)?;
+        }
+        block.accept(self)
+    }
+
+    fn visit_parameter(&mut self, variable: &Ast, type_spec: &Ast, _by_ref: bool) -> Result<()> {
+        variable.accept(self)?;
+        type_spec.accept(self)
+    }
+
+    fn visit_variable_declaration(&mut self, variable: &Ast, type_spec: &Ast) -> Result<()> {
+        variable.accept(self)?;
+        type_spec.accept(self)
+    }
+
+    fn visit_constant_declaration(&mut self, _name: &str, value: &Ast) -> Result<()> {
+        value.accept(self)
+    }
+
+    fn visit_type_declaration(&mut self, _name: &str, _variants: &[String]) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_type(&mut self, _type_spec: &TypeSpec) -> Result<()> {
+        Ok(())
+    }
+
+    fn visit_compound(&mut self, statements: &[Ast]) -> Result<()> {
+        for statement in statements {
+            statement.accept(self)?;
+        }
+        Ok(())
+    }
+
+    fn visit_assign(&mut self, _variable: &Variable, value: &Ast) -> Result<()> {
+        value.accept(self)
+    }
+
+    fn visit_indexed_assign(&mut self, _array: &Variable, index: &Ast, value: &Ast) -> Result<()> {
+        index.accept(self)?;
+        value.accept(self)
+    }
+
+    fn visit_procedure_call(&mut self, _name: &str, arguments: &[Ast]) -> Result<()> {
+        for argument in arguments {
+            argument.accept(self)?;
+        }
+        Ok(())
+    }
+
+    fn visit_while(&mut self, condition: &Ast, body: &Ast) -> Result<()> {
+        condition.accept(self)?;
+        body.accept(self)
+    }
+
+    fn visit_with(&mut self, _record: &Variable, body: &Ast) -> Result<()> {
+        body.accept(self)
+    }
+
+    fn visit_if(
+        &mut self,
+        condition: &Ast,
+        then_branch: &Ast,
+        else_branch: Option<&Ast>,
+    ) -> Result<()> {
+        condition.accept(self)?;
+        then_branch.accept(self)?;
+        else_branch.map_or(Ok(()), |else_branch| else_branch.accept(self))
+    }
+
+    fn visit_case(
+        &mut self,
+        selector: &Ast,
+        branches: &[CaseBranch],
+        otherwise: Option<&Ast>,
+    ) -> Result<()> {
+        selector.accept(self)?;
+        for branch in branches {
+            branch.statement.accept(self)?;
+        }
+        otherwise.map_or(Ok(()), |otherwise| otherwise.accept(self))
+    }
+
+    fn visit_no_op(&mut self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Any node computing a value, from literals and variables to operators and function calls
+    fn visit_expression(&mut self, expression: &Ast) -> Result<()> {
+        for child in expression.children() {
+            child.accept(self)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,313 @@
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+fn run_spi(arguments: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_spi"))
+        .args(arguments)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .output()
+        .expect("could not run spi")
+}
+
+fn run_spi_with_input(arguments: &[&str], input: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_spi"))
+        .args(arguments)
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("could not run spi");
+    child
+        .stdin
+        .take()
+        .expect("stdin is piped")
+        .write_all(input.as_bytes())
+        .expect("could not write to spi");
+    child.wait_with_output().expect("could not run spi")
+}
+
+#[test]
+fn test_program_output_precedes_variables() {
+    let output = run_spi(&["tests/fixtures/writeln.pas"]);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let program_output = stdout.find("21142\n").expect("Expected the writeln output");
+    let variables = stdout
+        .find("Variables:")
+        .expect("Expected the variables table");
+    assert!(program_output < variables, "{}", stdout);
+}
+
+#[test]
+fn test_halt_sets_the_exit_code() {
+    let output = run_spi(&["tests/fixtures/halt.pas"]);
+    assert_eq!(output.status.code(), Some(3));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Variables:"), "{}", stdout);
+    assert!(!stdout.contains("99"), "{}", stdout);
+
+    assert_eq!(
+        run_spi(&["tests/fixtures/writeln.pas"]).status.code(),
+        Some(0)
+    );
+    assert_eq!(
+        run_spi(&["tests/fixtures/missing.pas"]).status.code(),
+        Some(1)
+    );
+}
+
+#[test]
+fn test_source_encoding() {
+    let output = run_spi(&["tests/fixtures/latin1.pas"]);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("is not valid UTF-8 (at byte 80); please re-encode it"),
+        "{}",
+        stderr
+    );
+
+    let output = run_spi(&["tests/fixtures/latin1.pas", "--encoding", "latin1"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("caf\u{e9}\n"), "{}", stdout);
+}
+
+#[test]
+fn test_output_file() -> std::io::Result<()> {
+    let path = std::env::temp_dir().join(format!("spi-output-{}.txt", std::process::id()));
+    let output = run_spi(&[
+        "tests/fixtures/writeln.pas",
+        "--output",
+        &path.to_string_lossy(),
+    ]);
+    assert!(output.status.success());
+    let written = std::fs::read_to_string(&path)?;
+    std::fs::remove_file(&path)?;
+    assert!(written.starts_with("21142\n"), "{}", written);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(!stdout.contains("21142\n"), "{}", stdout);
+
+    let output = run_spi(&[
+        "tests/fixtures/writeln.pas",
+        "--output",
+        "tests/fixtures/missing/output.txt",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("could not create output file"),
+        "{}",
+        stderr
+    );
+    Ok(())
+}
+
+#[test]
+fn test_interactive_after_file() {
+    let output = run_spi_with_input(
+        &["tests/fixtures/writeln.pas", "--interactive"],
+        "answer * 1000 + 1\n",
+    );
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let variables = stdout
+        .find("Variables:")
+        .expect("Expected the variables table");
+    let result = stdout.find("42001").expect("Expected the REPL result");
+    assert!(variables < result, "{}", stdout);
+}
+
+#[test]
+fn test_profile_in_the_repl() {
+    let output = run_spi_with_input(&["--profile"], "1 + 2 * 3\n");
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let result = stdout.find("Result").expect("Expected the REPL result");
+    let profile = stdout.find("Profile:").expect("Expected the profile");
+    assert!(result < profile, "{}", stdout);
+    assert!(stdout.contains("Multiply"), "{}", stdout);
+}
+
+#[test]
+fn test_failing_assert() {
+    let output = run_spi(&["tests/fixtures/assert.pas"]);
+    assert_eq!(output.status.code(), Some(5));
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with("42\n"), "{}", stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("Assertion failed: x is not negative"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_ast_equal() {
+    let output = run_spi(&[
+        "--ast-equal",
+        "tests/fixtures/writeln.pas",
+        "tests/fixtures/writeln_reformatted.pas",
+    ]);
+    assert_eq!(output.status.code(), Some(0));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "The ASTs are equal\n"
+    );
+
+    let output = run_spi(&[
+        "--ast-equal",
+        "tests/fixtures/writeln.pas",
+        "tests/fixtures/writeln_changed.pas",
+    ]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "The ASTs differ at Program Greeting > Block > Compound > Assign answer > Multiply > \
+         IntegerConstant 7 vs IntegerConstant 8\n"
+    );
+}
+
+#[test]
+fn test_step() {
+    let output = run_spi_with_input(&["--step", "tests/fixtures/step.pas"], "\n\nc\n");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Next: a := 1\n"), "{}", stdout);
+    assert!(stdout.contains("Next: b := a + 1\n  a = 1\n"), "{}", stdout);
+    assert!(
+        stdout.contains("Next: writeln(b)\n  a = 1\n  b = 2\n"),
+        "{}",
+        stdout
+    );
+    assert!(!stdout.contains("Next: a := 10"), "{}", stdout);
+    assert!(stdout.contains("> 2\n"), "{}", stdout);
+
+    let output = run_spi_with_input(&["--step", "tests/fixtures/step.pas"], "\nq\n");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("Next: b := a + 1\n"), "{}", stdout);
+    assert!(!stdout.contains("Next: writeln(b)"), "{}", stdout);
+    assert!(!stdout.contains("> 2\n"), "{}", stdout);
+}
+
+#[test]
+fn test_step_with_readln() {
+    let output = run_spi_with_input(&["--step", "tests/fixtures/step_readln.pas"], "\n21\n\n");
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Next: writeln(n * 2)\n  n = 21\n"),
+        "{}",
+        stdout
+    );
+    assert!(stdout.contains("> 42\n"), "{}", stdout);
+}
+
+#[test]
+fn test_exit_code_per_failing_phase() {
+    let failure = |fixture: &str| {
+        let output = run_spi(&[fixture]);
+        (
+            output.status.code(),
+            String::from_utf8_lossy(&output.stderr).into_owned(),
+        )
+    };
+
+    let (code, stderr) = failure("tests/fixtures/lex_error.pas");
+    assert_eq!(code, Some(2), "{}", stderr);
+    assert!(stderr.contains("Could not lex the program"), "{}", stderr);
+    let (code, stderr) = failure("tests/fixtures/parse_error.pas");
+    assert_eq!(code, Some(3), "{}", stderr);
+    assert!(stderr.contains("Could not parse the program"), "{}", stderr);
+    let (code, stderr) = failure("tests/fixtures/undeclared.pas");
+    assert_eq!(code, Some(4), "{}", stderr);
+    let (code, stderr) = failure("tests/fixtures/division_by_zero.pas");
+    assert_eq!(code, Some(5), "{}", stderr);
+    assert!(
+        stderr.contains("integer division (div) by zero at line 6, column 12"),
+        "{}",
+        stderr
+    );
+}
+
+#[test]
+fn test_highlight() {
+    let source = std::fs::read_to_string("tests/fixtures/writeln.pas").unwrap();
+
+    let output = run_spi(&["--highlight", "--no-color", "tests/fixtures/writeln.pas"]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), source);
+
+    let output = Command::new(env!("CARGO_BIN_EXE_spi"))
+        .args(["--highlight", "tests/fixtures/writeln.pas"])
+        .current_dir(env!("CARGO_MANIFEST_DIR"))
+        .env("NO_COLOR", "1")
+        .output()
+        .expect("could not run spi");
+    assert_eq!(String::from_utf8_lossy(&output.stdout), source);
+}
+
+#[test]
+fn test_fmt() -> std::io::Result<()> {
+    let path = std::env::temp_dir().join(format!("spi-fmt-{}.pas", std::process::id()));
+    let path_argument = path.to_string_lossy().to_string();
+    let unformatted = std::fs::read_to_string("tests/fixtures/unformatted.pas")?;
+    std::fs::write(&path, &unformatted)?;
+
+    let output = run_spi(&["--fmt", "--check", &path_argument]);
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        format!("{} is not formatted\n", path_argument)
+    );
+    assert_eq!(std::fs::read_to_string(&path)?, unformatted);
+
+    assert!(run_spi(&["--fmt", &path_argument]).status.success());
+    let formatted = std::fs::read_to_string(&path)?;
+    assert_eq!(
+        formatted,
+        "PROGRAM Ugly;
+VAR
+    x     : INTEGER;
+    total : INTEGER;
+    r     : REAL;
+PROCEDURE Show(a : INTEGER);
+    BEGIN
+        writeln(a)
+    END;
+BEGIN
+    x := 1;
+    total := x + 2 * 3;
+    r := x / 2;
+    IF x > 0 THEN
+        Show(x)
+    ELSE
+        BEGIN
+            Show(total)
+        END
+END.
+"
+    );
+
+    // Formatting again changes nothing
+    assert!(run_spi(&["--fmt", "--check", &path_argument])
+        .status
+        .success());
+    assert!(run_spi(&["--fmt", &path_argument]).status.success());
+    assert_eq!(std::fs::read_to_string(&path)?, formatted);
+
+    std::fs::write(&path, "PROGRAM P; { keep me } BEGIN END.")?;
+    let output = run_spi(&["--fmt", &path_argument]);
+    std::fs::remove_file(&path)?;
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("its comments would be lost"));
+    Ok(())
+}
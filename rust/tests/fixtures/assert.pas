@@ -0,0 +1,10 @@
+PROGRAM Assertions;
+VAR
+    x : INTEGER;
+BEGIN
+    x := 6 * 7;
+    assert(x = 42);
+    writeln(x);
+    assert(x < 0, 'x is not negative');
+    writeln(0)
+END.
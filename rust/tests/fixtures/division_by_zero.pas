@@ -0,0 +1,7 @@
+PROGRAM DivisionByZero;
+VAR
+    x : INTEGER;
+BEGIN
+    x := 0;
+    x := 1 div x
+END.
@@ -0,0 +1,5 @@
+PROGRAM NoNewline;
+VAR x : INTEGER;
+BEGIN
+    x := 1
+END.
\ No newline at end of file
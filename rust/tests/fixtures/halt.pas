@@ -0,0 +1,8 @@
+PROGRAM Halting;
+VAR
+    x : INTEGER;
+BEGIN
+    x := 1;
+    halt(x + 2);
+    x := 99
+END.
@@ -0,0 +1,5 @@
+PROGRAM Latin1;
+{ Saved as ISO-8859-1 rather than UTF-8 }
+BEGIN
+    writeln('caf')
+END.
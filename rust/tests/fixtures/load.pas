@@ -0,0 +1,8 @@
+PROGRAM Load;
+VAR
+    i, total : INTEGER;
+BEGIN
+    total := 0;
+    i := 5;
+    total := i + i + i
+END.
@@ -0,0 +1,12 @@
+PROGRAM LoadArray;
+VAR
+    squares : ARRAY[1..3] OF INTEGER;
+    i : INTEGER;
+BEGIN
+    i := 1;
+    WHILE i <= 3 DO
+    BEGIN
+        squares[i] := i * i;
+        i := i + 1
+    END
+END.
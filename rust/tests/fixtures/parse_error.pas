@@ -0,0 +1,4 @@
+PROGRAM Broken;
+BEGIN
+    x := 1 +
+END.
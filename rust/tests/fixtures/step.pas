@@ -0,0 +1,9 @@
+PROGRAM Step;
+VAR
+    a, b : INTEGER;
+BEGIN
+    a := 1;
+    b := a + 1;
+    writeln(b);
+    a := 10
+END.
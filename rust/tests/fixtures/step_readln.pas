@@ -0,0 +1,7 @@
+PROGRAM StepReadln;
+VAR
+    n : INTEGER;
+BEGIN
+    readln(n);
+    writeln(n * 2)
+END.
@@ -0,0 +1,4 @@
+PROGRAM Undeclared;
+BEGIN
+    x := 1
+END.
@@ -0,0 +1,5 @@
+program   Ugly ;var x,total:integer; r:real;
+procedure   Show(a:integer);begin writeln(a) end;
+BEGIN x:=1;total:=x+2*3 ; r:=x/2;
+if x>0 then Show(x) else begin Show(total) end;
+END.
@@ -0,0 +1,9 @@
+PROGRAM Greeting;
+VAR
+   answer : INTEGER;
+
+BEGIN {Greeting}
+   answer := 6 * 7;
+   write(answer div 2, 1);
+   writeln(answer)
+END.  {Greeting}
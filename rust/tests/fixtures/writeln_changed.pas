@@ -0,0 +1,9 @@
+PROGRAM Greeting;
+VAR
+   answer : INTEGER;
+
+BEGIN
+   answer := 6 * 8;
+   write(answer div 2, 1);
+   writeln(answer)
+END.
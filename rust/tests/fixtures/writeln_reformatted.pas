@@ -0,0 +1,5 @@
+program Greeting; var answer: integer;
+begin
+  answer:=6*7; write(answer div 2,1);
+  writeln( answer );
+end.
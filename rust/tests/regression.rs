@@ -0,0 +1,90 @@
+//! Runs every program in `tests/regression` and compares what it writes, followed by its
+//! variables and any error, with the `.expected` file beside it. Adding a case is a matter of
+//! dropping in a `.pas` file; `UPDATE_EXPECTED=1 cargo test --test regression` writes its
+//! `.expected` file, to be checked by hand before committing.
+
+use spi::interpreting::interpreter::Interpreter;
+use spi::lexing::lexer::Lexer;
+use spi::parsing::parser::Parser;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+
+#[derive(Clone, Default)]
+struct SharedOutput(Arc<Mutex<Vec<u8>>>);
+
+impl Write for SharedOutput {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// The program's output, then its variables sorted by name, then the error it failed with if any
+fn report(source: &str) -> String {
+    let output = SharedOutput::default();
+    let mut interpreter = Interpreter::new(false);
+    interpreter.output = Box::new(output.clone());
+    let result = Parser::new(Lexer::new(source))
+        .parse()
+        .and_then(|ast| interpreter.interpret(&ast));
+
+    let mut report = String::from_utf8_lossy(&output.0.lock().unwrap()).to_string();
+    report.push_str("--- variables\n");
+    let variables: BTreeMap<String, String> = interpreter
+        .global_scope
+        .iter()
+        .map(|(name, value)| (name.to_string(), value.to_string()))
+        .collect();
+    for (name, value) in variables {
+        report.push_str(&format!("{} = {}\n", name, value));
+    }
+    if let Err(err) = result {
+        report.push_str(&format!("--- error\n{}\n", err));
+    }
+    report
+}
+
+#[test]
+fn test_regression_fixtures() -> std::io::Result<()> {
+    let directory = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/regression");
+    let mut programs: Vec<_> = std::fs::read_dir(directory)?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<std::io::Result<_>>()?;
+    programs.retain(|path| path.extension().is_some_and(|extension| extension == "pas"));
+    programs.sort();
+    assert!(!programs.is_empty(), "no regression programs found");
+
+    let update = std::env::var_os("UPDATE_EXPECTED").is_some();
+    let mut failures = vec![];
+    for program in programs {
+        let actual = report(&std::fs::read_to_string(&program)?);
+        let expected_path = program.with_extension("expected");
+        if update {
+            std::fs::write(&expected_path, &actual)?;
+            continue;
+        }
+        match std::fs::read_to_string(&expected_path) {
+            Ok(expected) if expected == actual => {}
+            Ok(expected) => failures.push(format!(
+                "{}:\n--- expected\n{}--- actual\n{}",
+                program.display(),
+                expected,
+                actual
+            )),
+            Err(err) => failures.push(format!(
+                "{}: cannot read {} ({}), the program reported:\n{}",
+                program.display(),
+                expected_path.display(),
+                err,
+                actual
+            )),
+        }
+    }
+    assert!(failures.is_empty(), "{}", failures.join("\n"));
+    Ok(())
+}
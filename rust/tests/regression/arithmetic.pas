@@ -0,0 +1,14 @@
+PROGRAM Arithmetic;
+VAR
+    a, b, quotient, remainder : INTEGER;
+    ratio, power : REAL;
+BEGIN
+    a := 20;
+    b := 7;
+    quotient := a DIV b;
+    remainder := a MOD b;
+    ratio := a / b;
+    power := 2 ** 0.5;
+    writeln(quotient, ' ', remainder);
+    writeln(-a DIV b, ' ', 2 ** 10, ' ', 1 + 2 * 3 - 4)
+END.
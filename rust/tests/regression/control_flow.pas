@@ -0,0 +1,34 @@
+PROGRAM ControlFlow;
+VAR n, steps, total : INTEGER;
+    kind : STRING;
+BEGIN
+    { Collatz steps from 27 }
+    n := 27;
+    steps := 0;
+    WHILE n <> 1 DO
+    BEGIN
+        IF n MOD 2 = 0 THEN
+            n := n DIV 2
+        ELSE
+            n := 3 * n + 1;
+        steps := steps + 1
+    END;
+    writeln('steps: ', steps);
+
+    total := 0;
+    n := 1;
+    WHILE (n <= 10) AND NOT (total > 20) DO
+    BEGIN
+        total := total + n;
+        n := n + 1
+    END;
+    writeln('total: ', total);
+
+    CASE steps MOD 3 OF
+        0: kind := 'fizz';
+        1, 2: kind := 'buzz'
+    ELSE
+        kind := 'none'
+    END;
+    writeln(kind)
+END.
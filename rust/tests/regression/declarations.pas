@@ -0,0 +1,29 @@
+PROGRAM Declarations;
+CONST
+    Limit = 10;
+    Half = Limit / 4;
+VAR
+    count : INTEGER;
+    name : STRING;
+    done : BOOLEAN;
+    squares : ARRAY[1..3] OF INTEGER;
+
+PROCEDURE Fill(start : INTEGER);
+VAR i : INTEGER;
+BEGIN
+    i := 1;
+    WHILE i <= 3 DO
+    BEGIN
+        squares[i] := (start + i) * (start + i);
+        i := i + 1
+    END
+END;
+
+BEGIN
+    count := Limit;
+    name := 'it''s';
+    done := count > Half;
+    Fill(1);
+    writeln(name, ' ', done, ' ', Half);
+    writeln(squares)
+END.
@@ -0,0 +1,8 @@
+PROGRAM DivisionByZero;
+VAR a, b : INTEGER;
+BEGIN
+    a := 1;
+    writeln('before');
+    b := a DIV (a - 1);
+    writeln('after')
+END.
@@ -0,0 +1,6 @@
+PROGRAM EmptyBody;
+VAR
+    x : INTEGER;
+    r : REAL;
+BEGIN
+END.
@@ -0,0 +1,58 @@
+use proptest::prelude::*;
+use spi::interpreting::misc::to_pascal_source;
+use spi::lexing::lexer::Lexer;
+use spi::parsing::ast::Ast;
+use spi::parsing::parser::Parser;
+
+const VARIABLES: &[&str] = &["a", "b", "total", "x1", "_tmp"];
+
+fn parse(code: &str) -> anyhow::Result<Ast> {
+    Parser::new(Lexer::new(code)).parse()
+}
+
+/// Integer arithmetic over the declared variables, built from the same operators the parser knows
+fn expression() -> impl Strategy<Value = String> {
+    let leaf = prop_oneof![
+        (0..=i32::MAX).prop_map(|i| i.to_string()),
+        prop::sample::select(VARIABLES).prop_map(str::to_string),
+    ];
+    leaf.prop_recursive(4, 32, 2, |inner| {
+        prop_oneof![
+            (
+                inner.clone(),
+                prop::sample::select(&["+", "-", "*", "div", "MOD", "**"][..]),
+                inner.clone()
+            )
+                .prop_map(|(l, operator, r)| format!("{} {} {}", l, operator, r)),
+            inner.clone().prop_map(|e| format!("({})", e)),
+            (prop::sample::select(&["-", "+"][..]), inner)
+                .prop_map(|(sign, e)| format!("{}{}", sign, e)),
+        ]
+    })
+}
+
+fn assignment() -> impl Strategy<Value = String> {
+    (prop::sample::select(VARIABLES), expression())
+        .prop_map(|(variable, value)| format!("{} := {}", variable, value))
+}
+
+fn program() -> impl Strategy<Value = String> {
+    prop::collection::vec(assignment(), 0..8).prop_map(|statements| {
+        format!(
+            "PROGRAM Generated;\nVAR {} : INTEGER;\nBEGIN\n{}\nEND.",
+            VARIABLES.join(", "),
+            statements.join(";\n")
+        )
+    })
+}
+
+proptest! {
+    #[test]
+    fn test_pretty_printing_round_trips(code in program()) {
+        let original = parse(&code).unwrap();
+        let printed = to_pascal_source(&original);
+        let reparsed = parse(&printed)
+            .unwrap_or_else(|e| panic!("{}\nwhile parsing:\n{}", e, printed));
+        prop_assert_eq!(original, reparsed, "printed as:\n{}", printed);
+    }
+}
@@ -0,0 +1,64 @@
+use spi::lexing::lexer::Lexer;
+use spi::parsing::ast::{Ast, Variable};
+use spi::parsing::parser::Parser;
+use spi::parsing::visitor::AstVisitor;
+
+/// Counts the assignments in a program, relying on the default methods to reach nested ones
+#[derive(Default)]
+struct AssignmentCounter {
+    assignments: usize,
+}
+
+impl AstVisitor for AssignmentCounter {
+    fn visit_assign(&mut self, _variable: &Variable, value: &Ast) -> anyhow::Result<()> {
+        self.assignments += 1;
+        value.accept(self)
+    }
+
+    fn visit_indexed_assign(
+        &mut self,
+        _array: &Variable,
+        index: &Ast,
+        value: &Ast,
+    ) -> anyhow::Result<()> {
+        self.assignments += 1;
+        index.accept(self)?;
+        value.accept(self)
+    }
+}
+
+#[test]
+fn test_counting_assignments() -> anyhow::Result<()> {
+    let code = r#"
+        PROGRAM Counting;
+        VAR
+            i : INTEGER;
+            squares : ARRAY[1..3] OF INTEGER;
+
+        PROCEDURE Reset(VAR n : INTEGER);
+        BEGIN
+            n := 0
+        END;
+
+        BEGIN
+            i := 1;
+            WHILE i <= 3 DO
+            BEGIN
+                squares[i] := i * i;
+                CASE i OF
+                    2: writeln(i)
+                ELSE
+                    i := i
+                END;
+                i := i + 1
+            END;
+            Reset(i)
+        END.
+    "#;
+    let program = Parser::new(Lexer::new(code)).parse()?;
+
+    let mut counter = AssignmentCounter::default();
+    program.accept(&mut counter as &mut dyn AstVisitor)?;
+    assert_eq!(counter.assignments, 5);
+    Ok(())
+}